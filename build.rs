@@ -0,0 +1,17 @@
+fn main() {
+    // Brings the header for the C interface up to date with the functions in src/ffi.rs.
+    #[cfg(feature = "header")]
+    {
+        println!("cargo:rerun-if-changed=src/ffi.rs");
+        println!("cargo:rerun-if-changed=cbindgen.toml");
+        let dir = std::path::PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").expect("cargo should set the manifest directory"));
+        let config = cbindgen::Config::from_file(dir.join("cbindgen.toml")).expect("cbindgen.toml should be valid");
+        // Only the interface is read: the rest of the crate has nothing to export.
+        cbindgen::Builder::new()
+            .with_config(config)
+            .with_src(dir.join("src/ffi.rs"))
+            .generate()
+            .expect("the C header should generate")
+            .write_to_file(dir.join("include/qawale.h"));
+    }
+}
@@ -0,0 +1,171 @@
+/* The C interface to the Qawale engine. Generated from src/ffi.rs by cbindgen; do not edit. */
+
+#ifndef QAWALE_H
+#define QAWALE_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/*
+ The result of a game, from [`qawale_game_outcome`].
+ */
+typedef enum QawaleOutcome {
+  /*
+   The game goes on.
+   */
+  QAWALE_OUTCOME_ONGOING,
+  /*
+   The player to move has no stones left.
+   */
+  QAWALE_OUTCOME_DRAW,
+  /*
+   Red topped a line.
+   */
+  QAWALE_OUTCOME_RED_WINS,
+  /*
+   White topped a line.
+   */
+  QAWALE_OUTCOME_WHITE_WINS,
+  /*
+   Blue topped a line.
+   */
+  QAWALE_OUTCOME_BLUE_WINS,
+  /*
+   Green topped a line.
+   */
+  QAWALE_OUTCOME_GREEN_WINS,
+} QawaleOutcome;
+
+/*
+ A game in progress, owned by the caller between [`qawale_game_new`] and [`qawale_game_free`].
+ */
+typedef struct QawaleGame QawaleGame;
+
+/*
+ The best move found by [`qawale_game_search`].
+ */
+typedef struct QawaleSearchResult {
+  /*
+   The move in compact notation, to be freed with [`qawale_string_free`]. Null if the game is over.
+   */
+  char *best_move;
+  /*
+   The score for the player to move.
+   */
+  int32_t score;
+  /*
+   The deepest search that finished.
+   */
+  uint8_t depth;
+  /*
+   The positions searched.
+   */
+  uint64_t nodes;
+} QawaleSearchResult;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/*
+ The message for the last error on this thread, or null if there has been none. The message
+ belongs to the library and lasts until the next error on the thread.
+ */
+const char *qawale_last_error(void);
+
+/*
+ A new game from the starting position, played by `rules`, e.g. "standard" or
+ "three:diagonals=no". Null rules are the standard rules. Returns null if the rules are invalid.
+
+ # Safety
+ `rules` must be null or a nul-terminated string.
+ */
+struct QawaleGame *qawale_game_new(const char *rules);
+
+/*
+ A new game from a position string, e.g. "nn,,,nn/,,,/,,,/nn,,,nn W", played by `rules` as in
+ [`qawale_game_new`]. Returns null if either is invalid.
+
+ # Safety
+ `position` must be a nul-terminated string, and `rules` null or one.
+ */
+struct QawaleGame *qawale_game_from_position(const char *position, const char *rules);
+
+/*
+ Frees a game. Null is ignored.
+
+ # Safety
+ `game` must be null or a game from this library not already freed.
+ */
+void qawale_game_free(struct QawaleGame *game);
+
+/*
+ Frees a string returned by this library. Null is ignored.
+
+ # Safety
+ `text` must be null or a string from this library not already freed.
+ */
+void qawale_string_free(char *text);
+
+/*
+ Plays a move in compact notation, e.g. "a1rrd". Returns 0, or -1 if the move is not legal, leaving
+ the game as it was.
+
+ # Safety
+ `game` must be a game from this library and `notation` a nul-terminated string.
+ */
+int32_t qawale_game_apply_move(struct QawaleGame *game, const char *notation);
+
+/*
+ The legal moves in compact notation, separated by spaces, with their number written to `count`
+ if it is not null. The string is empty once the game is over. Returns null if `game` is.
+
+ # Safety
+ `game` must be a game from this library, and `count` null or valid to write.
+ */
+char *qawale_game_legal_moves(const struct QawaleGame *game, size_t *count);
+
+/*
+ The position string for the game. Returns null if `game` is.
+
+ # Safety
+ `game` must be a game from this library.
+ */
+char *qawale_game_position(const struct QawaleGame *game);
+
+/*
+ The letter of the player to move, e.g. 'W', or 0 if `game` is null.
+
+ # Safety
+ `game` must be a game from this library.
+ */
+char qawale_game_to_move(const struct QawaleGame *game);
+
+/*
+ Whether the game is over and who won. A null game is reported as ongoing.
+
+ # Safety
+ `game` must be a game from this library.
+ */
+enum QawaleOutcome qawale_game_outcome(const struct QawaleGame *game);
+
+/*
+ Searches for the best move to at most `max_depth` moves ahead, stopping early after `time_ms`
+ milliseconds unless it is 0. Returns 0 and fills in `result`, or -1 if either pointer is null.
+
+ # Safety
+ `game` must be a game from this library and `result` valid to write.
+ */
+int32_t qawale_game_search(const struct QawaleGame *game,
+                           uint8_t max_depth,
+                           uint32_t time_ms,
+                           struct QawaleSearchResult *result);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* QAWALE_H */
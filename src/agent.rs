@@ -0,0 +1,336 @@
+//! Agents, which choose the moves in a game, and the specs the command line builds them from.
+use crate::book::OpeningBook;
+use crate::error::Error;
+use crate::evaluator::{Evaluator, LineEvaluator};
+use crate::game_state::{GameState, Move, Outcome};
+use crate::mcts::Mcts;
+use crate::multiplayer::{MultiSearch, Strategy};
+use crate::nn::Mlp;
+use crate::search::Search;
+use crate::tactics::Threats;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use std::io::{self, BufRead, Write};
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// An Agent is anything that can pick moves in a game: an engine, a heuristic or a person.
+pub trait Agent {
+    /// A short name for the agent in match results and records, e.g. "search-d4".
+    fn name(&self) -> String;
+
+    /// Picks a move for the player to move. The game must not be over.
+    fn choose_move(&mut self, state: &GameState) -> Move;
+
+    /// Called after any move is played, by either player, with the state before the move.
+    fn notify_move(&mut self, _state: &GameState, _mv: &Move) {}
+
+    /// Called once the game has finished.
+    fn game_over(&mut self, _state: &GameState, _outcome: Outcome) {}
+}
+
+/// AgentSpec is a parsed agent spec of the form `kind[:key=value,...]`, for example "greedy",
+/// "search:depth=4" or "mcts:iterations=2000,weights=net.txt". Search and MCTS agents use the
+/// LineEvaluator unless given a network weights file. The max-n and paranoid agents search games of
+/// more than two players. Any agent given an opening book plays from
+/// it while it has moves for the position.
+///
+/// The network and book files are read once, when the spec is parsed, and shared by every agent
+/// built from it.
+#[derive(Clone)]
+pub struct AgentSpec {
+    kind: String,
+    depth: u8,
+    iterations: u32,
+    network: Option<Arc<Mlp>>,
+    book: Option<Arc<OpeningBook>>,
+}
+
+impl AgentSpec {
+    /// Parses a spec, reading any network or book file it names.
+    pub fn parse(spec: &str) -> Result<Self, Error> {
+        let (kind, options) = spec.split_once(':').unwrap_or((spec, ""));
+        let mut depth = 3;
+        let mut iterations = 1000;
+        let mut weights = None;
+        let mut book = None;
+        for option in options.split(',').filter(|o| !o.is_empty()) {
+            let (key, value) = option.split_once('=').ok_or_else(|| Error::Parse(format!("invalid option {option:?} in {spec:?}")))?;
+            let invalid = |_| Error::Parse(format!("invalid value for {key} in {spec:?}"));
+            match key {
+                "depth" => depth = value.parse().map_err(invalid)?,
+                "iterations" => iterations = value.parse().map_err(invalid)?,
+                "weights" => weights = Some(value),
+                "book" => book = Some(value),
+                _ => return Err(Error::Parse(format!("unknown option {key:?} in {spec:?}"))),
+            }
+        }
+        if !KINDS.contains(&kind) {
+            return Err(Error::Parse(format!("unknown agent {kind:?}")));
+        }
+        Ok(AgentSpec{
+            kind: kind.to_string(),
+            depth,
+            iterations,
+            network: weights.map(|path| Mlp::load(path).map(Arc::new)).transpose()?,
+            book: book.map(|path| OpeningBook::load(path).map(Arc::new)).transpose()?,
+        })
+    }
+
+    /// Checks the agent can play a game of `players` players: the search and MCTS agents assume
+    /// every other player is an opponent to beat, so only play two player games.
+    pub fn check_players(&self, players: usize) -> Result<(), Error> {
+        if players > 2 && matches!(self.kind.as_str(), "search" | "mcts") {
+            return Err(Error::Invalid(format!("{} agents only play two player games, use max-n or paranoid", self.kind)));
+        }
+        Ok(())
+    }
+
+    /// Whether the agent asks a person for its moves.
+    pub fn is_human(&self) -> bool {
+        self.kind == "human"
+    }
+
+    /// A new agent playing as the spec says, with its own random moves from `seed`.
+    pub fn build(&self, seed: u64) -> Box<dyn Agent> {
+        let evaluator = || -> Box<dyn Evaluator> {
+            match &self.network {
+                Some(network) => Box::new(network.clone()),
+                None => Box::new(LineEvaluator),
+            }
+        };
+        let agent: Box<dyn Agent> = match self.kind.as_str() {
+            "random" => Box::new(RandomAgent::new(seed)),
+            "greedy" => Box::new(GreedyAgent::new(seed)),
+            "search" => Box::new(SearchAgent::new(self.depth, evaluator())),
+            "mcts" => Box::new(MctsAgent::new(self.iterations, evaluator())),
+            "max-n" | "paranoid" => Box::new(MultiSearchAgent::new(self.kind.parse().expect("the kind was checked when parsing"), self.depth)),
+            _ => Box::new(HumanAgent),
+        };
+        match &self.book {
+            Some(book) => Box::new(BookAgent::new(book.clone(), agent, seed)),
+            None => agent,
+        }
+    }
+}
+
+impl FromStr for AgentSpec {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        AgentSpec::parse(s)
+    }
+}
+
+// The kinds of agent a spec can name.
+const KINDS: [&str; 7] = ["random", "greedy", "search", "mcts", "max-n", "paranoid", "human"];
+
+/// Builds an agent from a spec, as described for AgentSpec.
+pub fn agent_from_spec(spec: &str, seed: u64) -> Result<Box<dyn Agent>, Error> {
+    Ok(AgentSpec::parse(spec)?.build(seed))
+}
+
+/// BookAgent plays weighted random moves from an opening book, and defers to another agent once
+/// the game leaves the book.
+pub struct BookAgent {
+    book: Arc<OpeningBook>,
+    inner: Box<dyn Agent>,
+    rng: StdRng,
+}
+
+impl BookAgent {
+    /// Plays from the book, then as `inner`, with its own random moves from `seed`.
+    pub fn new(book: Arc<OpeningBook>, inner: Box<dyn Agent>, seed: u64) -> Self {
+        BookAgent{book, inner, rng: StdRng::seed_from_u64(seed)}
+    }
+}
+
+impl Agent for BookAgent {
+    fn name(&self) -> String {
+        format!("{}+book", self.inner.name())
+    }
+
+    fn choose_move(&mut self, state: &GameState) -> Move {
+        match self.book.pick(state, &mut self.rng) {
+            Some(mv) => mv,
+            None => self.inner.choose_move(state),
+        }
+    }
+
+    fn notify_move(&mut self, state: &GameState, mv: &Move) {
+        self.inner.notify_move(state, mv)
+    }
+
+    fn game_over(&mut self, state: &GameState, outcome: Outcome) {
+        self.inner.game_over(state, outcome)
+    }
+}
+
+/// RandomAgent plays uniformly random legal moves.
+pub struct RandomAgent {
+    rng: StdRng,
+}
+
+impl RandomAgent {
+    /// An agent with its own random moves from `seed`.
+    pub fn new(seed: u64) -> Self {
+        RandomAgent{rng: StdRng::seed_from_u64(seed)}
+    }
+}
+
+impl Agent for RandomAgent {
+    fn name(&self) -> String {
+        "random".to_string()
+    }
+
+    fn choose_move(&mut self, state: &GameState) -> Move {
+        *state.legal_moves().choose(&mut self.rng).expect("game should not be over")
+    }
+}
+
+/// GreedyAgent looks one move ahead: it takes an immediate win if there is one, otherwise plays a
+/// random move that doesn't give the opponent a line or a winning reply.
+pub struct GreedyAgent {
+    rng: StdRng,
+}
+
+impl GreedyAgent {
+    /// An agent with its own random moves from `seed`.
+    pub fn new(seed: u64) -> Self {
+        GreedyAgent{rng: StdRng::seed_from_u64(seed)}
+    }
+}
+
+impl Agent for GreedyAgent {
+    fn name(&self) -> String {
+        "greedy".to_string()
+    }
+
+    fn choose_move(&mut self, state: &GameState) -> Move {
+        let threats = Threats::find(state);
+        if let Some(mv) = threats.winning.first() {
+            return *mv;
+        }
+        // Failing a safe move, at least make the opponent find their win.
+        let candidates = if !threats.safe.is_empty() {
+            threats.safe
+        } else if !threats.allows_win.is_empty() {
+            threats.allows_win.iter().map(|&(mv, _)| mv).collect()
+        } else {
+            threats.hands_line
+        };
+        *candidates.choose(&mut self.rng).expect("game should not be over")
+    }
+}
+
+/// SearchAgent plays the best move found by a fixed-depth alpha-beta search.
+pub struct SearchAgent {
+    depth: u8,
+    evaluator: Box<dyn Evaluator>,
+}
+
+impl SearchAgent {
+    /// An agent searching `depth` plies with the evaluator.
+    pub fn new(depth: u8, evaluator: Box<dyn Evaluator>) -> Self {
+        SearchAgent{depth, evaluator}
+    }
+}
+
+impl Agent for SearchAgent {
+    fn name(&self) -> String {
+        format!("search-d{}", self.depth)
+    }
+
+    fn choose_move(&mut self, state: &GameState) -> Move {
+        let result = Search::new(self.evaluator.as_ref()).search(state, self.depth);
+        result.best_move.expect("game should not be over")
+    }
+}
+
+/// MctsAgent plays the most visited move after a fixed number of MCTS iterations.
+pub struct MctsAgent {
+    iterations: u32,
+    evaluator: Box<dyn Evaluator>,
+}
+
+impl MctsAgent {
+    /// An agent running `iterations` iterations per move with the evaluator.
+    pub fn new(iterations: u32, evaluator: Box<dyn Evaluator>) -> Self {
+        MctsAgent{iterations, evaluator}
+    }
+}
+
+impl Agent for MctsAgent {
+    fn name(&self) -> String {
+        format!("mcts-{}", self.iterations)
+    }
+
+    fn choose_move(&mut self, state: &GameState) -> Move {
+        let result = Mcts::new(self.evaluator.as_ref()).search(state, self.iterations);
+        result.best_move.expect("game should not be over")
+    }
+}
+
+/// MultiSearchAgent plays the best move found by a fixed-depth search of a game of any number of
+/// players.
+pub struct MultiSearchAgent {
+    strategy: Strategy,
+    depth: u8,
+}
+
+impl MultiSearchAgent {
+    /// An agent searching `depth` plies with the strategy.
+    pub fn new(strategy: Strategy, depth: u8) -> Self {
+        MultiSearchAgent{strategy, depth}
+    }
+}
+
+impl Agent for MultiSearchAgent {
+    fn name(&self) -> String {
+        format!("{}-d{}", self.strategy, self.depth)
+    }
+
+    fn choose_move(&mut self, state: &GameState) -> Move {
+        let result = MultiSearch::new(self.strategy).search(state, self.depth);
+        result.best_move.expect("game should not be over")
+    }
+}
+
+/// HumanAgent asks for moves in compact notation on stdin.
+#[derive(Default)]
+pub struct HumanAgent;
+
+impl Agent for HumanAgent {
+    fn name(&self) -> String {
+        "human".to_string()
+    }
+
+    fn choose_move(&mut self, state: &GameState) -> Move {
+        println!("{}", state.board());
+        let moves: Vec<String> = state.legal_moves().iter().map(|mv| mv.to_string()).collect();
+        println!("Legal moves: {}", moves.join(" "));
+        let stdin = io::stdin();
+        loop {
+            print!("{:?} to move> ", state.player_to_move());
+            io::stdout().flush().expect("should flush stdout");
+            let mut line = String::new();
+            if stdin.lock().read_line(&mut line).expect("should read stdin") == 0 {
+                panic!("stdin closed while waiting for a move");
+            }
+            match state.parse_move(&line) {
+                Ok(mv) => return mv,
+                Err(e) => println!("{e}"),
+            }
+        }
+    }
+
+    fn notify_move(&mut self, state: &GameState, mv: &Move) {
+        println!("{:?} played {mv}", state.player_to_move());
+    }
+
+    fn game_over(&mut self, state: &GameState, outcome: Outcome) {
+        println!("{}", state.board());
+        println!("Game over: {outcome:?}");
+    }
+}
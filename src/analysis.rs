@@ -0,0 +1,214 @@
+//! Going over a finished game move by move, grading each move against the search's best.
+use crate::error::Error;
+use crate::evaluator::Evaluator;
+use crate::game_state::{Move, PlayerColor};
+use crate::record::{result_string, GameRecord};
+use crate::rules::Rules;
+use crate::search::{is_win_score, Search};
+use std::fmt;
+use std::fmt::Write as _;
+use std::time::{Duration, Instant};
+
+/// Judgement grades a played move by how much worse it scored than the best move.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Debug, Hash)]
+pub enum Judgement {
+    /// As good as the best move.
+    Best,
+    /// Up to 50 worse.
+    Good,
+    /// Up to 150 worse.
+    Inaccuracy,
+    /// Up to 400 worse.
+    Mistake,
+    /// More than 400 worse, e.g. throwing away a win.
+    Blunder,
+}
+
+impl Judgement {
+    /// Grades a loss in score, on the search's scale where a won position is worth 1000 or more.
+    pub fn from_loss(loss: i32) -> Judgement {
+        match loss {
+            i32::MIN..=0 => Judgement::Best,
+            1..=50 => Judgement::Good,
+            51..=150 => Judgement::Inaccuracy,
+            151..=400 => Judgement::Mistake,
+            _ => Judgement::Blunder,
+        }
+    }
+
+    /// Every judgement, from best to worst.
+    pub fn all() -> [Judgement; 5] {
+        [Judgement::Best, Judgement::Good, Judgement::Inaccuracy, Judgement::Mistake, Judgement::Blunder]
+    }
+}
+
+impl fmt::Display for Judgement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Judgement::Best => "best",
+            Judgement::Good => "good",
+            Judgement::Inaccuracy => "inaccuracy",
+            Judgement::Mistake => "mistake",
+            Judgement::Blunder => "blunder",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// MoveAnalysis is a played move compared with the best move the search found in its position.
+/// Scores are for the player who moved.
+#[derive(Clone, Debug)]
+pub struct MoveAnalysis {
+    /// The player who moved.
+    pub player: PlayerColor,
+    /// The move played.
+    pub played: Move,
+    /// The search's score for the move played.
+    pub played_score: i32,
+    /// The best move found.
+    pub best: Move,
+    /// The search's score for the best move.
+    pub best_score: i32,
+    /// The grade for the move played, from its loss.
+    pub judgement: Judgement,
+}
+
+impl MoveAnalysis {
+    /// How much worse the move played scored than the best move, never less than 0.
+    pub fn loss(&self) -> i32 {
+        // Win scores differ by the distance to the win, so a slower win or a quicker loss loses
+        // just those plies, but otherwise they are capped before comparing.
+        if is_win_score(self.best_score) && is_win_score(self.played_score) && (self.best_score > 0) == (self.played_score > 0) {
+            return (self.best_score - self.played_score).max(0);
+        }
+        let cap = |s: i32| s.clamp(-1000, 1000);
+        (cap(self.best_score) - cap(self.played_score)).max(0)
+    }
+}
+
+/// GameAnalysis is a game record with every move compared against the search's best move.
+#[derive(Clone, Debug)]
+pub struct GameAnalysis {
+    /// The game analysed.
+    pub record: GameRecord,
+    /// Every move of the game, in order.
+    pub moves: Vec<MoveAnalysis>,
+}
+
+/// Searches every position of the game to `depth`, or for up to `time_per_move` if given, and
+/// grades the move played in it. The depth is at least 1. Games of more than two players are
+/// refused, as the search is for two.
+pub fn analyse_game(record: &GameRecord, evaluator: &dyn Evaluator, depth: u8, time_per_move: Option<Duration>) -> Result<GameAnalysis, Error> {
+    if record.rules.players > 2 {
+        return Err(Error::Invalid("only two player games can be analysed".to_string()));
+    }
+    let depth = depth.max(1);
+    let states = record.states();
+    let mut moves = Vec::new();
+    for (state, played) in states.iter().zip(&record.moves) {
+        let deadline = time_per_move.map(|t| Instant::now() + t);
+        let result = Search::new(evaluator).with_deadline(deadline).search_iterative(state, depth);
+        let best = result.best_move.expect("game should not be over");
+        let played_score = if *played == best {
+            result.score
+        } else {
+            let mut child = *state;
+            child.apply_move(played);
+            // Search the played move as deeply as the best move was, without a deadline.
+            -Search::new(evaluator).search(&child, result.depth - 1).score
+        };
+        let mut analysis = MoveAnalysis {
+            player: state.player_to_move(),
+            played: *played,
+            played_score,
+            best,
+            best_score: result.score.max(played_score),
+            judgement: Judgement::Best,
+        };
+        // Missing a forced win, or walking into a forced loss, is always a blunder. Winning more
+        // slowly, or losing more quickly, is graded by the plies it gives away.
+        let wins = |score: i32| is_win_score(score) && score > 0;
+        let loses = |score: i32| is_win_score(score) && score < 0;
+        let missed_win = wins(analysis.best_score) && !wins(analysis.played_score);
+        let walked_into_loss = loses(analysis.played_score) && !loses(analysis.best_score);
+        analysis.judgement = if missed_win || walked_into_loss { Judgement::Blunder } else { Judgement::from_loss(analysis.loss()) };
+        moves.push(analysis);
+    }
+    Ok(GameAnalysis{record: record.clone(), moves})
+}
+
+impl GameAnalysis {
+    /// The record with a comment after each move giving its grade, and the best move where it differs.
+    pub fn annotated_record(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "[White \"{}\"]", self.record.white).unwrap();
+        writeln!(out, "[Red \"{}\"]", self.record.red).unwrap();
+        writeln!(out, "[Result \"{}\"]", result_string(self.record.outcome)).unwrap();
+        if self.record.rules != Rules::standard() {
+            writeln!(out, "[Rules \"{}\"]", self.record.rules).unwrap();
+        }
+        if let Some(setup) = &self.record.setup {
+            writeln!(out, "[Setup \"{}\"]", setup.position_string()).unwrap();
+        }
+        for (i, m) in self.moves.iter().enumerate() {
+            write!(out, "{}. {} {{{} {}", i + 1, m.played, m.judgement, m.played_score).unwrap();
+            if m.judgement != Judgement::Best {
+                write!(out, "; best {} {}", m.best, m.best_score).unwrap();
+            }
+            writeln!(out, "}}").unwrap();
+        }
+        out
+    }
+
+    /// Per player: how many moves got each grade, the average score lost per move, and accuracy as
+    /// the share of moves graded best or good.
+    pub fn summary(&self) -> String {
+        let mut out = String::new();
+        for (player, name) in [(PlayerColor::White, &self.record.white), (PlayerColor::Red, &self.record.red)] {
+            let moves: Vec<&MoveAnalysis> = self.moves.iter().filter(|m| m.player == player).collect();
+            let n = moves.len().max(1) as f64;
+            let accurate = moves.iter().filter(|m| m.judgement <= Judgement::Good).count();
+            let average_loss = moves.iter().map(|m| m.loss() as f64).sum::<f64>() / n;
+            let counts: Vec<String> = Judgement::all().iter()
+                .map(|j| format!("{} {j}", moves.iter().filter(|m| m.judgement == *j).count()))
+                .collect();
+            writeln!(out, "{player:?} ({name}): accuracy {:.0}%, average loss {average_loss:.0}, {}", 100.0 * accurate as f64 / n, counts.join(", ")).unwrap();
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::LineEvaluator;
+
+    #[test]
+    fn only_two_player_games() {
+        let record = GameRecord::parse("[Rules \"three-player\"]\na1rrr").unwrap();
+        assert!(matches!(analyse_game(&record, &LineEvaluator, 2, None), Err(Error::Invalid(_))));
+    }
+
+    #[test]
+    fn missing_a_win_is_a_blunder() {
+        // White can finish the top row at c1 with b2rur, but plays elsewhere, and then Red misses
+        // the same win for White by giving it away.
+        let record = GameRecord::parse("[Setup \"W,W,,W/,WW,,/nn,,,nn/nn,,,nn W\"]\na4uuu b2urd").unwrap();
+        let analysis = analyse_game(&record, &LineEvaluator, 2, None).unwrap();
+        let white = &analysis.moves[0];
+        assert_eq!(white.judgement, Judgement::Blunder);
+        assert!(is_win_score(white.best_score) && white.best_score > 0);
+        assert!(!is_win_score(white.played_score));
+        let red = &analysis.moves[1];
+        assert_eq!(red.judgement, Judgement::Blunder);
+        assert!(is_win_score(red.played_score) && red.played_score < 0);
+    }
+
+    #[test]
+    fn winning_is_best() {
+        let record = GameRecord::parse("[Setup \"W,W,,W/,WW,,/nn,,,nn/nn,,,nn W\"]\nb2rur").unwrap();
+        let analysis = analyse_game(&record, &LineEvaluator, 2, None).unwrap();
+        assert_eq!(analysis.moves[0].judgement, Judgement::Best);
+        assert_eq!(analysis.moves[0].loss(), 0);
+    }
+}
@@ -0,0 +1,26 @@
+//! qawale-host [--address HOST:PORT] [--records FILE] [--clock SECONDS] [--increment SECONDS]
+//! Hosts live games between people and bots over WebSocket. Games are untimed unless given a
+//! clock, here or when their room is created.
+use qawale::cli::Args;
+use qawale::game_server::{self, GameServerConfig};
+use std::time::Duration;
+
+const USAGE: &str = "qawale-host [--address HOST:PORT] [--records FILE] [--clock SECONDS] [--increment SECONDS]";
+
+fn main() {
+    if let Err(e) = run(&Args::parse(std::env::args().skip(1), &[])) {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
+}
+
+fn run(args: &Args) -> Result<(), String> {
+    args.check_options(&["address", "records", "clock", "increment"], USAGE)?;
+    game_server::serve(GameServerConfig {
+        address: args.get("address", "127.0.0.1:8081".to_string())?,
+        records: args.get_opt("records")?,
+        clock: args.get_seconds_opt("clock")?,
+        increment: args.get_seconds("increment", Duration::ZERO)?,
+    })?;
+    Ok(())
+}
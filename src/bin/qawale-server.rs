@@ -0,0 +1,33 @@
+//! qawale-server [--address HOST:PORT] [--threads N] [--max-queued N] [--timeout SECONDS] [--max-depth N] [--weights FILE]
+//! Serves the JSON API for legal moves, applying moves, best moves and analysis.
+use qawale::cli::Args;
+use qawale::nn::Mlp;
+use qawale::server::{self, ServerConfig};
+use qawale::LineEvaluator;
+use std::thread;
+use std::time::Duration;
+
+const USAGE: &str = "qawale-server [--address HOST:PORT] [--threads N] [--max-queued N] [--timeout SECONDS] [--max-depth N] [--weights FILE]";
+
+fn main() {
+    if let Err(e) = run(&Args::parse(std::env::args().skip(1), &[])) {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
+}
+
+fn run(args: &Args) -> Result<(), String> {
+    args.check_options(&["address", "threads", "max-queued", "timeout", "max-depth", "weights"], USAGE)?;
+    let config = ServerConfig {
+        address: args.get("address", "127.0.0.1:8080".to_string())?,
+        threads: args.get("threads", thread::available_parallelism().map_or(1, |n| n.get()))?,
+        max_queued: args.get("max-queued", 64)?,
+        timeout: args.get_seconds("timeout", Duration::from_secs(10))?,
+        max_depth: args.get("max-depth", 8)?,
+    };
+    match args.get_opt::<String>("weights")? {
+        Some(path) => server::serve(&config, &Mlp::load(&path)?)?,
+        None => server::serve(&config, &LineEvaluator)?,
+    }
+    Ok(())
+}
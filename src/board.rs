@@ -1,42 +1,65 @@
+//! The board: a grid of stone piles, and the lines that win the game.
+use crate::error::Error;
 use crate::position::Position;
-use crate::position::NUM_PILES;
 use crate::position::BOARD_SIZE;
 use crate::stone::Stone;
 use crate::stone_pile::StonePile;
 use crate::game_state::Move;
 use crate::stone_pile::PileTop;
 use crate::game_state::PlayerColor;
+use crate::rules::Rules;
 use std::fmt;
 
-#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
-pub struct Board {
-    piles: [StonePile; NUM_PILES],
+/// Board is an N by N grid of stone piles. It knows nothing of turns or of the stones the players
+/// have left, which GameState keeps.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub struct Board<const N: usize = { BOARD_SIZE as usize }> {
+    // Indexed by row, then column.
+    piles: [[StonePile; N]; N],
 }
 
-impl Board {
+impl<const N: usize> Default for Board<N> {
+    fn default() -> Self {
+        Self{piles: [[StonePile::default(); N]; N]}
+    }
+}
+
+impl<const N: usize> Board<N> {
+    /// The board at the start of a game played by the standard rules.
     pub fn starting_board() -> Self {
-        let mut b = Board::default();
-        for pos in [Position::top_left(), Position::top_right(), Position::bottom_left(), Position::bottom_right()] {
-            let p = &mut b.piles[pos.to_index()];
-            p.add_stone(Stone::Neutral);
-            p.add_stone(Stone::Neutral);
+        Self::setup(&Rules::standard())
+    }
+
+    /// The board at the start of a game played by the given rules.
+    pub fn setup(rules: &Rules) -> Self {
+        let mut b = Self::default();
+        for pos in rules.layout.squares() {
+            for _ in 0..rules.neutrals {
+                b.pile_mut(pos).add_stone(Stone::Neutral);
+            }
         }
         b
     }
 
-    pub fn top(&self) -> BoardTop {
-        let mut piles = [PileTop::Empty; NUM_PILES];
-        for i in 0..NUM_PILES {
-            piles[i] = self.piles[i].top();
-        }
-        BoardTop::new(piles)
+    /// The top stone of every pile, which is all that decides the lines.
+    pub fn top(&self) -> BoardTop<N> {
+        BoardTop(self.piles.map(|row| row.map(|pile| pile.top())))
+    }
+
+    /// The pile on the square.
+    pub fn pile(&self, pos: Position<N>) -> &StonePile {
+        &self.piles.as_flattened()[pos.to_index()]
     }
 
-    pub fn iter_piles(&self) -> impl Iterator<Item = (Position, &StonePile)> {
-        return self.piles.iter().enumerate().map(|(i,p)| (Position::from_index(i), p))
+    /// Every square with its pile, row by row from a1.
+    pub fn iter_piles(&self) -> impl Iterator<Item = (Position<N>, &StonePile)> {
+        return self.piles.as_flattened().iter().enumerate().map(|(i,p)| (Position::from_index(i), p))
     }
 
-    pub fn apply_move(&mut self, mv: &Move) {
+    /// Picks up the pile at the start of the move, adds the new stone on top and drops the stones
+    /// one per step from the bottom. Panics if the path does not fit the pile; legality is left to
+    /// GameState.
+    pub fn apply_move(&mut self, mv: &Move<N>) {
         let mut hand = self.pile_mut(mv.path.start()).take_pile();
         if hand.len() == 0 {
             panic!("Cannot start move {mv:?} in empty pile")
@@ -50,12 +73,60 @@ impl Board {
         }
     }
 
-    fn pile_mut(&mut self, pos: Position) -> &mut StonePile {
-        return &mut self.piles[pos.to_index()]
+    /// Puts a stone on top of the pile on the square.
+    pub fn add_stone(&mut self, pos: Position<N>, stone: Stone) {
+        self.pile_mut(pos).add_stone(stone)
+    }
+
+    /// The number of stones of the given colour anywhere on the board.
+    pub fn count_stones(&self, stone: Stone) -> u8 {
+        // Saturating, as a parsed board can hold more stones than any game.
+        self.piles.as_flattened().iter().fold(0u8, |n, p| n.saturating_add(p.iter().filter(|&s| s == stone).count() as u8))
+    }
+
+    /// The board part of a position string: piles listed row by row, each as its stones from bottom
+    /// to top, with commas between piles and slashes between rows.
+    pub fn position_string(&self) -> String {
+        let mut out = String::new();
+        for (pos, pile) in self.iter_piles() {
+            for stone in pile.iter() {
+                out += &stone.to_string();
+            }
+            if pos.next_on_new_row() {
+                out.push('/');
+            } else if pos.to_index() != Position::<N>::NUM_PILES - 1 {
+                out.push(',');
+            }
+        }
+        out
+    }
+
+    /// Parses the board part of a position string, as written by position_string. Only the
+    /// notation is checked, not the stones.
+    pub fn parse_position(piles: &str) -> Result<Self, Error> {
+        let rows: Vec<&str> = piles.split('/').collect();
+        if rows.len() != N {
+            return Err(Error::Parse(format!("expected {N} rows")));
+        }
+        let mut board = Self::default();
+        for (y, row) in rows.iter().enumerate() {
+            let row_piles: Vec<&str> = row.split(',').collect();
+            if row_piles.len() != N {
+                return Err(Error::Parse(format!("expected {N} piles in row {}", y + 1)));
+            }
+            for (x, pile) in row_piles.iter().enumerate() {
+                *board.pile_mut(Position::from_coord(x as u8, y as u8)) = StonePile::parse(pile)?;
+            }
+        }
+        Ok(board)
+    }
+
+    fn pile_mut(&mut self, pos: Position<N>) -> &mut StonePile {
+        return &mut self.piles.as_flattened_mut()[pos.to_index()]
     }
 }
 
-impl fmt::Display for Board {
+impl<const N: usize> fmt::Display for Board<N> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         for (pos, pile) in self.iter_piles() {
             pile.fmt(f)?;
@@ -65,82 +136,186 @@ impl fmt::Display for Board {
     }
 }
 
+/// Line is a straight run of squares along a row, a column or a diagonal. On a torus a line may
+/// wrap around the edges of the board.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Hash)]
+pub struct Line<const N: usize = { BOARD_SIZE as usize }> {
+    start: Position<N>,
+    step: (i8, i8),
+    length: u8,
+    wraps: bool,
+}
+
+impl<const N: usize> Line<N> {
+    /// The first square of the line.
+    pub fn start(&self) -> Position<N> {
+        self.start
+    }
+
+    /// The last square of the line.
+    pub fn end(&self) -> Position<N> {
+        self.at(self.length - 1)
+    }
+
+    /// The number of squares in the line.
+    pub fn len(&self) -> usize {
+        self.length as usize
+    }
+
+    /// Whether the line has no squares, which lines() never gives.
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// The squares of the line, from start to end.
+    pub fn positions(&self) -> impl Iterator<Item = Position<N>> + '_ {
+        (0..self.length).map(|i| self.at(i))
+    }
+
+    /// Whether the square is on the line.
+    pub fn contains(&self, pos: Position<N>) -> bool {
+        self.positions().any(|p| p == pos)
+    }
+
+    fn at(&self, i: u8) -> Position<N> {
+        if self.wraps {
+            let coord = |c: u8, step: i8| (c as i16 + step as i16 * i as i16).rem_euclid(N as i16) as u8;
+            return Position::from_coord(coord(self.start.x(), self.step.0), coord(self.start.y(), self.step.1));
+        }
+        let stride = self.step.0 as isize + self.step.1 as isize * N as isize;
+        Position::from_index(self.start.to_index().wrapping_add_signed(stride * i as isize))
+    }
+}
+
+/// Every line of `length` squares on the board: rows, then columns, then unless left out the
+/// diagonals running down to the right, then those running up to the right, then if `wrap` is set
+/// the lines wrapping around the edges.
+pub fn lines<const N: usize>(length: usize, diagonals: bool, wrap: bool) -> impl Iterator<Item = Line<N>> {
+    straight_lines(length, diagonals).chain(wrapped_lines(length, diagonals).filter(move |_| wrap))
+}
+
+fn line_steps(diagonals: bool) -> &'static [(i8, i8)] {
+    if diagonals { &[(1, 0), (0, 1), (1, 1), (1, -1)] } else { &[(1, 0), (0, 1)] }
+}
+
+fn straight_lines<const N: usize>(length: usize, diagonals: bool) -> impl Iterator<Item = Line<N>> {
+    let (size, length) = (N as u8, length.clamp(1, N + 1) as u8);
+    // The coordinates a line can start from along an axis it moves along by `step`.
+    let starts = move |step: i8| match step {
+        1 => 0..(size + 1).saturating_sub(length),
+        -1 => length - 1..size,
+        _ => 0..size,
+    };
+    line_steps(diagonals).iter().copied().flat_map(move |step: (i8, i8)| {
+        // Columns are listed one after another, rather than in square order.
+        let by_column = step == (0, 1);
+        let (outer, inner) = if by_column { (starts(step.0), starts(step.1)) } else { (starts(step.1), starts(step.0)) };
+        outer.flat_map(move |a| inner.clone().map(move |b| {
+            let (x, y) = if by_column { (a, b) } else { (b, a) };
+            Line{start: Position::from_coord(x, y), step, length, wraps: false}
+        }))
+    })
+}
+
+// The lines that cross an edge of a torus. Lines as long as the board's side only differ from the
+// straight ones along the diagonals, and only one line is listed for each wrapped diagonal.
+fn wrapped_lines<const N: usize>(length: usize, diagonals: bool) -> impl Iterator<Item = Line<N>> {
+    let length = length.clamp(1, N) as u8;
+    line_steps(diagonals).iter().copied().flat_map(move |step: (i8, i8)| {
+        (0..Position::<N>::NUM_PILES).map(Position::<N>::from_index).filter_map(move |start| {
+            let span = length as i8 - 1;
+            let crosses_edge = start.offset(step.0 * span, step.1 * span).is_none();
+            let distinct = (length as usize) < N || (step.0 != 0 && step.1 != 0 && start.x() == 0);
+            (crosses_edge && distinct).then_some(Line{start, step, length, wraps: true})
+        })
+    })
+}
 
 /// BoardTop is a representation of the top of the board without any detail about lower stones in stacks
-#[derive(PartialEq, Eq, Copy, Clone, Debug, Hash, Default)]
-pub struct BoardTop([PileTop; NUM_PILES]);
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Hash)]
+pub struct BoardTop<const N: usize = { BOARD_SIZE as usize }>([[PileTop; N]; N]);
 
-impl BoardTop {
-    fn new(piles: [PileTop; NUM_PILES]) -> Self {
-        Self(piles)
+impl<const N: usize> BoardTop<N> {
+    /// The top of the pile on the square.
+    pub fn pile(&self, pos: Position<N>) -> PileTop {
+        self.0.as_flattened()[pos.to_index()]
     }
 
-    pub fn iter_piles(&self) -> impl Iterator<Item = (Position, &PileTop)> {
-        return self.0.iter().enumerate().map(|(i,p)| (Position::from_index(i), p))
+    /// Every square with the top of its pile, row by row from a1.
+    pub fn iter_piles(&self) -> impl Iterator<Item = (Position<N>, &PileTop)> {
+        return self.0.as_flattened().iter().enumerate().map(|(i,p)| (Position::from_index(i), p))
     }
 
-    pub fn winner(&self) -> Option<PlayerColor> {
-        // Check rows
-        'row_loop: for y in 0..BOARD_SIZE {
-            let row_start = self.0[Position::from_coord(0,y).to_index()];
-            let row_player = match PlayerColor::from_pile_top(row_start) {
-                None => { continue 'row_loop; },
-                Some(c) => c,
-            };
-            for x in 1..BOARD_SIZE {
-                if self.0[Position::from_coord(x,y).to_index()] != row_start {
-                    continue 'row_loop;
-                }
+    /// The player whose stones top the whole of the line, if any.
+    pub fn line_owner(&self, line: &Line<N>) -> Option<PlayerColor> {
+        let first = self.pile(line.start());
+        let player = PlayerColor::from_pile_top(first)?;
+        line.positions().all(|pos| self.pile(pos) == first).then_some(player)
+    }
+
+    /// The owner of the first winning line under the rules topped by one player, or by `only` if
+    /// given, checking lines in the same order as lines(). This is on the search's hot path, so it
+    /// avoids building each Line where it can.
+    pub fn line_winner(&self, rules: &Rules, only: Option<PlayerColor>) -> Option<PlayerColor> {
+        let (length, diagonals) = (rules.line_length as usize, rules.diagonals);
+        // Lines usually span the board, and a constant length lets the compiler unroll the checks.
+        let straight = if length == N {
+            self.scan_lines(N, diagonals, only)
+        } else {
+            self.scan_lines(length, diagonals, only)
+        };
+        if straight.is_some() || !rules.wrapped_lines {
+            return straight;
+        }
+        wrapped_lines(length, diagonals).find_map(|line| self.line_owner(&line).filter(|&p| only.is_none_or(|o| o == p)))
+    }
+
+    #[inline(always)]
+    fn scan_lines(&self, length: usize, diagonals: bool, only: Option<PlayerColor>) -> Option<PlayerColor> {
+        if length == 0 || length > N {
+            return None;
+        }
+        // The player topping `length` squares from (x, y) on, moving by (dx, dy) each step.
+        let run = |x: usize, y: usize, dx: isize, dy: isize| {
+            let first = self.0[y][x];
+            let player = PlayerColor::from_pile_top(first).filter(|&p| only.is_none_or(|o| o == p))?;
+            let at = |i: usize| self.0[y.wrapping_add_signed(dy * i as isize)][x.wrapping_add_signed(dx * i as isize)];
+            (1..length).all(|i| at(i) == first).then_some(player)
+        };
+        let last_start = N - length;
+        for y in 0..N {
+            for x in 0..=last_start {
+                if let Some(p) = run(x, y, 1, 0) { return Some(p); }
             }
-            return Some(row_player);
-        }
-        // Check cols
-        'col_loop: for x in 0..BOARD_SIZE {
-            let col_start = self.0[Position::from_coord(x,0).to_index()];
-            let col_player = match PlayerColor::from_pile_top(col_start) {
-                None => { continue 'col_loop; },
-                Some(c) => c,
-            };
-            for y in 1..BOARD_SIZE {
-                if self.0[Position::from_coord(x,y).to_index()] != col_start {
-                    continue 'col_loop;
-                }
+        }
+        for x in 0..N {
+            for y in 0..=last_start {
+                if let Some(p) = run(x, y, 0, 1) { return Some(p); }
             }
-            return Some(col_player);
-        }
-        // Check diagonal 1, using icky goto-style break for consistency with rows/cols
-        'diag_1: {
-            let diag_start = self.0[Position::from_coord(0,0).to_index()];
-            let diag_player = match PlayerColor::from_pile_top(diag_start) {
-                None => { break 'diag_1; },
-                Some(c) => c,
-            };
-            for i in 1..BOARD_SIZE {
-                if self.0[Position::from_coord(i,i).to_index()] != diag_start {
-                    break 'diag_1;
-                }
+        }
+        if !diagonals {
+            return None;
+        }
+        for y in 0..=last_start {
+            for x in 0..=last_start {
+                if let Some(p) = run(x, y, 1, 1) { return Some(p); }
             }
-            return Some(diag_player);
-        }
-        // Check diagonal 2, using icky goto-style break for consistency with rows/cols
-        'diag_2: {
-            let diag_start = self.0[Position::from_coord(0,BOARD_SIZE-1).to_index()];
-            let diag_player = match PlayerColor::from_pile_top(diag_start) {
-                None => { break 'diag_2; },
-                Some(c) => c,
-            };
-            for i in 1..BOARD_SIZE {
-                if self.0[Position::from_coord(i,BOARD_SIZE-1-i).to_index()] != diag_start {
-                    break 'diag_2;
-                }
+        }
+        for y in length-1..N {
+            for x in 0..=last_start {
+                if let Some(p) = run(x, y, 1, -1) { return Some(p); }
             }
-            return Some(diag_player);
         }
         None
     }
+
+    /// The winner under the standard rules scaled to the board: a row, column or long diagonal.
+    pub fn winner(&self) -> Option<PlayerColor> {
+        self.line_winner(&Rules{line_length: N as u8, ..Rules::standard()}, None)
+    }
 }
 
-impl fmt::Display for BoardTop {
+impl<const N: usize> fmt::Display for BoardTop<N> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         for (pos, pile) in self.iter_piles() {
             pile.fmt(f)?;
@@ -148,4 +323,4 @@ impl fmt::Display for BoardTop {
         }
         Ok(())
     }
-}
\ No newline at end of file
+}
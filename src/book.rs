@@ -0,0 +1,272 @@
+//! Opening books, built by searching the first moves or from the results of recorded games.
+use crate::error::Error;
+use crate::evaluator::Evaluator;
+use crate::game_state::{GameState, Move, Outcome};
+use crate::path::Path;
+use crate::record::GameRecord;
+use crate::rules::Rules;
+use crate::search::{terminal_score, Search};
+use crate::symmetry::canonical;
+use rand::Rng;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write as _;
+use std::fs;
+
+// Search-built books keep at most this many moves per position.
+const MAX_MOVES_PER_POSITION: usize = 4;
+
+// Record-built books score a move by the mover's average result, on the search's scale.
+const RESULT_SCORE: i32 = 1000;
+
+/// BookMove is a move recommended by the book, with how often it should be played and its score
+/// for the player making it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BookMove {
+    /// The move.
+    pub mv: Move,
+    /// How often to play the move, relative to the position's other book moves.
+    pub weight: u32,
+    /// The move's score, on the search's scale.
+    pub score: i32,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Entry {
+    // The move's path in the position's canonical orientation.
+    path: Path,
+    weight: u32,
+    score: i32,
+}
+
+/// OpeningBook maps positions, reduced by symmetry to a canonical orientation, to weighted moves.
+/// Books are built for games played by the standard rules, and have no moves for other games.
+/// The standard rules aren't symmetric, see Rules::symmetric, so their books keep every
+/// orientation of a position apart.
+#[derive(Clone, Debug, Default)]
+pub struct OpeningBook {
+    rules: Rules,
+    entries: HashMap<u64, Vec<Entry>>,
+}
+
+impl OpeningBook {
+    /// An empty book for the standard rules.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The rules of the games the book is for.
+    pub fn rules(&self) -> Rules {
+        self.rules
+    }
+
+    /// The number of positions in the book.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the book has no positions.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The book moves for the state, best first, in the state's own orientation. Moves that aren't
+    /// legal in the state, as from a hash collision or a corrupt file, are left out.
+    pub fn lookup(&self, state: &GameState) -> Vec<BookMove> {
+        if *state.rules() != self.rules {
+            return Vec::new();
+        }
+        let (key, symmetry) = canonical(state);
+        let Some(entries) = self.entries.get(&key) else { return Vec::new() };
+        let inverse = symmetry.inverse();
+        let legal = state.legal_moves();
+        let mut moves: Vec<BookMove> = entries.iter().map(|e| BookMove {
+            mv: Move{new_stone: state.player_to_move().stone_color(), path: inverse.apply_path(&e.path)},
+            weight: e.weight,
+            score: e.score,
+        }).filter(|m| legal.contains(&m.mv)).collect();
+        moves.sort_by_key(|m| (-m.score, -(m.weight as i64)));
+        moves
+    }
+
+    /// Picks a book move at random, in proportion to the weights.
+    pub fn pick(&self, state: &GameState, rng: &mut impl Rng) -> Option<Move> {
+        let moves = self.lookup(state);
+        let total: u32 = moves.iter().map(|m| m.weight).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut choice = rng.gen_range(0..total);
+        for m in moves {
+            if choice < m.weight {
+                return Some(m.mv);
+            }
+            choice -= m.weight;
+        }
+        unreachable!()
+    }
+
+    /// Adds weight to a move, averaging its score with any the book already has. Moves that lead to
+    /// the same position up to symmetry are merged. Moves in games by other rules than the book's
+    /// are ignored.
+    pub fn add(&mut self, state: &GameState, mv: &Move, weight: u32, score: i32) {
+        if *state.rules() != self.rules {
+            return;
+        }
+        let (key, symmetry) = canonical(state);
+        let canonical_state = symmetry.apply_state(state);
+        let path = symmetry.apply_path(&mv.path);
+        let child_key = |path: &Path| {
+            let mut child = canonical_state;
+            child.play(*path);
+            canonical(&child).0
+        };
+        let new_child = child_key(&path);
+        let entries = self.entries.entry(key).or_default();
+        match entries.iter_mut().find(|e| child_key(&e.path) == new_child) {
+            Some(e) => {
+                let total = e.weight + weight;
+                e.score = ((e.score as i64 * e.weight as i64 + score as i64 * weight as i64) / total.max(1) as i64) as i32;
+                e.weight = total;
+            }
+            None => entries.push(Entry{path, weight, score}),
+        }
+    }
+
+    /// Adds the first `plies` moves of each game, scored by the result for the player who made them.
+    pub fn add_records(&mut self, records: &[GameRecord], plies: usize) {
+        // Games from other setups never reach the book's positions.
+        let rules = self.rules;
+        for record in records.iter().filter(|r| r.setup.is_none() && r.rules == rules) {
+            let states = record.states();
+            for (state, mv) in states.iter().zip(&record.moves).take(plies) {
+                let score = match record.outcome {
+                    Some(Outcome::Win(p)) if p == state.player_to_move() => RESULT_SCORE,
+                    Some(Outcome::Win(_)) => -RESULT_SCORE,
+                    _ => 0,
+                };
+                self.add(state, mv, 1, score);
+            }
+        }
+    }
+
+    /// Builds a book by searching every move to `depth` in each position up to `plies` moves from the
+    /// start, keeping the moves scoring within `margin` of the best and following each of them.
+    pub fn build_from_search(evaluator: &dyn Evaluator, depth: u8, plies: u8, margin: i32, mut progress: impl FnMut(usize)) -> Self {
+        let mut book = OpeningBook::new();
+        let mut seen = HashSet::new();
+        let mut search = Search::new(evaluator);
+        book.expand(&GameState::starting_state(), &mut search, depth, plies, margin, &mut seen, &mut progress);
+        book
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn expand(&mut self, state: &GameState, search: &mut Search, depth: u8, plies: u8, margin: i32, seen: &mut HashSet<u64>, progress: &mut impl FnMut(usize)) {
+        if plies == 0 || state.is_over() || !seen.insert(canonical(state).0) {
+            return;
+        }
+        let mut scored = Vec::new();
+        let mut children = HashSet::new();
+        for mv in state.legal_moves() {
+            let mut child = *state;
+            child.apply_move(&mv);
+            if !children.insert(canonical(&child).0) {
+                continue; // The same as an earlier move, up to symmetry.
+            }
+            let score = match child.outcome() {
+                Some(outcome) => -terminal_score(&child, outcome, 1),
+                None => -search.search(&child, depth.saturating_sub(1)).score,
+            };
+            scored.push((mv, child, score));
+        }
+        scored.sort_by_key(|&(_, _, score)| -score);
+        let best = scored.first().map_or(0, |&(_, _, score)| score);
+        scored.retain(|&(_, _, score)| score >= best - margin);
+        scored.truncate(MAX_MOVES_PER_POSITION);
+        for (mv, _, score) in &scored {
+            self.add(state, mv, 1, *score);
+        }
+        progress(self.len());
+        for (_, child, _) in &scored {
+            self.expand(child, search, depth, plies - 1, margin, seen, progress);
+        }
+    }
+
+    /// Reads a book written by save.
+    pub fn load(path: &str) -> Result<Self, Error> {
+        let text = fs::read_to_string(path).map_err(Error::io(path))?;
+        let mut book = OpeningBook::new();
+        for (n, line) in text.lines().enumerate().filter(|(_, l)| !l.trim().is_empty() && !l.starts_with('#')) {
+            let place = format!("{path}:{}", n + 1);
+            let invalid = || Error::Parse(format!("{place}: invalid book line"));
+            // Books written before the rules line are for the standard rules.
+            if let Some(rules) = line.strip_prefix("rules ") {
+                book.rules = rules.trim().parse().map_err(|e: Error| e.at(&place))?;
+                continue;
+            }
+            let mut fields = line.split_whitespace();
+            let key = u64::from_str_radix(fields.next().ok_or_else(invalid)?, 16).map_err(|_| invalid())?;
+            let mut entries = Vec::new();
+            for field in fields {
+                let parts: Vec<&str> = field.split(':').collect();
+                let [path, weight, score] = parts[..] else { return Err(invalid()) };
+                entries.push(Entry {
+                    path: Path::parse(path, book.rules.topology).map_err(|e| e.at(&place))?,
+                    weight: weight.parse().map_err(|_| invalid())?,
+                    score: score.parse().map_err(|_| invalid())?,
+                });
+            }
+            book.entries.insert(key, entries);
+        }
+        Ok(book)
+    }
+
+    /// Writes the book as text: its rules, then a line per position with the position's hash and
+    /// each move's path, weight and score.
+    pub fn save(&self, path: &str) -> Result<(), Error> {
+        let mut out = String::from("# qawale opening book: canonical position hash, then path:weight:score per move\n");
+        writeln!(out, "rules {}", self.rules).unwrap();
+        let mut keys: Vec<&u64> = self.entries.keys().collect();
+        keys.sort();
+        for key in keys {
+            write!(out, "{key:016x}").unwrap();
+            for e in &self.entries[key] {
+                write!(out, " {}:{}:{}", e.path.notation(), e.weight, e.score).unwrap();
+            }
+            out.push('\n');
+        }
+        fs::write(path, out).map_err(Error::io(path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::symmetry::Symmetry;
+
+    // A book with one move after White's first, and a symmetry that turns the position round.
+    fn book_and_turned(rules: Rules) -> (OpeningBook, GameState, Symmetry, Move) {
+        let mut book = OpeningBook{rules, ..OpeningBook::new()};
+        let mut state = GameState::starting_state_with(rules).unwrap();
+        state.apply_move(&state.parse_move("a1rrr").unwrap());
+        let mv = state.parse_move("d1ldru").unwrap();
+        book.add(&state, &mv, 3, 10);
+        let symmetry = Symmetry::all().find(|s| s.apply_state(&state) != state).unwrap();
+        (book, state, symmetry, mv)
+    }
+
+    #[test]
+    fn standard_books_keep_orientations_apart() {
+        let (book, state, symmetry, mv) = book_and_turned(Rules::standard());
+        let moves = book.lookup(&state);
+        assert_eq!((moves.len(), moves[0].mv, moves[0].weight, moves[0].score), (1, mv, 3, 10));
+        assert!(book.lookup(&symmetry.apply_state(&state)).is_empty());
+    }
+
+    #[test]
+    fn symmetric_rules_share_entries() {
+        let (book, state, symmetry, mv) = book_and_turned("standard:simultaneous=mover".parse().unwrap());
+        let moves = book.lookup(&symmetry.apply_state(&state));
+        assert_eq!(moves.len(), 1);
+        assert_eq!(moves[0].mv, symmetry.apply_move(&mv));
+    }
+}
@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// A duration given in seconds, such as a time limit, which must be a finite number and not negative.
+pub fn seconds(seconds: f64) -> Result<Duration, String> {
+    Duration::try_from_secs_f64(seconds).map_err(|_| format!("{seconds} is not a number of seconds"))
+}
+
+/// Args is a command line split into positional arguments and `--name value` options. Options
+/// named as switches take no value.
+pub struct Args {
+    positional: Vec<String>,
+    options: HashMap<String, String>,
+}
+
+impl Args {
+    pub fn parse(args: impl Iterator<Item = String>, switches: &[&str]) -> Args {
+        let mut positional = Vec::new();
+        let mut options = HashMap::new();
+        let mut args = args.peekable();
+        while let Some(arg) = args.next() {
+            let Some(name) = arg.strip_prefix("--") else {
+                positional.push(arg);
+                continue;
+            };
+            let (name, value) = match name.split_once('=') {
+                Some((name, value)) => (name.to_string(), value.to_string()),
+                None if switches.contains(&name) => (name.to_string(), String::new()),
+                None => {
+                    let value = args.next_if(|v| !v.starts_with("--")).unwrap_or_default();
+                    (name.to_string(), value)
+                }
+            };
+            options.insert(name, value);
+        }
+        Args{positional, options}
+    }
+
+    pub fn positional(&self, i: usize) -> Option<&str> {
+        self.positional.get(i).map(String::as_str)
+    }
+
+    pub fn positionals_from(&self, i: usize) -> &[String] {
+        self.positional.get(i..).unwrap_or_default()
+    }
+
+    pub fn required(&self, i: usize, what: &str) -> Result<&str, String> {
+        self.positional(i).ok_or_else(|| format!("missing {what}"))
+    }
+
+    /// Fails with the usage if any option given is not among `known`.
+    pub fn check_options(&self, known: &[&str], usage: &str) -> Result<(), String> {
+        let mut unknown: Vec<String> = self.options.keys().filter(|name| !known.contains(&name.as_str())).map(|name| format!("--{name}")).collect();
+        if unknown.is_empty() {
+            return Ok(());
+        }
+        unknown.sort();
+        Err(format!("unknown option {}\nusage: {usage}", unknown.join(", ")))
+    }
+
+    pub fn has(&self, name: &str) -> bool {
+        self.options.contains_key(name)
+    }
+
+    pub fn get_opt<T: FromStr>(&self, name: &str) -> Result<Option<T>, String> {
+        match self.options.get(name) {
+            None => Ok(None),
+            Some(v) => v.parse().map(Some).map_err(|_| format!("invalid value {v:?} for --{name}")),
+        }
+    }
+
+    pub fn get<T: FromStr>(&self, name: &str, default: T) -> Result<T, String> {
+        Ok(self.get_opt(name)?.unwrap_or(default))
+    }
+
+    /// An option given in seconds, checked as by seconds().
+    pub fn get_seconds_opt(&self, name: &str) -> Result<Option<Duration>, String> {
+        self.get_opt::<f64>(name)?.map(|t| seconds(t).map_err(|e| format!("invalid value for --{name}: {e}"))).transpose()
+    }
+
+    pub fn get_seconds(&self, name: &str, default: Duration) -> Result<Duration, String> {
+        Ok(self.get_seconds_opt(name)?.unwrap_or(default))
+    }
+}
@@ -0,0 +1,72 @@
+use std::fmt;
+use std::io;
+
+/// Why the library could not read, build or play something. The message is written for people,
+/// as the programs print it, e.g. `invalid square "e1"`.
+///
+/// ```
+/// use qawale::{Error, GameState};
+///
+/// let state = GameState::starting_state();
+/// assert!(matches!(state.parse_move("e1rrr"), Err(Error::Parse(_))));
+/// assert!(matches!(state.parse_move("a1uuu"), Err(Error::Invalid(_))));
+/// assert_eq!(state.parse_move("e1rrr").unwrap_err().to_string(), "invalid square \"e1\"");
+/// ```
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// Text not written in the notation expected: a square, path, move, position, rules, record,
+    /// book or agent.
+    Parse(String),
+    /// Something well written that the rules or the settings do not allow, e.g. an illegal move or
+    /// a position with too many stones.
+    Invalid(String),
+    /// A file, an address to serve on or the terminal could not be used.
+    Io {
+        /// The file, address or terminal.
+        name: String,
+        /// What went wrong.
+        error: io::Error,
+    },
+}
+
+impl Error {
+    /// The same error, saying where in a file it was found, e.g. "book.txt:3".
+    pub(crate) fn at(self, place: &str) -> Self {
+        match self {
+            Error::Parse(message) => Error::Parse(format!("{place}: {message}")),
+            Error::Invalid(message) => Error::Invalid(format!("{place}: {message}")),
+            io @ Error::Io{..} => io,
+        }
+    }
+
+    /// Wraps an I/O error with the name of what was being used.
+    pub(crate) fn io(name: &str) -> impl FnOnce(io::Error) -> Self + '_ {
+        move |error| Error::Io{name: name.to_string(), error}
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Parse(message) | Error::Invalid(message) => f.write_str(message),
+            Error::Io{name, error} => write!(f, "{name}: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io{error, ..} => Some(error),
+            _ => None,
+        }
+    }
+}
+
+// The programs and servers report errors as text.
+impl From<Error> for String {
+    fn from(error: Error) -> String {
+        error.to_string()
+    }
+}
@@ -0,0 +1,55 @@
+//! Evaluators, which score positions for the searches.
+use crate::game_state::{GameState, Move, PlayerColor};
+use std::sync::Arc;
+
+/// An Evaluator scores positions for the search, without looking any further ahead.
+pub trait Evaluator {
+    /// The value of the state for the player to move, from -1 (lost) to 1 (won).
+    fn value(&self, state: &GameState) -> f32;
+
+    /// Prior probabilities of each of the given moves being best, summing to 1. Unless the
+    /// evaluator knows better, every move is as likely as any other.
+    fn policy(&self, _state: &GameState, moves: &[Move]) -> Vec<f32> {
+        vec![1.0 / moves.len() as f32; moves.len()]
+    }
+}
+
+// Evaluators are shared between agents, e.g. a network loaded once for a whole match.
+impl<E: Evaluator + ?Sized> Evaluator for Arc<E> {
+    fn value(&self, state: &GameState) -> f32 {
+        (**self).value(state)
+    }
+
+    fn policy(&self, state: &GameState, moves: &[Move]) -> Vec<f32> {
+        (**self).policy(state, moves)
+    }
+}
+
+/// LineEvaluator is a hand-written heuristic that favours lines partly topped by the player's
+/// own stones and not yet blocked by the opponent.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LineEvaluator;
+
+impl Evaluator for LineEvaluator {
+    fn value(&self, state: &GameState) -> f32 {
+        let top = state.board().top();
+        let own = state.player_to_move();
+        let mut score = 0;
+        for line in state.rules().winning_lines() {
+            let (mut mine, mut theirs) = (0, 0);
+            for pos in line.positions() {
+                match PlayerColor::from_pile_top(top.pile(pos)) {
+                    Some(p) if p == own => mine += 1,
+                    Some(_) => theirs += 1,
+                    None => (),
+                }
+            }
+            if theirs == 0 {
+                score += mine * mine;
+            } else if mine == 0 {
+                score -= theirs * theirs;
+            }
+        }
+        (score as f32 / 16.0).tanh()
+    }
+}
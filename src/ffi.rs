@@ -0,0 +1,252 @@
+//! A C interface to the engine in the shared library, for programs not written in Rust. It is
+//! declared in `include/qawale.h`, which is regenerated by building with the `header` feature.
+//!
+//! A game is created with [`qawale_game_new`] or [`qawale_game_from_position`] and freed with
+//! [`qawale_game_free`]. Moves and positions are passed as strings in the same notations as the
+//! command line, and strings returned to the caller are theirs to free with
+//! [`qawale_string_free`]. Functions that can fail return -1 or null, and
+//! [`qawale_last_error`] then says why. A panic inside the library is caught and reported the
+//! same way, as an internal error, rather than unwinding into the caller.
+//!
+//! Built with the `python` feature, the shared library expects the Python interpreter to supply
+//! its symbols, so C programs should link a build without that feature.
+use crate::evaluator::LineEvaluator;
+use crate::game_state::{GameState, Move, Outcome, PlayerColor};
+use crate::multiplayer::{MultiSearch, Strategy};
+use crate::rules::Rules;
+use crate::search::Search;
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::time::{Duration, Instant};
+
+/// A game in progress, owned by the caller between [`qawale_game_new`] and [`qawale_game_free`].
+pub struct QawaleGame {
+    state: GameState,
+}
+
+/// The result of a game, from [`qawale_game_outcome`].
+#[repr(C)]
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum QawaleOutcome {
+    /// The game goes on.
+    Ongoing,
+    /// The player to move has no stones left.
+    Draw,
+    /// Red topped a line.
+    RedWins,
+    /// White topped a line.
+    WhiteWins,
+    /// Blue topped a line.
+    BlueWins,
+    /// Green topped a line.
+    GreenWins,
+}
+
+/// The best move found by [`qawale_game_search`].
+#[repr(C)]
+#[derive(Debug)]
+pub struct QawaleSearchResult {
+    /// The move in compact notation, to be freed with [`qawale_string_free`]. Null if the game is over.
+    pub best_move: *mut c_char,
+    /// The score for the player to move.
+    pub score: i32,
+    /// The deepest search that finished.
+    pub depth: u8,
+    /// The positions searched.
+    pub nodes: u64,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_error(error: String) {
+    // An error with a nul in it would be cut off there by C anyway.
+    let error = CString::new(error.replace('\0', " ")).expect("nuls should be replaced");
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(error));
+}
+
+// Runs the body of an exported function, returning its value, or recording the error and returning
+// `failed`. A panic is caught and reported the same way, as it must not unwind into the caller.
+fn guard<T>(failed: T, body: impl FnOnce() -> Result<T, String>) -> T {
+    let result = panic::catch_unwind(AssertUnwindSafe(body)).unwrap_or_else(|payload| {
+        let message = payload.downcast_ref::<&str>().copied().or_else(|| payload.downcast_ref::<String>().map(String::as_str));
+        Err(format!("internal error: {}", message.unwrap_or("panic")))
+    });
+    result.unwrap_or_else(|e| {
+        set_error(e);
+        failed
+    })
+}
+
+fn to_c_string(text: String) -> *mut c_char {
+    CString::new(text).expect("notations should have no nuls").into_raw()
+}
+
+// The string at `text`, or `default` if it is null.
+unsafe fn read_str<'a>(text: *const c_char, what: &str, default: Option<&'a str>) -> Result<&'a str, String> {
+    if text.is_null() {
+        return default.ok_or_else(|| format!("{what} should not be null"));
+    }
+    CStr::from_ptr(text).to_str().map_err(|_| format!("{what} should be UTF-8"))
+}
+
+unsafe fn read_rules(rules: *const c_char) -> Result<Rules, String> {
+    Ok(read_str(rules, "rules", Some("standard"))?.parse()?)
+}
+
+unsafe fn game<'a>(game: *const QawaleGame) -> Result<&'a QawaleGame, String> {
+    game.as_ref().ok_or_else(|| "game should not be null".to_string())
+}
+
+/// The message for the last error on this thread, or null if there has been none. The message
+/// belongs to the library and lasts until the next error on the thread.
+#[no_mangle]
+pub extern "C" fn qawale_last_error() -> *const c_char {
+    guard(ptr::null(), || Ok(LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |e| e.as_ptr()))))
+}
+
+/// A new game from the starting position, played by `rules`, e.g. "standard" or
+/// "three:diagonals=no". Null rules are the standard rules. Returns null if the rules are invalid.
+///
+/// # Safety
+/// `rules` must be null or a nul-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn qawale_game_new(rules: *const c_char) -> *mut QawaleGame {
+    guard(ptr::null_mut(), || {
+        let state = GameState::starting_state_with(read_rules(rules)?)?;
+        Ok(Box::into_raw(Box::new(QawaleGame{state})))
+    })
+}
+
+/// A new game from a position string, e.g. "nn,,,nn/,,,/,,,/nn,,,nn W", played by `rules` as in
+/// [`qawale_game_new`]. Returns null if either is invalid.
+///
+/// # Safety
+/// `position` must be a nul-terminated string, and `rules` null or one.
+#[no_mangle]
+pub unsafe extern "C" fn qawale_game_from_position(position: *const c_char, rules: *const c_char) -> *mut QawaleGame {
+    guard(ptr::null_mut(), || {
+        let state = GameState::parse_position(read_str(position, "position", None)?, read_rules(rules)?)?;
+        Ok(Box::into_raw(Box::new(QawaleGame{state})))
+    })
+}
+
+/// Frees a game. Null is ignored.
+///
+/// # Safety
+/// `game` must be null or a game from this library not already freed.
+#[no_mangle]
+pub unsafe extern "C" fn qawale_game_free(game: *mut QawaleGame) {
+    guard((), || {
+        if !game.is_null() {
+            drop(Box::from_raw(game));
+        }
+        Ok(())
+    })
+}
+
+/// Frees a string returned by this library. Null is ignored.
+///
+/// # Safety
+/// `text` must be null or a string from this library not already freed.
+#[no_mangle]
+pub unsafe extern "C" fn qawale_string_free(text: *mut c_char) {
+    guard((), || {
+        if !text.is_null() {
+            drop(CString::from_raw(text));
+        }
+        Ok(())
+    })
+}
+
+/// Plays a move in compact notation, e.g. "a1rrd". Returns 0, or -1 if the move is not legal, leaving
+/// the game as it was.
+///
+/// # Safety
+/// `game` must be a game from this library and `notation` a nul-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn qawale_game_apply_move(game: *mut QawaleGame, notation: *const c_char) -> i32 {
+    guard(-1, || {
+        let game = game.as_mut().ok_or("game should not be null")?;
+        let mv = game.state.parse_move(read_str(notation, "move", None)?)?;
+        game.state.apply_move(&mv);
+        Ok(0)
+    })
+}
+
+/// The legal moves in compact notation, separated by spaces, with their number written to `count`
+/// if it is not null. The string is empty once the game is over. Returns null if `game` is.
+///
+/// # Safety
+/// `game` must be a game from this library, and `count` null or valid to write.
+#[no_mangle]
+pub unsafe extern "C" fn qawale_game_legal_moves(game: *const QawaleGame, count: *mut usize) -> *mut c_char {
+    guard(ptr::null_mut(), || {
+        let moves = self::game(game)?.state.legal_moves();
+        if let Some(count) = count.as_mut() {
+            *count = moves.len();
+        }
+        Ok(to_c_string(moves.iter().map(|mv| mv.to_string()).collect::<Vec<_>>().join(" ")))
+    })
+}
+
+/// The position string for the game. Returns null if `game` is.
+///
+/// # Safety
+/// `game` must be a game from this library.
+#[no_mangle]
+pub unsafe extern "C" fn qawale_game_position(game: *const QawaleGame) -> *mut c_char {
+    guard(ptr::null_mut(), || Ok(to_c_string(self::game(game)?.state.position_string())))
+}
+
+/// The letter of the player to move, e.g. 'W', or 0 if `game` is null.
+///
+/// # Safety
+/// `game` must be a game from this library.
+#[no_mangle]
+pub unsafe extern "C" fn qawale_game_to_move(game: *const QawaleGame) -> c_char {
+    guard(0, || Ok(self::game(game)?.state.player_to_move().letter() as c_char))
+}
+
+/// Whether the game is over and who won. A null game is reported as ongoing.
+///
+/// # Safety
+/// `game` must be a game from this library.
+#[no_mangle]
+pub unsafe extern "C" fn qawale_game_outcome(game: *const QawaleGame) -> QawaleOutcome {
+    guard(QawaleOutcome::Ongoing, || Ok(match self::game(game)?.state.outcome() {
+        None => QawaleOutcome::Ongoing,
+        Some(Outcome::Draw) => QawaleOutcome::Draw,
+        Some(Outcome::Win(PlayerColor::Red)) => QawaleOutcome::RedWins,
+        Some(Outcome::Win(PlayerColor::White)) => QawaleOutcome::WhiteWins,
+        Some(Outcome::Win(PlayerColor::Blue)) => QawaleOutcome::BlueWins,
+        Some(Outcome::Win(PlayerColor::Green)) => QawaleOutcome::GreenWins,
+    }))
+}
+
+/// Searches for the best move to at most `max_depth` moves ahead, stopping early after `time_ms`
+/// milliseconds unless it is 0. Returns 0 and fills in `result`, or -1 if either pointer is null.
+///
+/// # Safety
+/// `game` must be a game from this library and `result` valid to write.
+#[no_mangle]
+pub unsafe extern "C" fn qawale_game_search(game: *const QawaleGame, max_depth: u8, time_ms: u32, result: *mut QawaleSearchResult) -> i32 {
+    guard(-1, || {
+        let state = &self::game(game)?.state;
+        let result = result.as_mut().ok_or("result should not be null")?;
+        let deadline = (time_ms > 0).then(|| Instant::now() + Duration::from_millis(time_ms as u64));
+        let max_depth = max_depth.max(1);
+        let best_move = |mv: Option<Move>| mv.map_or(ptr::null_mut(), |mv| to_c_string(mv.to_string()));
+        *result = if state.players().len() > 2 {
+            let found = MultiSearch::new(Strategy::Paranoid).with_deadline(deadline).search_iterative(state, max_depth);
+            QawaleSearchResult{best_move: best_move(found.best_move), score: found.score, depth: found.depth, nodes: found.nodes}
+        } else {
+            let found = Search::new(&LineEvaluator).with_deadline(deadline).search_iterative(state, max_depth);
+            QawaleSearchResult{best_move: best_move(found.best_move), score: found.score, depth: found.depth, nodes: found.nodes}
+        };
+        Ok(0)
+    })
+}
@@ -0,0 +1,794 @@
+//! A WebSocket server hosting live games between people and bots, built with the
+//! `game-server` feature.
+use crate::agent::{agent_from_spec, AgentSpec};
+use crate::error::Error;
+use crate::game_state::{GameState, Move, Outcome, PlayerColor, MAX_PLAYERS};
+use crate::record::{result_string, GameRecord};
+use crate::rules::Rules;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::io::ErrorKind;
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Sender};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+use tungstenite::Message;
+
+// How often connections check for messages to send, and the clocks for flags.
+const POLL: Duration = Duration::from_millis(50);
+
+// How long a game goes on with none of its players in the room before it is abandoned.
+const ABANDONED_AFTER: Duration = Duration::from_secs(300);
+
+/// GameServerConfig is where the server listens and the games it hosts.
+#[derive(Clone, Debug)]
+pub struct GameServerConfig {
+    /// The address to listen on, e.g. "127.0.0.1:8081".
+    pub address: String,
+    /// Finished two player games are appended here.
+    pub records: Option<String>,
+    /// The time control for games that don't ask for their own: each player's time, if the games
+    /// are timed, and the time added after each of their moves.
+    pub clock: Option<Duration>,
+    /// See clock.
+    pub increment: Duration,
+}
+
+// Who sits in a colour's seat. Humans keep their seat when they leave or disconnect, so they, or
+// someone else, can take it back by joining as that colour.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Seat {
+    Open,
+    Human{connection: u64, name: String},
+    Bot{spec: String},
+}
+
+#[derive(Clone, Debug)]
+struct Clock {
+    left: [Duration; MAX_PLAYERS],
+    increment: Duration,
+    turn_started: Instant,
+}
+
+struct Room {
+    start: GameState,
+    state: GameState,
+    moves: Vec<Move>,
+    // Indexed by PlayerColor.
+    seats: [Seat; MAX_PLAYERS],
+    clock: Option<Clock>,
+    started: bool,
+    // When the last of the human players left the game, if none has come back since.
+    deserted: Option<Instant>,
+    // How the game ended, and why: "line", "draw", "resignation", "time" or "abandoned".
+    result: Option<(Outcome, &'static str)>,
+}
+
+impl Room {
+    fn is_over(&self) -> bool {
+        self.result.is_some()
+    }
+
+    // The time the player has left, counting down on their turn.
+    fn time_left(&self, player: PlayerColor) -> Option<Duration> {
+        let clock = self.clock.as_ref()?;
+        let left = clock.left[player as usize];
+        if self.started && !self.is_over() && player == self.state.player_to_move() {
+            return Some(left.saturating_sub(clock.turn_started.elapsed()));
+        }
+        Some(left)
+    }
+}
+
+// A bot's turn to move, to be worked out away from the lobby's lock.
+struct BotTurn {
+    room: u64,
+    ply: usize,
+    state: GameState,
+    spec: String,
+}
+
+/// Lobby is everything the game server knows: the open connections, each with a channel to the
+/// thread writing to its socket, and the rooms they play in.
+struct Lobby {
+    config: GameServerConfig,
+    next_connection: u64,
+    next_room: u64,
+    connections: HashMap<u64, Sender<String>>,
+    rooms: BTreeMap<u64, Room>,
+    // The room each connection is in, as a player or a spectator.
+    membership: HashMap<u64, u64>,
+}
+
+type Shared = Arc<Mutex<Lobby>>;
+
+// Messages are handled so that a panic leaves the lobby as it was or nearly so, and one game's
+// bug is better than taking down every game, so a poisoned lock is used anyway.
+fn lock(shared: &Shared) -> MutexGuard<'_, Lobby> {
+    shared.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Hosts live games over WebSocket until the process is killed. Clients send JSON messages with a
+/// "type" of:
+///   list                  the rooms, with their open seats
+///   create                a room, with optional "rules", "position", "colour" (W by default),
+///                         "name", "clock_ms", "increment_ms" and "bots": [{"spec", "colour"}]
+///   join                  "room", with an optional "colour"; with none, the first open seat, or
+///                         as a spectator if there are none or "spectate" is true
+///   bot                   "spec" and optional "colour": seats a bot in the room before it starts
+///   move                  "move" in compact notation
+///   resign, leave
+/// The server answers with "rooms", "joined" and "error" messages, and sends everyone in a room a
+/// "state" message whenever it changes. Games start once every seat is taken. A player who runs
+/// out of time or resigns loses, and the game goes to the next player in turn. Players who leave
+/// or disconnect keep their seat and can join again to take it back, or someone else can; a game
+/// none of its players has been in for five minutes is abandoned, and lost by whichever of them
+/// moves next.
+pub fn serve(config: GameServerConfig) -> Result<(), Error> {
+    let listener = TcpListener::bind(&config.address).map_err(Error::io(&config.address))?;
+    println!("hosting games on ws://{}", config.address);
+    let shared = Arc::new(Mutex::new(Lobby::new(config)));
+    let clocks = Arc::clone(&shared);
+    thread::spawn(move || loop {
+        thread::sleep(POLL);
+        lock(&clocks).check_flags();
+    });
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else { continue };
+        let shared = Arc::clone(&shared);
+        thread::spawn(move || connection(shared, stream));
+    }
+    Ok(())
+}
+
+fn connection(shared: Shared, stream: TcpStream) {
+    let Ok(mut socket) = tungstenite::accept(stream) else { return };
+    // Reads time out so that the thread can also send what the lobby has for the client.
+    if socket.get_ref().set_read_timeout(Some(POLL)).is_err() {
+        return;
+    }
+    let (sender, outgoing) = mpsc::channel();
+    let id = lock(&shared).connect(sender);
+    'open: loop {
+        match socket.read() {
+            Ok(Message::Text(text)) => handle_message(&shared, id, text.as_str()),
+            Ok(Message::Close(_)) => break,
+            Ok(_) => (),
+            Err(tungstenite::Error::Io(e)) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => (),
+            Err(_) => break,
+        }
+        while let Ok(text) = outgoing.try_recv() {
+            if socket.send(Message::text(text)).is_err() {
+                break 'open;
+            }
+        }
+    }
+    lock(&shared).disconnect(id);
+}
+
+fn handle_message(shared: &Shared, connection: u64, text: &str) {
+    let bot = {
+        let mut lobby = lock(shared);
+        match panic::catch_unwind(AssertUnwindSafe(|| lobby.handle(connection, text))) {
+            Ok(Ok(bot)) => bot,
+            Ok(Err(e)) => {
+                lobby.send(connection, json!({"type": "error", "message": e}));
+                None
+            }
+            Err(_) => {
+                lobby.send(connection, json!({"type": "error", "message": "internal error"}));
+                None
+            }
+        }
+    };
+    if let Some(turn) = bot {
+        spawn_bot(shared, turn);
+    }
+}
+
+fn spawn_bot(shared: &Shared, turn: BotTurn) {
+    let shared = Arc::clone(shared);
+    thread::spawn(move || {
+        // The spec was checked when the bot was seated, so this only fails if, say, its weights
+        // file has gone, or the bot has a bug, and then the bot resigns.
+        let mv = panic::catch_unwind(|| {
+            agent_from_spec(&turn.spec, rand::random()).ok().map(|mut agent| agent.choose_move(&turn.state))
+        }).ok().flatten();
+        let next = lock(&shared).bot_move(turn.room, turn.ply, mv);
+        if let Some(next) = next {
+            spawn_bot(&shared, next);
+        }
+    });
+}
+
+impl Lobby {
+    fn new(config: GameServerConfig) -> Self {
+        Lobby {
+            config,
+            next_connection: 1,
+            next_room: 1,
+            connections: HashMap::new(),
+            rooms: BTreeMap::new(),
+            membership: HashMap::new(),
+        }
+    }
+
+    fn connect(&mut self, sender: Sender<String>) -> u64 {
+        let id = self.next_connection;
+        self.next_connection += 1;
+        self.connections.insert(id, sender);
+        id
+    }
+
+    fn disconnect(&mut self, connection: u64) {
+        // Gone first, so that the room is told they are no longer connected.
+        self.connections.remove(&connection);
+        self.leave(connection);
+    }
+
+    fn send(&self, connection: u64, message: Value) {
+        if let Some(sender) = self.connections.get(&connection) {
+            // The connection may be closing, in which case it no longer matters.
+            let _ = sender.send(message.to_string());
+        }
+    }
+
+    fn broadcast(&self, room_id: u64) {
+        let Some(room) = self.rooms.get(&room_id) else { return };
+        let message = self.state_json(room_id, room);
+        for (&member, _) in self.membership.iter().filter(|(_, &r)| r == room_id) {
+            self.send(member, message.clone());
+        }
+    }
+
+    // Whether the seat is a human's who is in the room, rather than one who has left it or
+    // disconnected.
+    fn present(&self, room_id: u64, seat: &Seat) -> bool {
+        matches!(seat, Seat::Human{connection, ..} if self.membership.get(connection) == Some(&room_id))
+    }
+
+    fn handle(&mut self, connection: u64, text: &str) -> Result<Option<BotTurn>, String> {
+        let message: Value = serde_json::from_str(text).map_err(|e| format!("invalid JSON: {e}"))?;
+        let field = |key: &str| message.get(key).filter(|v| !v.is_null());
+        let text = |key: &str| field(key).map(|v| v.as_str().ok_or_else(|| format!("{key} should be a string"))).transpose();
+        let colour = |key: &str| -> Result<Option<PlayerColor>, String> {
+            text(key)?.map(|c| {
+                let mut chars = c.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => PlayerColor::from_letter(c),
+                    _ => None,
+                }.ok_or_else(|| format!("unknown colour {c:?}"))
+            }).transpose()
+        };
+        let millis = |key: &str| field(key).map(|v| v.as_u64().map(Duration::from_millis).ok_or_else(|| format!("{key} should be a whole number"))).transpose();
+        match text("type")?.ok_or("missing type")? {
+            "list" => {
+                self.send(connection, self.rooms_json());
+                Ok(None)
+            }
+            "create" => {
+                let rules: Rules = text("rules")?.unwrap_or("standard").parse()?;
+                let start = match text("position")? {
+                    Some(position) => GameState::parse_position(position, rules)?,
+                    None => GameState::starting_state_with(rules)?,
+                };
+                let clock = match millis("clock_ms")? {
+                    Some(time) if time.is_zero() => None,
+                    Some(time) => Some(time),
+                    None => self.config.clock,
+                };
+                let increment = millis("increment_ms")?.unwrap_or(self.config.increment);
+                let mut room = Room {
+                    start,
+                    state: start,
+                    moves: Vec::new(),
+                    seats: std::array::from_fn(|_| Seat::Open),
+                    clock: clock.map(|time| Clock{left: [time; MAX_PLAYERS], increment, turn_started: Instant::now()}),
+                    started: false,
+                    deserted: None,
+                    result: None,
+                };
+                let own = colour("colour")?.unwrap_or(PlayerColor::White);
+                if !start.players().contains(&own) {
+                    return Err(format!("{own:?} is not playing"));
+                }
+                let name = text("name")?.unwrap_or("anonymous").to_string();
+                room.seats[own as usize] = Seat::Human{connection, name};
+                for bot in field("bots").map(|b| b.as_array().ok_or("bots should be a list")).transpose()?.into_iter().flatten() {
+                    let spec = bot.get("spec").and_then(Value::as_str).ok_or("every bot needs a spec")?;
+                    let colour = match bot.get("colour").and_then(Value::as_str) {
+                        Some(c) => c.chars().next().and_then(PlayerColor::from_letter).ok_or_else(|| format!("unknown colour {c:?}"))?,
+                        None => first_open(&room).ok_or("there are no open seats for the bots")?,
+                    };
+                    seat_bot(&mut room, colour, spec)?;
+                }
+                self.leave(connection);
+                let room_id = self.next_room;
+                self.next_room += 1;
+                self.rooms.insert(room_id, room);
+                self.membership.insert(connection, room_id);
+                self.send(connection, json!({"type": "joined", "room": room_id, "colour": own.letter().to_string()}));
+                Ok(self.changed(room_id))
+            }
+            "join" => {
+                let room_id = field("room").and_then(Value::as_u64).ok_or("missing room")?;
+                let room = self.rooms.get(&room_id).ok_or_else(|| format!("there is no room {room_id}"))?;
+                let spectate = field("spectate").and_then(Value::as_bool).unwrap_or(false);
+                let seat = match colour("colour")? {
+                    _ if spectate => None,
+                    Some(c) if !room.state.players().contains(&c) => return Err(format!("{c:?} is not playing")),
+                    Some(c) => match &room.seats[c as usize] {
+                        Seat::Open => Some(c),
+                        seat @ Seat::Human{..} if !self.present(room_id, seat) => Some(c),
+                        _ => return Err(format!("{c:?} is taken")),
+                    },
+                    // A player who left takes their own seat back.
+                    None => room.state.players().iter().copied()
+                        .find(|&p| matches!(&room.seats[p as usize], Seat::Human{connection: c, ..} if *c == connection))
+                        .or_else(|| first_open(room)),
+                };
+                let name = text("name")?.unwrap_or("anonymous").to_string();
+                // Leaving would remove the room if it is theirs alone, so those joining the room
+                // they are in only give up their seat.
+                match self.membership.get(&connection) == Some(&room_id) {
+                    true => vacate(self.rooms.get_mut(&room_id).expect("membership should be of a room"), connection),
+                    false => self.leave(connection),
+                }
+                let room = self.rooms.get_mut(&room_id).expect("room should still be there");
+                if let Some(c) = seat {
+                    room.seats[c as usize] = Seat::Human{connection, name};
+                }
+                self.membership.insert(connection, room_id);
+                let colour = seat.map(|c| c.letter().to_string());
+                self.send(connection, json!({"type": "joined", "room": room_id, "colour": colour}));
+                Ok(self.changed(room_id))
+            }
+            "bot" => {
+                let room_id = self.seated_room(connection)?;
+                let spec = text("spec")?.ok_or("missing spec")?;
+                let room = self.rooms.get_mut(&room_id).expect("membership should be of a room");
+                if room.started {
+                    return Err("the game has already started".to_string());
+                }
+                let colour = match colour("colour")? {
+                    Some(c) => c,
+                    None => first_open(room).ok_or("there are no open seats")?,
+                };
+                seat_bot(room, colour, spec)?;
+                Ok(self.changed(room_id))
+            }
+            "move" => {
+                let room_id = self.seated_room(connection)?;
+                let notation = text("move")?.ok_or("missing move")?;
+                let room = &self.rooms[&room_id];
+                if !room.started || room.is_over() {
+                    return Err("the game is not being played".to_string());
+                }
+                let to_move = room.state.player_to_move();
+                if !matches!(&room.seats[to_move as usize], Seat::Human{connection: c, ..} if *c == connection) {
+                    return Err(format!("it is {to_move:?}'s turn"));
+                }
+                let mv = room.state.parse_move(notation)?;
+                self.play(room_id, mv);
+                Ok(self.changed(room_id))
+            }
+            "resign" => {
+                let room_id = self.seated_room(connection)?;
+                let room = &self.rooms[&room_id];
+                if !room.started || room.is_over() {
+                    return Err("the game is not being played".to_string());
+                }
+                let loser = room.state.players().iter().copied()
+                    .find(|&p| matches!(&room.seats[p as usize], Seat::Human{connection: c, ..} if *c == connection))
+                    .expect("a seated connection should have a colour");
+                self.finish(room_id, Outcome::Win(loser.next(room.state.players().len() as u8)), "resignation");
+                Ok(None)
+            }
+            "leave" => {
+                self.leave(connection);
+                self.send(connection, self.rooms_json());
+                Ok(None)
+            }
+            other => Err(format!("unknown message type {other:?}")),
+        }
+    }
+
+    // The room the connection has a seat in.
+    fn seated_room(&self, connection: u64) -> Result<u64, String> {
+        let room_id = *self.membership.get(&connection).ok_or("you are not in a room")?;
+        let seated = self.rooms[&room_id].seats.iter().any(|seat| matches!(seat, Seat::Human{connection: c, ..} if *c == connection));
+        seated.then_some(room_id).ok_or_else(|| "spectators can't do that".to_string())
+    }
+
+    fn leave(&mut self, connection: u64) {
+        let Some(room_id) = self.membership.remove(&connection) else { return };
+        let Some(room) = self.rooms.get_mut(&room_id) else { return };
+        vacate(room, connection);
+        self.broadcast(room_id);
+        self.tidy(room_id);
+    }
+
+    // Notes when a game's players have all gone, and removes rooms no one is in, except for games
+    // still being played, which the players can come back to until they are abandoned.
+    fn tidy(&mut self, room_id: u64) {
+        let Some(room) = self.rooms.get(&room_id) else { return };
+        let playing = room.started && !room.is_over();
+        let deserted = !room.seats.iter().any(|seat| self.present(room_id, seat));
+        if !playing && self.membership.values().all(|&r| r != room_id) {
+            self.rooms.remove(&room_id);
+            return;
+        }
+        let room = self.rooms.get_mut(&room_id).expect("the room should exist");
+        room.deserted = match deserted {
+            true => room.deserted.or(Some(Instant::now())),
+            false => None,
+        };
+    }
+
+    // Starts the game once every seat is taken, tells everyone in the room, and returns the bot's
+    // turn if it is one.
+    fn changed(&mut self, room_id: u64) -> Option<BotTurn> {
+        let room = self.rooms.get_mut(&room_id)?;
+        if !room.started && room.state.players().iter().all(|&p| room.seats[p as usize] != Seat::Open) {
+            room.started = true;
+            if let Some(clock) = &mut room.clock {
+                clock.turn_started = Instant::now();
+            }
+        }
+        self.broadcast(room_id);
+        self.tidy(room_id);
+        self.bot_turn(room_id)
+    }
+
+    fn bot_turn(&self, room_id: u64) -> Option<BotTurn> {
+        let room = self.rooms.get(&room_id)?;
+        if !room.started || room.is_over() {
+            return None;
+        }
+        match &room.seats[room.state.player_to_move() as usize] {
+            Seat::Bot{spec} => Some(BotTurn{room: room_id, ply: room.moves.len(), state: room.state, spec: spec.clone()}),
+            _ => None,
+        }
+    }
+
+    // Plays the bot's move, unless the game has moved on since it was asked, e.g. by ending on time.
+    fn bot_move(&mut self, room_id: u64, ply: usize, mv: Option<Move>) -> Option<BotTurn> {
+        let room = self.rooms.get(&room_id)?;
+        if room.moves.len() != ply || room.is_over() {
+            return None;
+        }
+        match mv {
+            Some(mv) => self.play(room_id, mv),
+            None => {
+                let bot = room.state.player_to_move();
+                self.finish(room_id, Outcome::Win(bot.next(room.state.players().len() as u8)), "resignation");
+            }
+        }
+        self.changed(room_id)
+    }
+
+    fn play(&mut self, room_id: u64, mv: Move) {
+        let room = self.rooms.get_mut(&room_id).expect("the room should exist");
+        let mover = room.state.player_to_move();
+        if let Some(clock) = &mut room.clock {
+            let left = &mut clock.left[mover as usize];
+            *left = left.saturating_sub(clock.turn_started.elapsed()) + clock.increment;
+            clock.turn_started = Instant::now();
+        }
+        room.state.apply_move(&mv);
+        room.moves.push(mv);
+        match room.state.outcome() {
+            Some(outcome @ Outcome::Win(_)) => self.finish(room_id, outcome, "line"),
+            Some(outcome @ Outcome::Draw) => self.finish(room_id, outcome, "draw"),
+            None => (),
+        }
+    }
+
+    // Ends the games of players who have run out of time, lost by the player to move, and those
+    // abandoned by their players, lost by the first of them to move from now.
+    fn check_flags(&mut self) {
+        let flagged: Vec<(u64, PlayerColor, &'static str)> = self.rooms.iter()
+            .filter(|(_, room)| room.started && !room.is_over())
+            .filter_map(|(&id, room)| {
+                let player = room.state.player_to_move();
+                if room.time_left(player).is_some_and(|left| left.is_zero()) {
+                    Some((id, player, "time"))
+                } else if room.deserted.is_some_and(|since| since.elapsed() >= ABANDONED_AFTER) {
+                    let players = room.state.players().len() as u8;
+                    let human = std::iter::successors(Some(player), |p| Some(p.next(players)))
+                        .find(|&p| matches!(room.seats[p as usize], Seat::Human{..}))
+                        .expect("a started game should have a human player");
+                    Some((id, human, "abandoned"))
+                } else {
+                    None
+                }
+            })
+            .collect();
+        for (room_id, player, reason) in flagged {
+            let players = self.rooms[&room_id].state.players().len() as u8;
+            self.finish(room_id, Outcome::Win(player.next(players)), reason);
+            self.tidy(room_id);
+        }
+    }
+
+    // Ends the game, saving it if it is a two player game and there is a records file, and tells
+    // everyone in the room.
+    fn finish(&mut self, room_id: u64, outcome: Outcome, reason: &'static str) {
+        let room = self.rooms.get_mut(&room_id).expect("the room should exist");
+        if let Some(clock) = &mut room.clock {
+            let mover = room.state.player_to_move() as usize;
+            clock.left[mover] = clock.left[mover].saturating_sub(clock.turn_started.elapsed());
+        }
+        room.result = Some((outcome, reason));
+        if let (Some(path), 2) = (&self.config.records, room.state.players().len()) {
+            let mut record = GameRecord::new(seat_name(&room.seats[PlayerColor::White as usize]), seat_name(&room.seats[PlayerColor::Red as usize]));
+            record.set_start(&room.start);
+            (record.moves, record.outcome) = (room.moves.clone(), Some(outcome));
+            if let Err(e) = record.append_to(path) {
+                eprintln!("{path}: {e}");
+            }
+        }
+        self.broadcast(room_id);
+    }
+
+    fn rooms_json(&self) -> Value {
+        let rooms: Vec<Value> = self.rooms.iter().map(|(id, room)| {
+            // Seats left by players who may come back are open to anyone.
+            let open: Vec<String> = room.state.players().iter()
+                .filter(|&&p| match &room.seats[p as usize] {
+                    Seat::Open => true,
+                    seat @ Seat::Human{..} => !self.present(*id, seat),
+                    Seat::Bot{..} => false,
+                })
+                .map(|p| p.letter().to_string())
+                .collect();
+            json!({"room": id, "rules": room.state.rules().to_string(), "open": open, "started": room.started, "over": room.is_over()})
+        }).collect();
+        json!({"type": "rooms", "rooms": rooms})
+    }
+
+    fn state_json(&self, room_id: u64, room: &Room) -> Value {
+        let mut players = serde_json::Map::new();
+        let mut clocks = serde_json::Map::new();
+        for &p in room.state.players() {
+            let seat = match &room.seats[p as usize] {
+                Seat::Open => Value::Null,
+                seat @ Seat::Human{name, ..} => json!({"name": name, "bot": false, "connected": self.present(room_id, seat)}),
+                Seat::Bot{spec} => json!({"name": spec, "bot": true, "connected": true}),
+            };
+            players.insert(p.letter().to_string(), seat);
+            if let Some(left) = room.time_left(p) {
+                clocks.insert(p.letter().to_string(), json!(left.as_millis() as u64));
+            }
+        }
+        let (result, reason) = match room.result {
+            Some((outcome, reason)) => (Some(result_string(Some(outcome))), Some(reason)),
+            None => (None, None),
+        };
+        let legal_moves: Vec<String> = match room.started && !room.is_over() {
+            true => room.state.legal_moves().iter().map(|mv| mv.to_string()).collect(),
+            false => Vec::new(),
+        };
+        json!({
+            "type": "state",
+            "room": room_id,
+            "rules": room.state.rules().to_string(),
+            "position": room.state.position_string(),
+            "to_move": room.state.player_to_move().letter().to_string(),
+            "moves": room.moves.iter().map(|mv| mv.to_string()).collect::<Vec<_>>(),
+            "legal_moves": legal_moves,
+            "players": players,
+            "clocks_ms": if room.clock.is_some() { Value::Object(clocks) } else { Value::Null },
+            "started": room.started,
+            "result": result,
+            "reason": reason,
+        })
+    }
+}
+
+// Frees the connection's seat for others if the game hasn't started. Once it has, the seat is kept
+// for them to come back to.
+fn vacate(room: &mut Room, connection: u64) {
+    if !room.started {
+        for seat in room.seats.iter_mut() {
+            if matches!(seat, Seat::Human{connection: c, ..} if *c == connection) {
+                *seat = Seat::Open;
+            }
+        }
+    }
+}
+
+fn first_open(room: &Room) -> Option<PlayerColor> {
+    room.state.players().iter().copied().find(|&p| room.seats[p as usize] == Seat::Open)
+}
+
+fn seat_bot(room: &mut Room, colour: PlayerColor, spec: &str) -> Result<(), String> {
+    if !room.state.players().contains(&colour) {
+        return Err(format!("{colour:?} is not playing"));
+    }
+    if room.seats[colour as usize] != Seat::Open {
+        return Err(format!("{colour:?} is taken"));
+    }
+    AgentSpec::parse(spec)?.check_players(room.state.players().len())?;
+    room.seats[colour as usize] = Seat::Bot{spec: spec.to_string()};
+    Ok(())
+}
+
+fn seat_name(seat: &Seat) -> String {
+    match seat {
+        Seat::Open => String::new(),
+        Seat::Human{name, ..} => name.clone(),
+        Seat::Bot{spec} => spec.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::Receiver;
+
+    fn config() -> GameServerConfig {
+        GameServerConfig{address: String::new(), records: None, clock: None, increment: Duration::ZERO}
+    }
+
+    fn connect(lobby: &mut Lobby) -> (u64, Receiver<String>) {
+        let (sender, receiver) = mpsc::channel();
+        (lobby.connect(sender), receiver)
+    }
+
+    fn send(lobby: &mut Lobby, connection: u64, message: Value) -> Result<Option<BotTurn>, String> {
+        lobby.handle(connection, &message.to_string())
+    }
+
+    // The last message sent to a connection, of those not read yet.
+    fn last(receiver: &Receiver<String>) -> Value {
+        let text = receiver.try_iter().last().expect("there should be a message");
+        serde_json::from_str(&text).unwrap()
+    }
+
+    #[test]
+    fn rooms() {
+        let mut lobby = Lobby::new(config());
+        let (alice, alice_messages) = connect(&mut lobby);
+        let (bob, bob_messages) = connect(&mut lobby);
+        send(&mut lobby, alice, json!({"type": "create", "name": "alice"})).unwrap();
+        send(&mut lobby, bob, json!({"type": "list"})).unwrap();
+        assert_eq!(last(&bob_messages)["rooms"], json!([{"room": 1, "rules": "standard", "open": ["R"], "started": false, "over": false}]));
+
+        send(&mut lobby, bob, json!({"type": "join", "room": 1, "name": "bob"})).unwrap();
+        let state = last(&alice_messages);
+        assert_eq!(state["started"], true);
+        assert_eq!(state["players"]["R"]["name"], "bob");
+        assert_eq!(state["legal_moves"].as_array().unwrap().len(), 40);
+
+        assert_eq!(send(&mut lobby, bob, json!({"type": "move", "move": "a1rrr"})).err().unwrap(), "it is White's turn");
+        send(&mut lobby, alice, json!({"type": "move", "move": "a1rrr"})).unwrap();
+        assert_eq!(last(&bob_messages)["moves"], json!(["a1rrr"]));
+
+        send(&mut lobby, bob, json!({"type": "resign"})).unwrap();
+        let state = last(&alice_messages);
+        assert_eq!((state["result"].as_str(), state["reason"].as_str()), (Some("1-0"), Some("resignation")));
+    }
+
+    #[test]
+    fn invalid_rooms_are_refused() {
+        let mut lobby = Lobby::new(config());
+        let (alice, _) = connect(&mut lobby);
+        let tall = json!({"type": "create", "position": "nnnnnnnnnnnnnnnnnnnnnnnn,,,/,,,/,,,/,,, W", "bots": [{"spec": "random"}]});
+        assert!(send(&mut lobby, alice, tall).is_err());
+        let search = json!({"type": "create", "rules": "three-player", "bots": [{"spec": "search"}]});
+        assert!(send(&mut lobby, alice, search).is_err());
+        assert!(lobby.rooms.is_empty());
+    }
+
+    #[test]
+    fn bots_take_their_turns() {
+        let mut lobby = Lobby::new(config());
+        let (alice, alice_messages) = connect(&mut lobby);
+        let create = json!({"type": "create", "colour": "R", "bots": [{"spec": "greedy", "colour": "W"}]});
+        let turn = send(&mut lobby, alice, create).unwrap().expect("the bot should move first");
+        let mv = agent_from_spec(&turn.spec, 0).unwrap().choose_move(&turn.state);
+        assert!(lobby.bot_move(turn.room, turn.ply, Some(mv)).is_none());
+        assert_eq!(last(&alice_messages)["to_move"], "R");
+        // A move for a turn that has passed is ignored.
+        assert!(lobby.bot_move(turn.room, turn.ply, Some(mv)).is_none());
+        assert_eq!(lobby.rooms[&turn.room].moves.len(), 1);
+    }
+
+    #[test]
+    fn clocks() {
+        let mut lobby = Lobby::new(config());
+        let (alice, alice_messages) = connect(&mut lobby);
+        let (bob, _) = connect(&mut lobby);
+        send(&mut lobby, alice, json!({"type": "create", "clock_ms": 200, "increment_ms": 1000})).unwrap();
+        send(&mut lobby, bob, json!({"type": "join", "room": 1})).unwrap();
+        send(&mut lobby, alice, json!({"type": "move", "move": "a1rrr"})).unwrap();
+        let clocks = &last(&alice_messages)["clocks_ms"];
+        assert!(clocks["W"].as_u64().unwrap() > 1000, "White should have had the increment: {clocks}");
+        // Red's clock is running now.
+        assert!((100..=200).contains(&clocks["R"].as_u64().unwrap()), "Red should have about 200ms: {clocks}");
+
+        thread::sleep(Duration::from_millis(250));
+        lobby.check_flags();
+        let state = last(&alice_messages);
+        assert_eq!((state["result"].as_str(), state["reason"].as_str()), (Some("1-0"), Some("time")));
+        assert_eq!(state["clocks_ms"]["R"], 0);
+    }
+
+    #[test]
+    fn leaving_and_coming_back() {
+        let mut lobby = Lobby::new(config());
+        let (alice, alice_messages) = connect(&mut lobby);
+        let (bob, _) = connect(&mut lobby);
+        send(&mut lobby, alice, json!({"type": "create", "name": "alice"})).unwrap();
+        send(&mut lobby, bob, json!({"type": "join", "room": 1, "name": "bob"})).unwrap();
+        send(&mut lobby, alice, json!({"type": "leave"})).unwrap();
+        assert_eq!(last(&alice_messages)["rooms"][0]["open"], json!(["W"]));
+        send(&mut lobby, alice, json!({"type": "join", "room": 1, "colour": "W"})).unwrap();
+        send(&mut lobby, alice, json!({"type": "move", "move": "a1rrr"})).unwrap();
+        assert_eq!(last(&alice_messages)["moves"], json!(["a1rrr"]));
+
+        // Without a colour, a player who left takes their own seat back.
+        send(&mut lobby, bob, json!({"type": "leave"})).unwrap();
+        send(&mut lobby, bob, json!({"type": "join", "room": 1})).unwrap();
+        let state = last(&alice_messages);
+        assert_eq!(state["players"]["R"], json!({"name": "anonymous", "bot": false, "connected": true}));
+        send(&mut lobby, bob, json!({"type": "move", "move": "d4lll"})).unwrap();
+
+        // A seat someone is in can't be taken.
+        let (carol, _) = connect(&mut lobby);
+        assert_eq!(send(&mut lobby, carol, json!({"type": "join", "room": 1, "colour": "W"})).err().unwrap(), "White is taken");
+    }
+
+    #[test]
+    fn disconnecting_from_a_bot_game() {
+        let path = std::env::temp_dir().join(format!("qawale-game-server-abandoned-{}.txt", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        let mut lobby = Lobby::new(GameServerConfig{records: Some(path.clone()), ..config()});
+        let (alice, _) = connect(&mut lobby);
+        send(&mut lobby, alice, json!({"type": "create", "name": "alice", "bots": [{"spec": "random"}]})).unwrap();
+        send(&mut lobby, alice, json!({"type": "move", "move": "a1rrr"})).unwrap();
+        lobby.disconnect(alice);
+        // The game waits for them to come back.
+        let room = &lobby.rooms[&1];
+        assert!(room.deserted.is_some() && !room.is_over());
+        let (again, again_messages) = connect(&mut lobby);
+        send(&mut lobby, again, json!({"type": "join", "room": 1, "colour": "W"})).unwrap();
+        assert_eq!(last(&again_messages)["players"]["W"]["connected"], true);
+        assert!(lobby.rooms[&1].deserted.is_none());
+
+        // Until it is abandoned and lost by them, even on the bot's turn, then it is saved and removed.
+        lobby.disconnect(again);
+        let room = lobby.rooms.get_mut(&1).unwrap();
+        room.deserted = Instant::now().checked_sub(ABANDONED_AFTER);
+        assert_eq!(room.state.player_to_move(), PlayerColor::Red);
+        lobby.check_flags();
+        assert!(lobby.rooms.is_empty());
+        let records = GameRecord::read_all(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].outcome, Some(Outcome::Win(PlayerColor::Red)));
+    }
+
+    #[test]
+    fn records() {
+        let path = std::env::temp_dir().join(format!("qawale-game-server-records-{}.txt", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        let mut lobby = Lobby::new(GameServerConfig{records: Some(path.clone()), ..config()});
+        let (alice, _) = connect(&mut lobby);
+        let (bob, _) = connect(&mut lobby);
+        send(&mut lobby, alice, json!({"type": "create", "name": "alice"})).unwrap();
+        send(&mut lobby, bob, json!({"type": "join", "room": 1, "name": "bob"})).unwrap();
+        send(&mut lobby, alice, json!({"type": "move", "move": "a1rrr"})).unwrap();
+        send(&mut lobby, bob, json!({"type": "resign"})).unwrap();
+
+        let records = GameRecord::read_all(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!((records[0].white.as_str(), records[0].red.as_str()), ("alice", "bob"));
+        assert_eq!(records[0].moves.iter().map(|mv| mv.to_string()).collect::<Vec<_>>(), ["a1rrr"]);
+        assert_eq!(records[0].outcome, Some(Outcome::Win(PlayerColor::White)));
+    }
+}
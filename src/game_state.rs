@@ -1,88 +1,371 @@
-use tinyvec::ArrayVec;
+//! The state of a game: the board, whose turn it is and the stones each player has left.
+use std::fmt;
 
+use crate::error::Error;
 use crate::path::PathGenerator;
-use crate::{stone::Stone, stone_pile::PileTop, path::Path, board::Board, position::{Position, NUM_PILES}};
+use crate::rules::Rules;
+use crate::{stone::Stone, stone_pile::PileTop, path::Path, board::Board, position::{Position, BOARD_SIZE}};
 
+/// Games have two players unless the rules add Blue, then Green.
+pub const MAX_PLAYERS: usize = 4;
+
+/// PlayerColor is a player, named by the colour of their stones. White moves first.
 #[derive(PartialEq, Eq, Copy, Clone, Debug, Hash)]
 #[repr(u8)]
 pub enum PlayerColor {
+    /// The second player.
     Red,
-    White
+    /// The first player.
+    White,
+    /// The third player, in games of three or four players.
+    Blue,
+    /// The fourth player, in games of four players.
+    Green,
 }
 
+// The order players take their turns in. Games of fewer players leave out those at the end.
+const TURN_ORDER: [PlayerColor; MAX_PLAYERS] = [PlayerColor::White, PlayerColor::Red, PlayerColor::Blue, PlayerColor::Green];
+
 impl PlayerColor {
+    /// Every colour, in the order of their values.
+    pub const ALL: [PlayerColor; MAX_PLAYERS] = [PlayerColor::Red, PlayerColor::White, PlayerColor::Blue, PlayerColor::Green];
+
+    /// The players of a game of `players` players, in turn order.
+    pub fn in_turn_order(players: u8) -> &'static [PlayerColor] {
+        &TURN_ORDER[..players as usize]
+    }
+
+    /// The player whose stone tops the pile, if a player's stone does.
     pub fn from_pile_top(pile_top: PileTop) -> Option<PlayerColor> {
         match pile_top {
             PileTop::Empty => None,
             PileTop::NeutralStone => None,
             PileTop::RedStone => Some(PlayerColor::Red),
             PileTop::WhiteStone => Some(PlayerColor::White),
+            PileTop::BlueStone => Some(PlayerColor::Blue),
+            PileTop::GreenStone => Some(PlayerColor::Green),
         }
     }
 
-    pub fn opponent(&self) -> PlayerColor {
+    /// How many turns after White's the player moves.
+    pub fn turn_index(&self) -> usize {
         match self {
-            PlayerColor::Red => PlayerColor::White,
-            PlayerColor::White => PlayerColor::Red
+            PlayerColor::White => 0,
+            PlayerColor::Red => 1,
+            PlayerColor::Blue => 2,
+            PlayerColor::Green => 3,
         }
     }
 
+    /// The player who moves after this one in a game of `players` players. In a two player game,
+    /// the opponent.
+    pub fn next(&self, players: u8) -> PlayerColor {
+        let i = self.turn_index() + 1;
+        TURN_ORDER[if i >= players as usize { 0 } else { i }]
+    }
+
+    /// The player who moved before this one in a game of `players` players.
+    pub fn previous(&self, players: u8) -> PlayerColor {
+        match self.turn_index() {
+            0 => TURN_ORDER[players as usize - 1],
+            i => TURN_ORDER[i - 1],
+        }
+    }
+
+    /// The player's stones.
     pub fn stone_color(&self) -> Stone {
         match self {
             PlayerColor::Red => Stone::Red,
-            PlayerColor::White => Stone::White
+            PlayerColor::White => Stone::White,
+            PlayerColor::Blue => Stone::Blue,
+            PlayerColor::Green => Stone::Green,
+        }
+    }
+
+    /// The top of a pile topped by the player's stones.
+    pub fn pile_top(&self) -> PileTop {
+        match self {
+            PlayerColor::Red => PileTop::RedStone,
+            PlayerColor::White => PileTop::WhiteStone,
+            PlayerColor::Blue => PileTop::BlueStone,
+            PlayerColor::Green => PileTop::GreenStone,
         }
     }
+
+    /// The letter for the player's stones, which also names the player to move in position strings.
+    pub fn letter(&self) -> char {
+        match self {
+            PlayerColor::Red => 'R',
+            PlayerColor::White => 'W',
+            PlayerColor::Blue => 'B',
+            PlayerColor::Green => 'G',
+        }
+    }
+
+    /// The player written as the letter, if any.
+    pub fn from_letter(c: char) -> Option<PlayerColor> {
+        PlayerColor::ALL.into_iter().find(|p| p.letter() == c)
+    }
 }
 
+/// Move is a turn: the mover's stone is put on top of the pile at the start of the path, then the
+/// pile is picked up and spread along the path, a stone per step from the bottom.
 #[derive(PartialEq, Eq, Copy, Clone, Debug, Hash)]
-pub struct Move {
+pub struct Move<const N: usize = { BOARD_SIZE as usize }> {
+    /// The stone the mover adds, always of their own colour.
     pub new_stone: Stone,
-    pub path: Path,
+    /// Where the pile is spread, as many steps as it then has stones.
+    pub path: Path<N>,
 }
 
-pub struct MoveGenerator {
-    piles: ArrayVec<[u8; NUM_PILES]>,
+// Moves are written in the compact path notation; the stone placed is implied by whose turn it is.
+impl<const N: usize> fmt::Display for Move<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.path.notation())
+    }
+}
+
+/// The paths of the moves on a board, as described for new. It is what GameState::legal_moves
+/// uses, without the stone or the check that the game is over.
+pub struct MoveGenerator<const N: usize = { BOARD_SIZE as usize }> {
+    // The height of each pile, indexed by row, then column.
+    piles: [[u8; N]; N],
     start_index: usize,
-    path_generator: PathGenerator,
+    rules: Rules,
+    path_generator: PathGenerator<N>,
 }
 
-impl MoveGenerator {
-    // MoveGenerator iterates all the possible moves that can be made on a board.
-    pub fn new(b: &Board) -> MoveGenerator {
-        let mut piles = ArrayVec::<[u8; NUM_PILES]>::new();
-        for (_, p) in b.iter_piles() {
-            piles.push(p.height());
-        }
-        let mut start_index: usize = 0;
-        for i in 0..NUM_PILES {
-            if piles[i] > 0 {
-                start_index = i;
-                break;
-            }
+impl<const N: usize> MoveGenerator<N> {
+    /// MoveGenerator iterates all the possible moves that can be made on a board under the rules.
+    pub fn new(b: &Board<N>, rules: &Rules) -> Self {
+        let mut piles = [[0; N]; N];
+        for ((_, pile), height) in b.iter_piles().zip(piles.as_flattened_mut()) {
+            *height = pile.height();
         }
+        let start_index = (0..Position::<N>::NUM_PILES)
+            .find(|&i| Self::height(&piles, i) > 0)
+            .unwrap_or(0);
         MoveGenerator {
             piles,
             start_index: start_index,
-            path_generator: PathGenerator::new(Position::from_index(start_index), piles[start_index]+1),
+            rules: *rules,
+            path_generator: PathGenerator::new(Position::from_index(start_index), Self::height(&piles, start_index)+1, rules),
         }
     }
+
+    fn height(piles: &[[u8; N]; N], index: usize) -> u8 {
+        piles.as_flattened()[index]
+    }
 }
 
-impl Iterator for MoveGenerator {
+impl<const N: usize> Iterator for MoveGenerator<N> {
     // We can refer to this type using Self::Item
-    type Item = Path;
+    type Item = Path<N>;
 
     fn next(&mut self) -> Option<Self::Item> {
         let mut path = self.path_generator.next();
-        while path == None && self.start_index < NUM_PILES-1 {
+        while path == None && self.start_index < Position::<N>::NUM_PILES-1 {
             self.start_index += 1;
-            if self.piles[self.start_index] == 0 {
+            let height = Self::height(&self.piles, self.start_index);
+            if height == 0 {
                 continue;
             }
-            self.path_generator = PathGenerator::new(Position::from_index(self.start_index), self.piles[self.start_index]+1);
+            self.path_generator = PathGenerator::new(Position::from_index(self.start_index), height+1, &self.rules);
             path = self.path_generator.next();
         }
         path
     }
-}
\ No newline at end of file
+}
+/// Each player starts the game with this many stones in hand.
+pub const STONES_PER_PLAYER: u8 = 8;
+
+/// Outcome is how a game ended.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Hash)]
+pub enum Outcome {
+    /// The player topped a winning line.
+    Win(PlayerColor),
+    /// The player to move had no stones left.
+    Draw,
+}
+
+/// GameState is a board together with whose turn it is, the stones each player has left to place
+/// and the rules the game is played by.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Hash)]
+pub struct GameState {
+    board: Board,
+    to_move: PlayerColor,
+    // Indexed by PlayerColor. Colours not playing have none.
+    stones_left: [u8; MAX_PLAYERS],
+    rules: Rules,
+}
+
+impl GameState {
+    /// The state as given, without checking it: stones_left is indexed by PlayerColor. See
+    /// parse_position and setup::SetupBuilder for states that are checked.
+    pub fn new(board: Board, to_move: PlayerColor, stones_left: [u8; MAX_PLAYERS], rules: Rules) -> Self {
+        Self{board, to_move, stones_left, rules}
+    }
+
+    /// The start of a game played by the standard rules.
+    pub fn starting_state() -> Self {
+        Self::start(Rules::standard())
+    }
+
+    /// The start of a game played by the rules, with White to move, or an error if the rules
+    /// cannot be played on the board.
+    pub fn starting_state_with(rules: Rules) -> Result<Self, Error> {
+        rules.validate::<{ BOARD_SIZE as usize }>()?;
+        Ok(Self::start(rules))
+    }
+
+    // The starting state for rules known to be valid.
+    fn start(rules: Rules) -> Self {
+        let mut stones_left = [0; MAX_PLAYERS];
+        for &player in PlayerColor::in_turn_order(rules.players) {
+            stones_left[player as usize] = rules.stones_per_player;
+        }
+        Self::new(Board::setup(&rules), PlayerColor::White, stones_left, rules)
+    }
+
+    /// The board.
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    /// The rules the game is played by.
+    pub fn rules(&self) -> &Rules {
+        &self.rules
+    }
+
+    /// The player whose turn it is.
+    pub fn player_to_move(&self) -> PlayerColor {
+        self.to_move
+    }
+
+    /// The player who moves after the player to move; in a two player game, their opponent.
+    pub fn next_player(&self) -> PlayerColor {
+        self.to_move.next(self.rules.players)
+    }
+
+    /// The players of the game, in turn order.
+    pub fn players(&self) -> &'static [PlayerColor] {
+        PlayerColor::in_turn_order(self.rules.players)
+    }
+
+    /// The stones the player has left to place.
+    pub fn stones_left(&self, player: PlayerColor) -> u8 {
+        self.stones_left[player as usize]
+    }
+
+    /// The number of moves left before the game ends in a draw.
+    pub fn moves_left(&self) -> u8 {
+        self.stones_left.iter().sum()
+    }
+
+    /// How the game ended, or None if it goes on: a win for the player topping a line, or a draw
+    /// once the player to move has no stones left.
+    pub fn outcome(&self) -> Option<Outcome> {
+        let mover = self.to_move.previous(self.rules.players);
+        if let Some(outcome) = self.rules.line_outcome(&self.board.top(), mover) {
+            return Some(outcome);
+        }
+        if self.stones_left(self.to_move) == 0 {
+            return Some(Outcome::Draw);
+        }
+        None
+    }
+
+    /// Whether the game has ended.
+    pub fn is_over(&self) -> bool {
+        self.outcome().is_some()
+    }
+
+    /// The moves available to the player to move, or none if the game is over.
+    pub fn legal_moves(&self) -> Vec<Move> {
+        if self.is_over() {
+            return Vec::new();
+        }
+        let new_stone = self.to_move.stone_color();
+        MoveGenerator::new(&self.board, &self.rules).map(|path| Move{new_stone, path}).collect()
+    }
+
+    /// The position string for the state: piles listed row by row, each as its stones from bottom
+    /// to top, with commas between piles and slashes between rows, then the player to move. For
+    /// example the starting position is "nn,,,nn/,,,/,,,/nn,,,nn W".
+    pub fn position_string(&self) -> String {
+        format!("{} {}", self.board.position_string(), self.to_move.letter())
+    }
+
+    /// Parses a position string for a game played by the rules. The stones each player has left are
+    /// those not on the board.
+    pub fn parse_position(position: &str, rules: Rules) -> Result<GameState, Error> {
+        let message = |why: &str| format!("invalid position {position:?}: {why}");
+        let malformed = |why: &str| Error::Parse(message(why));
+        let invalid = |why: &str| Error::Invalid(message(why));
+        // The rules are checked first, as the players to move depend on them.
+        rules.validate::<{ BOARD_SIZE as usize }>()?;
+        let (piles, to_move) = position.trim().split_once(' ').ok_or_else(|| malformed("missing player to move"))?;
+        let players = PlayerColor::in_turn_order(rules.players);
+        let letters: Vec<String> = players.iter().map(|p| p.letter().to_string()).collect();
+        let mut to_move_chars = to_move.trim().chars();
+        let to_move = match (to_move_chars.next(), to_move_chars.next()) {
+            (Some(c), None) => PlayerColor::from_letter(c).filter(|p| players.contains(p)),
+            _ => None,
+        }.ok_or_else(|| malformed(&format!("player to move should be {}", letters.join(" or "))))?;
+        let board = Board::parse_position(piles).map_err(|why| malformed(&why.to_string()))?;
+        // Together with the players' stones, which are checked below, this keeps every pile that
+        // play can build within PILE_CAPACITY.
+        let neutrals = board.count_stones(Stone::Neutral);
+        if neutrals != 4 * rules.neutrals {
+            return Err(invalid(&format!("there are {neutrals} neutral stones, but the rules have {}", 4 * rules.neutrals)));
+        }
+        let mut stones_left = [0; MAX_PLAYERS];
+        for player in PlayerColor::ALL {
+            let placed = board.count_stones(player.stone_color());
+            if !players.contains(&player) && placed > 0 {
+                return Err(invalid(&format!("{player:?} is not playing")));
+            }
+            if players.contains(&player) {
+                stones_left[player as usize] = rules.stones_per_player.checked_sub(placed)
+                    .ok_or_else(|| invalid(&format!("too many {} stones", player.stone_color())))?;
+            }
+        }
+        Ok(GameState::new(board, to_move, stones_left, rules))
+    }
+
+    /// Parses a move in compact notation, checking it is legal in this state.
+    pub fn parse_move(&self, notation: &str) -> Result<Move, Error> {
+        let path = Path::parse(notation.trim(), self.rules.topology)?;
+        self.legal_moves()
+            .into_iter()
+            .find(|mv| mv.path == path)
+            .ok_or_else(|| Error::Invalid(format!("{} is not a legal move", path.notation())))
+    }
+
+    /// Plays the move for the player to move. Only the stone is checked: moves should come from
+    /// legal_moves or parse_move.
+    pub fn apply_move(&mut self, mv: &Move) {
+        if mv.new_stone != self.to_move.stone_color() {
+            panic!("Move {mv:?} does not place a {} stone", self.to_move.stone_color())
+        }
+        let left = &mut self.stones_left[self.to_move as usize];
+        if *left == 0 {
+            panic!("{:?} has no stones left to play {mv:?}", self.to_move)
+        }
+        *left -= 1;
+        self.board.apply_move(mv);
+        self.to_move = self.next_player();
+    }
+
+    /// Plays the move along the path with the stone of the player to move.
+    pub fn play(&mut self, path: Path) {
+        let mv = Move{new_stone: self.to_move.stone_color(), path};
+        self.apply_move(&mv);
+    }
+}
+
+impl Default for GameState {
+    fn default() -> Self {
+        Self::starting_state()
+    }
+}
@@ -0,0 +1,162 @@
+//! Hints: the best move for the player to move, explained in plain words.
+use crate::board::{Board, Line};
+use crate::error::Error;
+use crate::evaluator::Evaluator;
+use crate::game_state::{GameState, Move, PlayerColor, MAX_PLAYERS};
+use crate::rules::Rules;
+use crate::search::{is_win_score, Search, WIN_SCORE};
+use crate::tactics::winning_moves;
+use std::time::{Duration, Instant};
+
+/// Hint is a suggested move for the player to move, with the reasons for it in plain words.
+#[derive(Clone, Debug)]
+pub struct Hint {
+    /// The suggested move.
+    pub mv: Move,
+    /// The search's score for the move, for the player to move.
+    pub score: i32,
+    /// The deepest search that finished.
+    pub depth: u8,
+    /// The score is exact rather than an estimate: a forced win or loss, or a fully searched game.
+    pub proven: bool,
+    /// Why the move is suggested, in a sentence or two.
+    pub explanation: String,
+}
+
+/// Searches up to `max_depth`, stopping early once `time` runs out, and explains the best move.
+/// Fails if the game is over, or has more than two players, as the search is for two.
+pub fn hint(state: &GameState, evaluator: &dyn Evaluator, max_depth: u8, time: Option<Duration>) -> Result<Hint, Error> {
+    if state.players().len() > 2 {
+        return Err(Error::Invalid("hints are only for two player games".to_string()));
+    }
+    let deadline = time.map(|t| Instant::now() + t);
+    let result = Search::new(evaluator).with_deadline(deadline).search_iterative(state, max_depth);
+    let mv = result.best_move.ok_or_else(|| Error::Invalid("the game is over".to_string()))?;
+    let proven = is_win_score(result.score) || result.depth >= state.moves_left();
+    Ok(Hint{mv, score: result.score, depth: result.depth, proven, explanation: explain(state, &mv, result.score, result.depth)})
+}
+
+fn explain(state: &GameState, mv: &Move, score: i32, depth: u8) -> String {
+    let me = state.player_to_move();
+    let opponent = state.next_player();
+    let mut after = *state;
+    after.apply_move(mv);
+
+    let mut reasons = Vec::new();
+    let rules = state.rules();
+    let completed = topped_lines(after.board(), me, rules);
+    if !completed.is_empty() {
+        reasons.push(format!("completes {} for {me:?}", line_names(&completed)));
+    }
+    let handed = topped_lines(after.board(), opponent, rules);
+    if !handed.is_empty() {
+        reasons.push(format!("completes {} for {opponent:?}", line_names(&handed)));
+    }
+    if completed.is_empty() && handed.is_empty() {
+        let remaining = threatened_lines(after.board(), opponent, rules);
+        let blocked: Vec<Line> = threatened_lines(state.board(), opponent, rules).into_iter()
+            .filter(|line| !remaining.contains(line))
+            .collect();
+        if !blocked.is_empty() {
+            reasons.push(format!("blocks {opponent:?}'s {}", line_names(&blocked)));
+        }
+    }
+
+    let moves_to_end = |score: i32| match (WIN_SCORE - score.abs() + 1) / 2 {
+        1 => "1 move".to_string(),
+        n => format!("{n} moves"),
+    };
+    if is_win_score(score) && score > 0 && completed.is_empty() {
+        reasons.push(format!("forces a win in {}", moves_to_end(score)));
+    } else if is_win_score(score) && score < 0 {
+        reasons.push(format!("every move loses, but this holds out for {}", moves_to_end(score)));
+    }
+    if reasons.is_empty() {
+        reasons.push(format!("best move found searching {depth} plies ahead"));
+    }
+    reasons.join(", ")
+}
+
+// The lines the player's stones top on the board.
+fn topped_lines(board: &Board, player: PlayerColor, rules: &Rules) -> Vec<Line> {
+    let top = board.top();
+    rules.winning_lines().filter(|line| top.line_owner(line) == Some(player)).collect()
+}
+
+// The lines the player could complete if it were their move on this board.
+fn threatened_lines(board: &Board, player: PlayerColor, rules: &Rules) -> Vec<Line> {
+    let state = GameState::new(*board, player, [1; MAX_PLAYERS], *rules);
+    let mut lines: Vec<Line> = Vec::new();
+    for mv in winning_moves(&state) {
+        let mut after = state;
+        after.apply_move(&mv);
+        for line in topped_lines(after.board(), player, rules) {
+            if !lines.contains(&line) {
+                lines.push(line);
+            }
+        }
+    }
+    lines
+}
+
+// Rows are numbered and columns lettered as in move notation, e.g. "row 2", "column c", "diagonal a1-d4".
+fn line_name(line: &Line) -> String {
+    let (first, last) = (line.start(), line.end());
+    if first.y() == last.y() {
+        format!("row {}", first.y() + 1)
+    } else if first.x() == last.x() {
+        format!("column {}", (b'a' + first.x()) as char)
+    } else {
+        format!("diagonal {}-{}", first.name(), last.name())
+    }
+}
+
+fn line_names(lines: &[Line]) -> String {
+    lines.iter().map(line_name).collect::<Vec<_>>().join(" and ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::LineEvaluator;
+
+    #[test]
+    fn only_two_player_games() {
+        let three = GameState::starting_state_with(Rules::preset("three-player").unwrap()).unwrap();
+        assert!(matches!(hint(&three, &LineEvaluator, 2, None), Err(Error::Invalid(_))));
+    }
+
+    // White tops a1, b1 and d1, and needs c1 to finish row 1.
+    const POSITION: &str = "W,W,,W/,WW,,/nn,,,nn/nn,,,nn";
+
+    #[test]
+    fn winning_move() {
+        let state = GameState::parse_position(&format!("{POSITION} W"), Rules::standard()).unwrap();
+        let hint = hint(&state, &LineEvaluator, 2, None).unwrap();
+        assert!(winning_moves(&state).contains(&hint.mv));
+        assert!(hint.proven && is_win_score(hint.score) && hint.score > 0);
+        assert_eq!(hint.explanation, "completes row 1 for White");
+    }
+
+    #[test]
+    fn blocking_move() {
+        let state = GameState::parse_position(&format!("{POSITION} R"), Rules::standard()).unwrap();
+        let hint = hint(&state, &LineEvaluator, 2, None).unwrap();
+        assert!(hint.explanation.starts_with("blocks White's row 1"), "{}", hint.explanation);
+    }
+
+    #[test]
+    fn forced_win() {
+        let state = GameState::parse_position("WW,n,WW,R/Rn,,nW,/Rn,nR,,/R,nn,n, W", Rules::standard()).unwrap();
+        let hint = hint(&state, &LineEvaluator, 3, None).unwrap();
+        assert_eq!(hint.mv.to_string(), "c1rdl");
+        assert_eq!(hint.explanation, "blocks Red's diagonal a4-d1, forces a win in 2 moves");
+    }
+
+    #[test]
+    fn game_over() {
+        let mut state = GameState::parse_position(&format!("{POSITION} W"), Rules::standard()).unwrap();
+        state.apply_move(&winning_moves(&state)[0]);
+        assert!(matches!(hint(&state, &LineEvaluator, 2, None), Err(Error::Invalid(_))));
+    }
+}
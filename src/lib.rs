@@ -0,0 +1,97 @@
+//! The rules engine and bots for Qawale, a game of piling stones on a 4x4 board and spreading
+//! them out again, trying to top a line with your own colour.
+//!
+//! The types most programs need are re-exported here:
+//!
+//! - [`GameState`] is a game in progress: the [`Board`], whose turn it is and the stones each
+//!   player has left, played by some [`Rules`]. It lists and plays [`Move`]s, and reads and writes
+//!   positions as position strings, e.g. `"nn,,,nn/,,,/,,,/nn,,,nn W"` for the start.
+//! - A [`Move`] places the mover's stone on a pile and spreads the pile along a [`Path`] of
+//!   [`Direction`]s from its [`Position`]. Moves are written in compact notation, e.g. `a1rrd`.
+//! - [`Search`] finds the best move in a two player game with an [`Evaluator`], and
+//!   [`agent::agent_from_spec`] builds the bots the command line plays with.
+//!
+//! ```
+//! use qawale::{GameState, Search, LineEvaluator};
+//!
+//! let mut state = GameState::starting_state();
+//! let mv = state.parse_move("a1rrr").unwrap();
+//! state.apply_move(&mv);
+//! let result = Search::new(&LineEvaluator).search(&state, 2);
+//! assert!(result.best_move.is_some());
+//! ```
+//!
+//! Whatever cannot be read, built or played fails with an [`Error`].
+//!
+//! The modules hold the rest of the API:
+//!
+//! - the rules engine: [`board`], [`stone_pile`], [`stone`], [`position`], [`path`],
+//!   [`game_state`], [`rules`], [`symmetry`] and [`setup`] for building positions;
+//! - the bots: [`agent`], [`search`], [`multiplayer`], [`mcts`], [`evaluator`], [`nn`] and
+//!   [`book`];
+//! - looking at games: [`analysis`], [`tactics`], [`hint`], [`puzzles`], [`record`] and
+//!   [`selfplay`].
+//!
+//! The `serde` feature adds serialization of the core types in the same compact notations, and
+//! the `tui`, `server` and `game-server` features add the terminal UI and the servers behind the
+//! `qawale-bot tui`, `qawale-server` and `qawale-host` programs. The shared library has a C
+//! interface, see [`ffi`], and with the `python` feature is also a Python extension module.
+
+#![warn(missing_docs)]
+
+pub mod agent;
+pub mod analysis;
+pub mod board;
+pub mod book;
+mod error;
+pub mod evaluator;
+pub mod ffi;
+#[cfg(feature = "game-server")]
+pub mod game_server;
+pub mod game_state;
+pub mod hint;
+pub mod mcts;
+pub mod multiplayer;
+pub mod nn;
+pub mod path;
+pub mod position;
+pub mod puzzles;
+#[cfg(feature = "python")]
+mod python;
+pub mod record;
+pub mod rules;
+pub mod search;
+pub mod selfplay;
+#[cfg(feature = "serde")]
+mod serialization;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod setup;
+pub mod stone;
+pub mod stone_pile;
+pub mod symmetry;
+pub mod tactics;
+#[cfg(feature = "tui")]
+pub mod tui;
+mod zobrist;
+
+// The modules below are public for the programs built from this package, which use the library
+// as any other program would, but are not part of its API.
+#[doc(hidden)]
+pub mod cli;
+#[doc(hidden)]
+pub mod match_runner;
+#[doc(hidden)]
+pub mod perft;
+#[doc(hidden)]
+pub mod tournament;
+
+pub use board::Board;
+pub use error::Error;
+pub use evaluator::{Evaluator, LineEvaluator};
+pub use game_state::{GameState, Move, Outcome, PlayerColor};
+pub use path::Path;
+pub use position::{Direction, Position};
+pub use rules::Rules;
+pub use search::{Search, SearchResult};
+pub use stone::Stone;
@@ -1,58 +1,605 @@
-use crate::board::{Board, BoardTop};
-use crate::game_state::{Move, MoveGenerator, PlayerColor};
-use crate::path::{Path, PathGenerator};
-use crate::stone::Stone;
-use crate::stone_pile::{StonePile, PILE_CAPACITY};
-use std::time::SystemTime;
-use tinyvec::array_vec;
-
-mod board;
-mod game_state;
-mod path;
-mod position;
-mod stone;
-mod stone_pile;
+//! qawale-bot, the command line for playing, searching and analysing games, running matches and
+//! tournaments, building books and puzzles, and self-play for training data. `qawale-bot help`
+//! lists the commands.
+use qawale::agent::AgentSpec;
+use qawale::board::Board;
+use qawale::book::OpeningBook;
+use qawale::cli::Args;
+use qawale::evaluator::{Evaluator, LineEvaluator};
+use qawale::game_state::{GameState, Move, PlayerColor};
+use qawale::perft::{dfs, DfsResults};
+use qawale::{agent, analysis, hint, match_runner, puzzles, record, selfplay, setup, tournament};
+use qawale::nn::Mlp;
+use qawale::record::GameRecord;
+use qawale::rules::Rules;
+use qawale::search::{is_win_score, Search, WIN_SCORE};
+use qawale::tactics::Threats;
+use qawale::match_runner::{FreeForAllConfig, MatchConfig, Sprt};
+use qawale::multiplayer::{MultiSearch, Strategy};
+use qawale::tournament::TournamentConfig;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+
+// Each command with its arguments and what it does, for the help.
+const COMMANDS: [(&str, &str, &str); 17] = [
+    ("perft", "[--size N] [--depth D] [--rules RULES]", "count the positions reached after each number of moves"),
+    ("search", "POSITION [--depth N] [--time SECONDS] [--weights FILE] [--multipv K [--diagram]] [--compare-ordering] [--strategy max-n|paranoid]", "search a position for the best move"),
+    ("solve", "POSITION [--time SECONDS] [--weights FILE]", "find the result of a position with best play"),
+    ("hint", "POSITION [--depth N] [--time SECONDS] [--weights FILE]", "suggest a move and explain it"),
+    ("threats", "POSITION", "list the moves that win, hand over a line or allow a winning reply"),
+    ("play", "POSITION [--agent SPEC] [--as W|R|B|G] [--seed N] [--records FILE]", "play a game against agents"),
+    ("tui", "POSITION [--agent SPEC [--as W|R|B|G]] [--weights FILE] [--seed N]", "play in a full-screen terminal UI with live analysis"),
+    ("selfplay", "[--games N] [--iterations N] [--sampling-plies N] [--weights FILE] [--out FILE] [--hidden N] [--epochs N] [--learning-rate F] [--threads N] [--seed N] [--records FILE]", "train a network on MCTS self-play games"),
+    ("match", "<agent-a> <agent-b> [--rules RULES] [--games N] [--threads N] [--opening-plies N] [--random-setup] [--seed N] [--records FILE] [--sprt ELO0,ELO1]", "play a match between two agents"),
+    ("tournament", "<agent>... [--rules RULES] [--gauntlet] [--games N] [--threads N] [--opening-plies N] [--random-setup] [--seed N] [--records FILE] [--csv FILE]", "play every pair of agents and rate them"),
+    ("free-for-all", "<agent>... [--rules RULES] [--games N] [--opening-plies N] [--seed N]", "play games of three or four agents"),
+    ("analyze", "<records-file> [--game N] [--depth N] [--time SECONDS] [--weights FILE] [--out FILE]", "grade the moves of recorded games"),
+    ("book", "build --out FILE [--records FILE] [--depth N] [--plies N] [--margin N] [--weights FILE] | query --book FILE POSITION", "build or look up an opening book"),
+    ("puzzles", "[--moves N] [--count K] [--games MAX] [--agent SPEC] [--seed N] [--weights FILE] [--out FILE]", "find win-in-N puzzles in self-play games"),
+    ("setup", "POSITION [--random] [--seed N]", "edit a position to start from"),
+    ("help", "[COMMAND]", "show this help, or a command's"),
+    ("version", "", "show the version"),
+];
+
+const COMMON_OPTIONS: &str = "\
+POSITION is [--position POS] [--rules RULES] [move...]: the position given as a position string
+(default the starting position), after the moves listed in compact notation, e.g. a1rrr d1ldru.
+Rules are a preset, optionally with options, e.g. standard or three:diagonals=no.
+Agents are specs like random, greedy, search:depth=4, mcts:iterations=2000,weights=net.txt,
+max-n:depth=3 or paranoid:depth=3, optionally with book=FILE.";
 
 fn main() {
-    let moves = 4;
+    let args = Args::parse(std::env::args().skip(1), &["gauntlet", "compare-ordering", "diagram", "random-setup", "random", "help"]);
+    if args.has("help") {
+        print_help(args.positional(0));
+        return;
+    }
+    if let Err(e) = check_options(&args) {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
+    let result = match args.positional(0) {
+        None => {
+            print_help(None);
+            Ok(())
+        }
+        Some("help") => {
+            print_help(args.positional(1));
+            Ok(())
+        }
+        Some("version") => {
+            println!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+            Ok(())
+        }
+        Some("play") => run_play(&args),
+        Some("tui") => run_tui(&args),
+        Some("selfplay") => run_selfplay(&args),
+        Some("solve") => run_solve(&args),
+        Some("match") => run_match(&args),
+        Some("tournament") => run_tournament(&args),
+        Some("free-for-all") => run_free_for_all(&args),
+        Some("book") => run_book(&args),
+        Some("search") => run_search(&args),
+        Some("threats") => run_threats(&args),
+        Some("analyze") => run_analyze(&args),
+        Some("puzzles") => run_puzzles(&args),
+        Some("hint") => run_hint(&args),
+        Some("perft") => run_perft(&args),
+        Some("setup") => run_setup(&args),
+        Some(command) => Err(format!("unknown command {command:?}, try help")),
+    };
+    if let Err(e) = result {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
+}
+
+// Refuses options the command doesn't take: those not in its usage, other than --position and
+// --rules for a POSITION.
+fn check_options(args: &Args) -> Result<(), String> {
+    let Some((command, usage, _)) = args.positional(0).and_then(|c| COMMANDS.iter().find(|(n, _, _)| *n == c)) else {
+        return Ok(());
+    };
+    let mut options: Vec<&str> = usage.split(|c: char| !(c.is_alphanumeric() || c == '-'))
+        .filter_map(|word| word.strip_prefix("--"))
+        .collect();
+    if usage.contains("POSITION") {
+        options.extend(["position", "rules"]);
+    }
+    args.check_options(&options, &format!("{} {command} {usage}", env!("CARGO_PKG_NAME")))
+}
+
+fn print_help(command: Option<&str>) {
+    let name = env!("CARGO_PKG_NAME");
+    match command.and_then(|c| COMMANDS.iter().find(|(n, _, _)| *n == c)) {
+        Some((command, usage, about)) => {
+            println!("{name} {command} {usage}");
+            println!("{about}");
+            if usage.contains("POSITION") || usage.contains("agent") || usage.contains("RULES") {
+                println!();
+                println!("{COMMON_OPTIONS}");
+            }
+        }
+        None => {
+            println!("usage: {name} <command> [options]");
+            println!();
+            for (command, _, about) in COMMANDS {
+                println!("  {command:<14}{about}");
+            }
+            println!();
+            println!("Run {name} help <command> for a command's options.");
+            println!();
+            println!("{COMMON_OPTIONS}");
+        }
+    }
+}
+
+// play [--position POS] [--rules RULES] [move...] [--agent SPEC] [--as COLOUR] [--seed N] [--records FILE]
+// Every player but the one given by --as, White by default, is played by the agent.
+fn run_play(args: &Args) -> Result<(), String> {
+    let start = state_from_args(args, 1)?;
+    let human: String = args.get("as", "W".to_string())?;
+    let human = start.players().iter().copied().find(|p| p.letter().to_string() == human)
+        .ok_or_else(|| format!("{human:?} is not one of the players"))?;
+    let records: Option<String> = args.get_opt("records")?;
+    if records.is_some() && start.players().len() > 2 {
+        return Err("only two player games can be recorded".to_string());
+    }
+    let spec: String = args.get("agent", "search:depth=4".to_string())?;
+    let seed = args.get_opt("seed")?.unwrap_or_else(rand::random);
+    let spec = AgentSpec::parse(&spec)?;
+    spec.check_players(start.players().len())?;
+    let mut agents: Vec<_> = start.players().iter()
+        .map(|&p| if p == human { Box::new(agent::HumanAgent) as Box<dyn agent::Agent> } else { spec.build(seed) })
+        .collect();
+    let (moves, outcome) = match_runner::play_free_for_all_game(&mut agents, &start, &[]);
+    if let Some(path) = records {
+        let names: Vec<String> = agents.iter().map(|a| a.name()).collect();
+        let mut record = GameRecord::new(names[0].clone(), names[1].clone());
+        record.set_start(&start);
+        (record.moves, record.outcome) = (moves, Some(outcome));
+        record.append_to(&path).map_err(|e| format!("{path}: {e}"))?;
+    }
+    Ok(())
+}
+
+// tui [--position POS] [--rules RULES] [move...] [--agent SPEC [--as COLOUR]] [--weights FILE] [--seed N]
+// With --agent, it plays every player but the one given by --as as soon as it is their turn.
+// Otherwise it only moves when asked, as search:depth=4, or paranoid:depth=3 with more players.
+#[cfg(feature = "tui")]
+fn run_tui(args: &Args) -> Result<(), String> {
+    let start = state_from_args(args, 1)?;
+    let default_spec = if start.players().len() == 2 { "search:depth=4" } else { "paranoid:depth=3" };
+    let spec: Option<String> = args.get_opt("agent")?;
+    let seed = args.get_opt("seed")?.unwrap_or_else(rand::random);
+    let engine = AgentSpec::parse(spec.as_deref().unwrap_or(default_spec))?;
+    engine.check_players(start.players().len())?;
+    let engine = engine.build(seed);
+    let mut automatic = Vec::new();
+    if spec.is_some() {
+        let human: String = args.get("as", "W".to_string())?;
+        let human = start.players().iter().copied().find(|p| p.letter().to_string() == human)
+            .ok_or_else(|| format!("{human:?} is not one of the players"))?;
+        automatic = start.players().iter().copied().filter(|&p| p != human).collect();
+    }
+    let evaluator: std::sync::Arc<dyn Evaluator + Send + Sync> = match args.get_opt::<String>("weights")? {
+        Some(path) => std::sync::Arc::new(Mlp::load(&path)?),
+        None => std::sync::Arc::new(LineEvaluator),
+    };
+    let moves = qawale::tui::run(start, qawale::tui::TuiOptions{engine, automatic, evaluator})?;
+    let moves: Vec<String> = moves.iter().map(|mv| mv.to_string()).collect();
+    println!("{}", moves.join(" "));
+    Ok(())
+}
+
+#[cfg(not(feature = "tui"))]
+fn run_tui(_args: &Args) -> Result<(), String> {
+    Err("this build doesn't include the terminal UI, rebuild with --features tui".to_string())
+}
+
+// selfplay [--games N] [--iterations N] [--sampling-plies N] [--weights FILE] [--out FILE] [--hidden N] [--epochs N] [--learning-rate F] [--threads N] [--seed N] [--records FILE]
+// Plays MCTS guided by the network in --weights, or the line heuristic, against itself, then
+// trains that network, or a new one, on the games and saves it to --out.
+fn run_selfplay(args: &Args) -> Result<(), String> {
+    let config = selfplay::SelfPlayConfig {
+        games: args.get("games", 20)?,
+        iterations: args.get("iterations", 400)?,
+        sampling_plies: args.get("sampling-plies", 4)?,
+        threads: args.get("threads", thread::available_parallelism().map_or(1, |n| n.get()))?,
+        seed: args.get("seed", 0)?,
+    };
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    let network = match args.get_opt::<String>("weights")? {
+        Some(path) => Some(Mlp::load(&path)?),
+        None => None,
+    };
+    let records: Option<String> = args.get_opt("records")?;
+    let mut games = 0;
+    let mut progress = |record: &GameRecord| {
+        games += 1;
+        println!("Game {games}: {} in {} moves", record::result_string(record.outcome), record.moves.len());
+        if let Some(path) = &records {
+            if let Err(e) = record.append_to(path) {
+                eprintln!("{path}: {e}");
+            }
+        }
+    };
+    let samples = match &network {
+        Some(network) => selfplay::run_selfplay(&config, network, &mut progress),
+        None => selfplay::run_selfplay(&config, &LineEvaluator, &mut progress),
+    };
+    let Some(out) = args.get_opt::<String>("out")? else {
+        return Ok(());
+    };
+    let mut network = match network {
+        Some(network) => network,
+        None => Mlp::new(args.get("hidden", 64)?, &mut rng),
+    };
+    let loss = network.train(&samples, args.get("epochs", 4)?, args.get("learning-rate", 0.01)?, &mut rng);
+    println!("Trained on {} positions, loss {loss:.4}", samples.len());
+    network.save(&out).map_err(|e| format!("{out}: {e}"))?;
+    println!("Wrote {out}");
+    Ok(())
+}
+
+// solve [--position POS] [--rules RULES] [move...] [--time SECONDS] [--weights FILE]
+// Searches to the end of the game, unless the time runs out first.
+fn run_solve(args: &Args) -> Result<(), String> {
+    let state = state_from_args(args, 1)?;
+    if state.players().len() > 2 {
+        return Err("only two player games can be solved".to_string());
+    }
+    if let Some(outcome) = state.outcome() {
+        println!("The game is over: {outcome:?}");
+        return Ok(());
+    }
+    let evaluator = evaluator_from_args(args)?;
+    let deadline = args.get_seconds_opt("time")?.map(|t| Instant::now() + t);
     let now = SystemTime::now();
+    let mut search = Search::new(evaluator.as_ref()).with_deadline(deadline);
+    let result = search.search_iterative(&state, state.moves_left());
+    let elapsed = now.elapsed().expect("should get time").as_secs_f64();
+    let me = state.player_to_move();
+    let moves = (WIN_SCORE - result.score.abs() + 1) / 2;
+    let verdict = if is_win_score(result.score) && result.score > 0 {
+        format!("{me:?} wins in {moves} moves")
+    } else if is_win_score(result.score) {
+        format!("{:?} wins in {moves} moves", state.next_player())
+    } else if result.depth >= state.moves_left() {
+        "draw".to_string()
+    } else {
+        return Err(format!("unsolved after searching {} plies in {elapsed:.1}s; best guess {} score {}",
+            result.depth, result.best_move.map_or("-".to_string(), |mv| mv.to_string()), result.score));
+    };
+    let pv: Vec<String> = result.pv.iter().map(|mv| mv.to_string()).collect();
+    println!("{verdict}, pv {} nodes {} time {elapsed:.3}s", pv.join(" "), result.nodes);
+    Ok(())
+}
+
+// match <agent-a> <agent-b> [--rules RULES] [--games N] [--threads N] [--opening-plies N] [--random-setup] [--seed N] [--records FILE] [--sprt ELO0,ELO1]
+fn run_match(args: &Args) -> Result<(), String> {
+    let config = MatchConfig {
+        agents: [args.required(1, "first agent")?.to_string(), args.required(2, "second agent")?.to_string()],
+        rules: args.get("rules", Rules::standard())?,
+        games: args.get("games", 100)?,
+        threads: args.get("threads", thread::available_parallelism().map_or(1, |n| n.get()))?,
+        opening_plies: args.get("opening-plies", 2)?,
+        random_setup: args.has("random-setup"),
+        seed: args.get("seed", 0)?,
+        records: args.get_opt("records")?,
+        sprt: args.get_opt::<Sprt>("sprt")?,
+    };
+    let stats = match_runner::run_match(&config, |record, stats| {
+        println!("{}", match_runner::game_summary(record, stats));
+    })?;
+    match_runner::print_result(&config, &stats);
+    Ok(())
+}
 
-    let b = Board::starting_board();
-    let p = PlayerColor::White;
-    let mut res = DfsResults::default();
-    dfs(b, p, moves, &mut res);
+// tournament <agent>... [--rules RULES] [--gauntlet] [--games N] [--threads N] [--opening-plies N] [--random-setup] [--seed N] [--records FILE] [--csv FILE]
+fn run_tournament(args: &Args) -> Result<(), String> {
+    let config = TournamentConfig {
+        agents: args.positionals_from(1).to_vec(),
+        rules: args.get("rules", Rules::standard())?,
+        gauntlet: args.has("gauntlet"),
+        games_per_pair: args.get("games", 20)?,
+        threads: args.get("threads", thread::available_parallelism().map_or(1, |n| n.get()))?,
+        opening_plies: args.get("opening-plies", 2)?,
+        random_setup: args.has("random-setup"),
+        seed: args.get("seed", 0)?,
+        records: args.get_opt("records")?,
+    };
+    let results = tournament::run_tournament(&config, |a, b, stats| println!("{a} vs {b}: {stats}"))?;
+    println!();
+    print!("{}", results.rating_table());
+    println!();
+    print!("{}", results.crosstable());
+    if let Some(path) = args.get_opt::<String>("csv")? {
+        results.write_csv(&path)?;
+    }
+    Ok(())
+}
 
-    println!(
-        "Took {}sec to evaluate {:?}",
-        now.elapsed().expect("should get time").as_secs(),
-        res
-    );
+// free-for-all <agent>... [--rules RULES] [--games N] [--opening-plies N] [--seed N]
+// Without rules, plays by the three-player or four-player preset to suit the number of agents.
+fn run_free_for_all(args: &Args) -> Result<(), String> {
+    let agents = args.positionals_from(1).to_vec();
+    let rules = match args.get_opt::<Rules>("rules")? {
+        Some(rules) => rules,
+        None => match agents.len() {
+            3 => Rules::preset("three-player").unwrap(),
+            4 => Rules::preset("four-player").unwrap(),
+            n => return Err(format!("free-for-all needs three or four agents, not {n}, unless given --rules")),
+        },
+    };
+    let config = FreeForAllConfig {
+        games: args.get("games", 12)?,
+        opening_plies: args.get("opening-plies", rules.players)?,
+        seed: args.get("seed", 0)?,
+        agents,
+        rules,
+    };
+    let stats = match_runner::run_free_for_all(&config, |seats, moves, outcome, stats| {
+        let names: Vec<&str> = seats.iter().map(|&i| config.agents[i].as_str()).collect();
+        let moves: Vec<String> = moves.iter().map(|mv| mv.to_string()).collect();
+        println!("Game {}: {} {outcome:?} | {}", stats.games(), names.join(" vs "), moves.join(" "));
+    })?;
+    println!();
+    for (agent, wins) in config.agents.iter().zip(&stats.wins) {
+        println!("{agent}: {wins} wins");
+    }
+    println!("draws: {}", stats.draws);
+    Ok(())
 }
 
-#[derive(Debug, Default)]
-struct DfsResults {
-    count: i32,
-    red_wins: i32,
-    white_wins: i32,
+// book build --out FILE [--records FILE] [--depth N] [--plies N] [--margin N] [--weights FILE]
+// book query --book FILE [--position POS] [move...]
+fn run_book(args: &Args) -> Result<(), String> {
+    match args.positional(1) {
+        Some("build") => {
+            let out: String = args.get_opt("out")?.ok_or("missing --out")?;
+            let plies = args.get("plies", 4)?;
+            let book = match args.get_opt::<String>("records")? {
+                Some(path) => {
+                    let mut book = OpeningBook::new();
+                    book.add_records(&GameRecord::read_all(&path)?, plies as usize);
+                    book
+                }
+                None => {
+                    let evaluator = evaluator_from_args(args)?;
+                    let book = OpeningBook::build_from_search(evaluator.as_ref(), args.get("depth", 3)?, plies, args.get("margin", 50)?, |n| {
+                        eprint!("\r{n} positions");
+                    });
+                    eprintln!();
+                    book
+                }
+            };
+            book.save(&out)?;
+            println!("Wrote {} positions to {out}", book.len());
+            Ok(())
+        }
+        Some("query") => {
+            let book = OpeningBook::load(&args.get_opt::<String>("book")?.ok_or("missing --book")?)?;
+            let state = state_from_args(args, 2)?;
+            println!("{}", state.board());
+            let moves = book.lookup(&state);
+            if moves.is_empty() {
+                println!("Position not in book");
+            }
+            for m in moves {
+                println!("{:<12} weight {:>5} score {:>6}", m.mv.to_string(), m.weight, m.score);
+            }
+            Ok(())
+        }
+        _ => Err("usage: book build|query ...".to_string()),
+    }
 }
 
-fn dfs(board: Board, player: PlayerColor, moves: i32, res: &mut DfsResults) {
-    if moves == 0 {
-        res.count += 1;
-        match board.top().winner() {
-            Some(PlayerColor::Red) => res.red_wins += 1,
-            Some(PlayerColor::White) => res.white_wins += 1,
-            _ => (),
+// search [--position POS] [--rules RULES] [move...] [--depth N] [--weights FILE] [--multipv K [--diagram]] [--compare-ordering]
+// Games of more than two players are searched with --strategy max-n or paranoid (the default).
+fn run_search(args: &Args) -> Result<(), String> {
+    let state = state_from_args(args, 1)?;
+    let evaluator = evaluator_from_args(args)?;
+    let depth = args.get("depth", 4)?;
+    let time = args.get_seconds_opt("time")?;
+    let deadline = time.map(|t| Instant::now() + t);
+    let now = SystemTime::now();
+    if state.players().len() > 2 {
+        let mut search = MultiSearch::new(args.get("strategy", Strategy::Paranoid)?).with_deadline(deadline);
+        // With a time limit, searches deeper until it runs out, to --depth if given.
+        let result = match time {
+            Some(_) => search.search_iterative(&state, args.get("depth", state.moves_left())?),
+            None => search.search(&state, depth),
+        };
+        let pv: Vec<String> = result.pv.iter().map(|mv| mv.to_string()).collect();
+        println!("depth {} score {} pv {} nodes {} time {:.3}s", result.depth, result.score, pv.join(" "), result.nodes, now.elapsed().expect("should get time").as_secs_f64());
+        return Ok(());
+    }
+    if let Some(count) = args.get_opt::<usize>("multipv")? {
+        // With a time limit, keeps the lines from the deepest search that finished in time.
+        let mut search = Search::new(evaluator.as_ref()).with_deadline(deadline);
+        let (mut lines, mut reached, mut nodes) = (Vec::new(), 0, 0);
+        for d in if time.is_some() { 1 } else { depth }..=depth {
+            let deeper = search.search_multipv(&state, d, count);
+            nodes += search.nodes();
+            if search.aborted() && !lines.is_empty() {
+                break;
+            }
+            (lines, reached) = (deeper, d);
         }
-        return;
+        for (i, line) in lines.iter().enumerate() {
+            println!("{:>2}. {}", i + 1, line.display(args.has("diagram")));
+        }
+        println!("depth {reached} nodes {nodes} time {:.3}s", now.elapsed().expect("should get time").as_secs_f64());
+        return Ok(());
     }
-    let it = MoveGenerator::new(&board);
-    for p in it {
-        let mut new_board = board;
-        new_board.apply_move(&Move {
-            new_stone: player.stone_color(),
-            path: p,
-        });
-        dfs(new_board, player.opponent(), moves - 1, res)
+    // With a time limit, searches deeper until it runs out, to --depth if given.
+    let result = match time {
+        Some(_) => Search::new(evaluator.as_ref()).with_deadline(deadline)
+            .search_iterative(&state, args.get("depth", state.moves_left())?),
+        None => Search::new(evaluator.as_ref()).search(&state, depth),
+    };
+    let elapsed = now.elapsed().expect("should get time");
+    let pv: Vec<String> = result.pv.iter().map(|mv| mv.to_string()).collect();
+    println!("depth {} score {} pv {} nodes {} time {:.3}s", result.depth, result.score, pv.join(" "), result.nodes, elapsed.as_secs_f64());
+    if args.has("compare-ordering") {
+        let unordered = Search::new(evaluator.as_ref()).with_ordering(false).search(&state, depth);
+        let untabled = Search::new(evaluator.as_ref()).with_table_size(0).search(&state, depth);
+        println!("unordered: score {} nodes {}", unordered.score, unordered.nodes);
+        println!("without the table: score {} nodes {}", untabled.score, untabled.nodes);
+        println!("ordering searched {:.1}% of the unordered nodes", 100.0 * result.nodes as f64 / unordered.nodes as f64);
+        println!("the table searched {:.1}% of the nodes without it", 100.0 * result.nodes as f64 / untabled.nodes as f64);
+    }
+    Ok(())
+}
+
+// threats [--position POS] [--rules RULES] [move...]
+fn run_threats(args: &Args) -> Result<(), String> {
+    let state = state_from_args(args, 1)?;
+    println!("{}", state.board());
+    let threats = Threats::find(&state);
+    let list = |moves: &[Move]| moves.iter().map(|mv| mv.to_string()).collect::<Vec<_>>().join(" ");
+    println!("{:?} to move", state.player_to_move());
+    println!("Wins now: {}", list(&threats.winning));
+    println!("Hands the opponent a line: {}", list(&threats.hands_line));
+    let refuted: Vec<String> = threats.allows_win.iter().map(|(mv, reply)| format!("{mv} ({reply})")).collect();
+    println!("Allows a winning reply: {}", refuted.join(" "));
+    println!("Safe: {} moves", threats.safe.len());
+    Ok(())
+}
+
+// analyze <records-file> [--game N] [--depth N] [--time SECONDS] [--weights FILE] [--out FILE]
+fn run_analyze(args: &Args) -> Result<(), String> {
+    let records = GameRecord::read_all(args.required(1, "records file")?)?;
+    let records: Vec<&GameRecord> = match args.get_opt::<usize>("game")? {
+        Some(n) => vec![records.get(n.wrapping_sub(1)).ok_or(format!("there is no game {n}"))?],
+        None => records.iter().collect(),
+    };
+    let evaluator = evaluator_from_args(args)?;
+    let depth = args.get("depth", 4)?;
+    let time_per_move = args.get_seconds_opt("time")?;
+    let mut annotated = String::new();
+    for record in records {
+        let analysis = analysis::analyse_game(record, evaluator.as_ref(), depth, time_per_move)?;
+        println!("{}", analysis.annotated_record());
+        print!("{}", analysis.summary());
+        println!();
+        annotated += &analysis.annotated_record();
+        annotated.push('\n');
+    }
+    if let Some(path) = args.get_opt::<String>("out")? {
+        std::fs::write(&path, annotated).map_err(|e| format!("{path}: {e}"))?;
+    }
+    Ok(())
+}
+
+// hint [--position POS] [--rules RULES] [move...] [--depth N] [--time SECONDS] [--weights FILE]
+fn run_hint(args: &Args) -> Result<(), String> {
+    let state = state_from_args(args, 1)?;
+    let evaluator = evaluator_from_args(args)?;
+    let time = args.get_seconds("time", Duration::from_secs(1))?;
+    let hint = hint::hint(&state, evaluator.as_ref(), args.get("depth", 12)?, Some(time))?;
+    let proven = if hint.proven { " (proven)" } else { "" };
+    println!("{} score {}{proven} depth {}", hint.mv, hint.score, hint.depth);
+    println!("{}", hint.explanation);
+    Ok(())
+}
+
+// puzzles [--moves N] [--count K] [--games MAX] [--agent SPEC] [--seed S] [--weights FILE] [--out FILE]
+fn run_puzzles(args: &Args) -> Result<(), String> {
+    let config = puzzles::PuzzleConfig {
+        moves_to_win: args.get("moves", 2)?,
+        count: args.get("count", 10)?,
+        max_games: args.get("games", 1000)?,
+        agent: args.get("agent", "greedy".to_string())?,
+        seed: args.get("seed", 0)?,
+    };
+    let evaluator = evaluator_from_args(args)?;
+    let found = puzzles::generate(&config, evaluator.as_ref(), |games, found| {
+        eprint!("\r{games} games, {} puzzles", found.len());
+    })?;
+    eprintln!();
+    let lines: Vec<String> = found.iter().map(|p| p.line()).collect();
+    for line in &lines {
+        println!("{line}");
+    }
+    if let Some(path) = args.get_opt::<String>("out")? {
+        std::fs::write(&path, lines.join("\n") + "\n").map_err(|e| format!("{path}: {e}"))?;
+    }
+    Ok(())
+}
+
+// setup [--position POS] [--rules RULES] [move...] [--random] [--seed N]
+// Edits a position to start from, printing its position string. With --random, prints a random
+// setup of the neutral stones instead.
+fn run_setup(args: &Args) -> Result<(), String> {
+    let mut rng = StdRng::seed_from_u64(args.get_opt("seed")?.unwrap_or_else(rand::random));
+    if args.has("random") {
+        let state = setup::random_setup(args.get("rules", Rules::standard())?, &mut rng)?;
+        println!("{}", state.board());
+        println!("{}", state.position_string());
+        return Ok(());
+    }
+    let start = setup::SetupBuilder::from_state(&state_from_args(args, 1)?);
+    let state = setup::edit(start, &mut rng).ok_or("no setup")?;
+    println!("{}", state.position_string());
+    Ok(())
+}
+
+// The position given by --position, or the starting position, after any moves listed from the
+// given positional argument onwards, played by the rules given by --rules.
+fn state_from_args(args: &Args, first_move: usize) -> Result<GameState, String> {
+    let rules = args.get("rules", Rules::standard())?;
+    let mut state = match args.get_opt::<String>("position")? {
+        Some(position) => GameState::parse_position(&position, rules)?,
+        None => GameState::starting_state_with(rules)?,
+    };
+    for notation in args.positionals_from(first_move) {
+        let mv = state.parse_move(notation)?;
+        state.apply_move(&mv);
+    }
+    Ok(state)
+}
+
+fn evaluator_from_args(args: &Args) -> Result<Box<dyn Evaluator>, String> {
+    match args.get_opt::<String>("weights")? {
+        Some(path) => Ok(Box::new(Mlp::load(&path)?)),
+        None => Ok(Box::new(LineEvaluator)),
+    }
+}
+
+// perft [--size N] [--depth D] [--rules RULES]
+fn run_perft(args: &Args) -> Result<(), String> {
+    let depth = args.get("depth", 3)?;
+    let rules = args.get_opt::<Rules>("rules")?;
+    match args.get("size", 4)? {
+        3 => perft::<3>(depth, rules),
+        4 => perft::<4>(depth, rules),
+        5 => perft::<5>(depth, rules),
+        6 => perft::<6>(depth, rules),
+        7 => perft::<7>(depth, rules),
+        8 => perft::<8>(depth, rules),
+        size => Err(format!("unsupported board size {size}, expected 3 to 8")),
+    }
+}
+
+// Counts the boards reached after each number of moves from the starting layout on an N by N
+// board, like the benchmark, without stopping at wins. Without rules, lines span the board.
+fn perft<const N: usize>(depth: i32, rules: Option<Rules>) -> Result<(), String> {
+    let rules = rules.unwrap_or_else(|| {
+        let mut rules = Rules::standard();
+        rules.line_length = N as u8;
+        rules
+    });
+    rules.validate::<N>()?;
+    for moves in 1..=depth {
+        let now = SystemTime::now();
+        let mut res = DfsResults::default();
+        dfs(Board::<N>::setup(&rules), PlayerColor::White, moves, &rules, &mut res);
+        println!("{N}x{N} depth {moves}: {res:?} in {:.3}s", now.elapsed().expect("should get time").as_secs_f64());
     }
+    Ok(())
 }
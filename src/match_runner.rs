@@ -0,0 +1,408 @@
+use crate::agent::{Agent, AgentSpec};
+use crate::error::Error;
+use crate::game_state::{GameState, Move, Outcome, PlayerColor};
+use crate::record::{result_string, GameRecord};
+use crate::position::BOARD_SIZE;
+use crate::rules::Rules;
+use crate::setup::random_setup;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+/// MatchConfig describes a match between two agents, given as agent specs.
+#[derive(Clone, Debug)]
+pub struct MatchConfig {
+    pub agents: [String; 2],
+    /// The rules of every game, which must be for two players.
+    pub rules: Rules,
+    pub games: u32,
+    pub threads: usize,
+    /// Random moves played before the agents take over. Each opening is played twice, once with
+    /// each agent as White.
+    pub opening_plies: u8,
+    /// Start each opening from neutral stones piled on random squares.
+    pub random_setup: bool,
+    pub seed: u64,
+    pub records: Option<String>,
+    pub sprt: Option<Sprt>,
+}
+
+/// MatchStats counts results from the point of view of the first agent.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MatchStats {
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+}
+
+impl MatchStats {
+    pub fn games(&self) -> u32 {
+        self.wins + self.losses + self.draws
+    }
+
+    pub fn add(&mut self, outcome: Option<Outcome>, first_agent: PlayerColor) {
+        match outcome {
+            Some(Outcome::Win(p)) if p == first_agent => self.wins += 1,
+            Some(Outcome::Win(_)) => self.losses += 1,
+            _ => self.draws += 1,
+        }
+    }
+
+    /// The first agent's mean score per game, counting draws as half a win.
+    pub fn score(&self) -> f64 {
+        (self.wins as f64 + self.draws as f64 / 2.0) / self.games().max(1) as f64
+    }
+
+    // Variance of the score of a single game.
+    fn variance(&self) -> f64 {
+        let (n, s) = (self.games().max(1) as f64, self.score());
+        (self.wins as f64 * (1.0 - s).powi(2) + self.draws as f64 * (0.5 - s).powi(2) + self.losses as f64 * s.powi(2)) / n
+    }
+
+    /// The Elo difference of the first agent over the second, with the margin of a 95% confidence interval.
+    pub fn elo(&self) -> (f64, f64) {
+        let margin = 1.96 * (self.variance() / self.games().max(1) as f64).sqrt();
+        let (low, high) = (score_to_elo(self.score() - margin), score_to_elo(self.score() + margin));
+        (score_to_elo(self.score()), (high - low) / 2.0)
+    }
+}
+
+impl fmt::Display for MatchStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (elo, margin) = self.elo();
+        write!(f, "+{} -{} ={} [{:.3}] Elo {:+.1} +/- {:.1}", self.wins, self.losses, self.draws, self.score(), elo, margin)
+    }
+}
+
+pub fn score_to_elo(score: f64) -> f64 {
+    -400.0 * (1.0 / score.clamp(1e-6, 1.0 - 1e-6) - 1.0).log10()
+}
+
+fn elo_to_score(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+/// Sprt is a sequential probability ratio test of the hypotheses that the first agent is elo0
+/// (H0) or elo1 (H1) stronger than the second, with error rates alpha and beta.
+#[derive(Clone, Copy, Debug)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+impl Sprt {
+    pub fn new(elo0: f64, elo1: f64) -> Self {
+        Sprt{elo0, elo1, alpha: 0.05, beta: 0.05}
+    }
+
+    /// Log-likelihood ratio of H1 over H0, using the normal approximation to the score distribution.
+    pub fn llr(&self, stats: &MatchStats) -> f64 {
+        let variance = stats.variance();
+        if stats.games() == 0 || variance == 0.0 {
+            return 0.0;
+        }
+        let (s0, s1) = (elo_to_score(self.elo0), elo_to_score(self.elo1));
+        stats.games() as f64 * (s1 - s0) * (2.0 * stats.score() - s0 - s1) / (2.0 * variance)
+    }
+
+    pub fn bounds(&self) -> (f64, f64) {
+        ((self.beta / (1.0 - self.alpha)).ln(), ((1.0 - self.beta) / self.alpha).ln())
+    }
+
+    /// Some(true) once H1 is accepted, Some(false) once H0 is accepted, None while undecided.
+    pub fn decision(&self, stats: &MatchStats) -> Option<bool> {
+        let (llr, (lower, upper)) = (self.llr(stats), self.bounds());
+        if llr >= upper {
+            Some(true)
+        } else if llr <= lower {
+            Some(false)
+        } else {
+            None
+        }
+    }
+}
+
+impl std::str::FromStr for Sprt {
+    type Err = String;
+
+    // Parses "elo0,elo1".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (elo0, elo1) = s.split_once(',').ok_or_else(|| format!("invalid SPRT bounds {s:?}"))?;
+        let parse = |e: &str| e.trim().parse::<f64>().map_err(|_| format!("invalid SPRT bounds {s:?}"));
+        Ok(Sprt::new(parse(elo0)?, parse(elo1)?))
+    }
+}
+
+// Openings that end the game are played again, this many times at most.
+const OPENING_ATTEMPTS: u32 = 1000;
+
+/// Plays `plies` random moves from the start, retrying if the game ends on the way.
+pub fn random_opening(plies: u8, rng: &mut StdRng) -> Result<Vec<Move>, Error> {
+    random_opening_from(&GameState::starting_state(), plies, rng)
+}
+
+/// Plays `plies` random moves from `start`, retrying if the game ends on the way. Fails if the
+/// opening is as long as the game, or if every attempt ended the game.
+pub fn random_opening_from(start: &GameState, plies: u8, rng: &mut StdRng) -> Result<Vec<Move>, Error> {
+    check_opening(start, plies)?;
+    'retry: for _ in 0..OPENING_ATTEMPTS {
+        let mut state = *start;
+        let mut moves = Vec::new();
+        for _ in 0..plies {
+            let mv = *state.legal_moves().choose(rng).expect("opening should not end the game");
+            state.apply_move(&mv);
+            if state.is_over() {
+                continue 'retry;
+            }
+            moves.push(mv);
+        }
+        return Ok(moves);
+    }
+    Err(Error::Invalid(format!("no random opening of {plies} moves was found that did not end the game")))
+}
+
+/// Checks the rules are for a game of two players, as matches between two agents are.
+pub fn check_rules(rules: &Rules) -> Result<(), Error> {
+    if rules.players != 2 {
+        return Err(Error::Invalid(format!("the rules are for {} players, play free-for-all games instead", rules.players)));
+    }
+    rules.validate::<{ BOARD_SIZE as usize }>()
+}
+
+/// Checks an opening of `plies` moves from `start` leaves the game a move to play.
+pub fn check_opening(start: &GameState, plies: u8) -> Result<(), Error> {
+    if plies >= start.moves_left() {
+        return Err(Error::Invalid(format!("an opening must be shorter than the {} moves the game lasts", start.moves_left())));
+    }
+    Ok(())
+}
+
+/// Plays a game from `start` to the end after the given opening moves. The agents are White, then Red.
+pub fn play_game(agents: [&mut dyn Agent; 2], start: &GameState, opening: &[Move]) -> GameRecord {
+    let [white, red] = agents;
+    let mut record = GameRecord::new(white.name(), red.name());
+    record.set_start(start);
+    let mut state = *start;
+    // Indexed by PlayerColor.
+    let mut players: [&mut dyn Agent; 2] = [red, white];
+    for mv in opening {
+        for agent in players.iter_mut() {
+            agent.notify_move(&state, mv);
+        }
+        state.apply_move(mv);
+        record.moves.push(*mv);
+    }
+    while state.outcome().is_none() {
+        let mv = players[state.player_to_move() as usize].choose_move(&state);
+        for agent in players.iter_mut() {
+            agent.notify_move(&state, &mv);
+        }
+        state.apply_move(&mv);
+        record.moves.push(mv);
+    }
+    record.outcome = state.outcome();
+    for agent in players.iter_mut() {
+        agent.game_over(&state, record.outcome.unwrap());
+    }
+    record
+}
+
+/// Runs a match across `threads` threads, calling `progress` after every finished game.
+pub fn run_match(config: &MatchConfig, mut progress: impl FnMut(&GameRecord, &MatchStats)) -> Result<MatchStats, String> {
+    let specs = config.agents.iter().map(|spec| AgentSpec::parse(spec)).collect::<Result<Vec<_>, _>>()?;
+    if config.threads > 1 && specs.iter().any(|spec| spec.is_human()) {
+        return Err("a human can only play in a match on one thread".to_string());
+    }
+    check_rules(&config.rules)?;
+    check_opening(&GameState::starting_state_with(config.rules)?, config.opening_plies)?;
+    let next_game = AtomicU32::new(0);
+    let stop = AtomicBool::new(false);
+    let (sender, receiver) = mpsc::channel();
+    let mut stats = MatchStats::default();
+    thread::scope(|scope| -> Result<(), String> {
+        for _ in 0..config.threads.max(1) {
+            let sender = sender.clone();
+            let (next_game, stop, specs) = (&next_game, &stop, &specs);
+            scope.spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    let game = next_game.fetch_add(1, Ordering::Relaxed);
+                    if game >= config.games {
+                        break;
+                    }
+                    let mut rng = StdRng::seed_from_u64(config.seed.wrapping_add((game / 2) as u64));
+                    let start = match config.random_setup {
+                        true => random_setup(config.rules, &mut rng).expect("the rules were checked"),
+                        false => GameState::starting_state_with(config.rules).expect("the rules were checked"),
+                    };
+                    let opening = match random_opening_from(&start, config.opening_plies, &mut rng) {
+                        Ok(opening) => opening,
+                        Err(e) => {
+                            let _ = sender.send(Err(e));
+                            break;
+                        }
+                    };
+                    let seed = config.seed.wrapping_add(game as u64);
+                    let mut a = specs[0].build(seed);
+                    let mut b = specs[1].build(seed);
+                    let first_agent_white = game % 2 == 0;
+                    let mut record = if first_agent_white {
+                        play_game([a.as_mut(), b.as_mut()], &start, &opening)
+                    } else {
+                        play_game([b.as_mut(), a.as_mut()], &start, &opening)
+                    };
+                    let [white, red] = if first_agent_white { [0, 1] } else { [1, 0] };
+                    (record.white, record.red) = (config.agents[white].clone(), config.agents[red].clone());
+                    let first_agent = if first_agent_white { PlayerColor::White } else { PlayerColor::Red };
+                    if sender.send(Ok((record, first_agent))).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+        for result in receiver {
+            let (record, first_agent) = result?;
+            if let Some(path) = &config.records {
+                record.append_to(path).map_err(|e| format!("{path}: {e}"))?;
+            }
+            stats.add(record.outcome, first_agent);
+            progress(&record, &stats);
+            if config.sprt.is_some_and(|sprt| sprt.decision(&stats).is_some()) {
+                stop.store(true, Ordering::Relaxed);
+            }
+        }
+        Ok(())
+    })?;
+    Ok(stats)
+}
+
+pub fn print_result(config: &MatchConfig, stats: &MatchStats) {
+    println!("{} vs {}: {stats}", config.agents[0], config.agents[1]);
+    if let Some(sprt) = config.sprt {
+        let (lower, upper) = sprt.bounds();
+        let verdict = match sprt.decision(stats) {
+            Some(true) => "H1 accepted",
+            Some(false) => "H0 accepted",
+            None => "inconclusive",
+        };
+        println!("SPRT [{}, {}]: LLR {:.2} ({:.2}, {:.2}) {verdict}", sprt.elo0, sprt.elo1, sprt.llr(stats), lower, upper);
+    }
+}
+
+pub fn game_summary(record: &GameRecord, stats: &MatchStats) -> String {
+    format!("Game {}: {} vs {} {} | {stats}", stats.games(), record.white, record.red, result_string(record.outcome))
+}
+
+/// FreeForAllConfig describes games between as many agents as the rules have players, given as
+/// agent specs. The agents take turns at each seat, so every opening is played once per seat.
+#[derive(Clone, Debug)]
+pub struct FreeForAllConfig {
+    pub agents: Vec<String>,
+    pub rules: Rules,
+    pub games: u32,
+    pub opening_plies: u8,
+    pub seed: u64,
+}
+
+/// FreeForAllStats counts the wins of each agent, indexed like the config's agents, and the draws.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FreeForAllStats {
+    pub wins: Vec<u32>,
+    pub draws: u32,
+}
+
+impl FreeForAllStats {
+    pub fn games(&self) -> u32 {
+        self.wins.iter().sum::<u32>() + self.draws
+    }
+}
+
+/// Plays a game from `start` to the end after the given opening moves, with any number of players.
+/// The agents are in turn order, starting with White.
+pub fn play_free_for_all_game(agents: &mut [Box<dyn Agent>], start: &GameState, opening: &[Move]) -> (Vec<Move>, Outcome) {
+    let mut state = *start;
+    let mut moves = Vec::new();
+    for mv in opening {
+        for agent in agents.iter_mut() {
+            agent.notify_move(&state, mv);
+        }
+        state.apply_move(mv);
+        moves.push(*mv);
+    }
+    while state.outcome().is_none() {
+        let mv = agents[state.player_to_move().turn_index()].choose_move(&state);
+        for agent in agents.iter_mut() {
+            agent.notify_move(&state, &mv);
+        }
+        state.apply_move(&mv);
+        moves.push(mv);
+    }
+    let outcome = state.outcome().unwrap();
+    for agent in agents.iter_mut() {
+        agent.game_over(&state, outcome);
+    }
+    (moves, outcome)
+}
+
+/// Plays the games one after another, calling `progress` after each with the agents' indexes in
+/// turn order, the moves and the outcome.
+pub fn run_free_for_all(config: &FreeForAllConfig, mut progress: impl FnMut(&[usize], &[Move], Outcome, &FreeForAllStats)) -> Result<FreeForAllStats, String> {
+    config.rules.validate::<{ BOARD_SIZE as usize }>()?;
+    let n = config.agents.len();
+    if n != config.rules.players as usize {
+        return Err(format!("the rules are for {} players, but there are {n} agents", config.rules.players));
+    }
+    let specs = config.agents.iter().map(|spec| AgentSpec::parse(spec)).collect::<Result<Vec<_>, _>>()?;
+    for spec in &specs {
+        spec.check_players(n)?;
+    }
+    check_opening(&GameState::starting_state_with(config.rules)?, config.opening_plies)?;
+    let mut stats = FreeForAllStats{wins: vec![0; n], draws: 0};
+    for game in 0..config.games {
+        let mut rng = StdRng::seed_from_u64(config.seed.wrapping_add((game / n as u32) as u64));
+        let start = GameState::starting_state_with(config.rules)?;
+        let opening = random_opening_from(&start, config.opening_plies, &mut rng)?;
+        let seed = config.seed.wrapping_add(game as u64);
+        let seats: Vec<usize> = (0..n).map(|seat| (seat + game as usize) % n).collect();
+        let mut agents: Vec<_> = seats.iter().map(|&i| specs[i].build(seed)).collect();
+        let (moves, outcome) = play_free_for_all_game(&mut agents, &start, &opening);
+        match outcome {
+            Outcome::Win(p) => stats.wins[seats[p.turn_index()]] += 1,
+            Outcome::Draw => stats.draws += 1,
+        }
+        progress(&seats, &moves, outcome, &stats);
+    }
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn openings() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let start = GameState::starting_state();
+        let opening = random_opening_from(&start, start.moves_left() - 1, &mut rng).unwrap();
+        assert_eq!(opening.len() as u8, start.moves_left() - 1);
+        assert!(matches!(random_opening_from(&start, start.moves_left(), &mut rng), Err(Error::Invalid(_))));
+        let config = MatchConfig {
+            agents: ["random".to_string(), "random".to_string()],
+            rules: Rules::standard(),
+            games: 1,
+            threads: 1,
+            opening_plies: 16,
+            random_setup: false,
+            seed: 0,
+            records: None,
+            sprt: None,
+        };
+        assert!(run_match(&config, |_, _| {}).is_err());
+    }
+}
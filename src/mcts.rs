@@ -0,0 +1,173 @@
+//! Monte Carlo tree search, guided by an evaluator.
+use crate::evaluator::Evaluator;
+use crate::game_state::{GameState, Move, Outcome};
+
+// Exploration constant for the PUCT selection rule.
+const C_PUCT: f32 = 1.5;
+
+// First play urgency: a move not yet tried is taken to be worth less than the position it is
+// played from, by this much once all the likely moves have been tried, so the priors decide which
+// moves are tried, and the search widens only once the moves it has tried look worse than that.
+const FPU_REDUCTION: f32 = 0.2;
+
+/// MctsResult is what a search found: the move to play and the statistics behind it.
+#[derive(Clone, Debug)]
+pub struct MctsResult {
+    /// The most visited move, or None if the game is over.
+    pub best_move: Option<Move>,
+    /// Root visit counts for every legal move, a policy target for training.
+    pub visits: Vec<(Move, u32)>,
+    /// Mean value of the root for the player to move.
+    pub value: f32,
+}
+
+struct Node {
+    state: GameState,
+    mv: Option<Move>,
+    prior: f32,
+    visits: u32,
+    // Summed from the point of view of the player who made `mv`.
+    value_sum: f32,
+    // The value for the player who made `mv`, if it ended the game.
+    result: Option<f32>,
+    children: Vec<usize>,
+    expanded: bool,
+}
+
+impl Node {
+    fn new(state: GameState, mv: Option<Move>, prior: f32) -> Self {
+        let mover = state.player_to_move().previous(state.players().len() as u8);
+        let result = state.outcome().map(|outcome| match outcome {
+            Outcome::Win(p) if p == mover => 1.0,
+            Outcome::Win(_) => -1.0,
+            Outcome::Draw => 0.0,
+        });
+        Node{state, mv, prior, visits: 0, value_sum: 0.0, result, children: Vec::new(), expanded: false}
+    }
+
+    fn mean_value(&self) -> f32 {
+        if self.visits == 0 { 0.0 } else { self.value_sum / self.visits as f32 }
+    }
+}
+
+/// Mcts is a Monte Carlo tree search guided by an evaluator's value and move priors (PUCT), in the
+/// style of AlphaZero: leaves are evaluated rather than played out.
+pub struct Mcts<'a> {
+    evaluator: &'a dyn Evaluator,
+    nodes: Vec<Node>,
+}
+
+impl<'a> Mcts<'a> {
+    /// A search guided by the evaluator.
+    pub fn new(evaluator: &'a dyn Evaluator) -> Self {
+        Mcts{evaluator, nodes: Vec::new()}
+    }
+
+    /// Runs `iterations` iterations from the state, at least one, starting a new tree.
+    pub fn search(&mut self, state: &GameState, iterations: u32) -> MctsResult {
+        self.nodes.clear();
+        self.nodes.push(Node::new(*state, None, 1.0));
+        for _ in 0..iterations.max(1) {
+            self.iterate();
+        }
+        let root = &self.nodes[0];
+        let visits: Vec<(Move, u32)> = root.children.iter()
+            .map(|&c| (self.nodes[c].mv.unwrap(), self.nodes[c].visits))
+            .collect();
+        let best_move = visits.iter().max_by_key(|(_, n)| *n).map(|(mv, _)| *mv);
+        MctsResult{best_move, visits, value: -root.mean_value()}
+    }
+
+    fn iterate(&mut self) {
+        let mut path = vec![0];
+        let mut node = 0;
+        while self.nodes[node].expanded && !self.nodes[node].children.is_empty() {
+            node = self.select_child(node);
+            path.push(node);
+        }
+        // The leaf's value for its player to move.
+        let value = match self.nodes[node].state.outcome() {
+            Some(Outcome::Draw) => 0.0,
+            Some(Outcome::Win(p)) if p == self.nodes[node].state.player_to_move() => 1.0,
+            Some(Outcome::Win(_)) => -1.0,
+            None => self.expand(node),
+        };
+        // Each node stores value for the player who moved into it, the opponent of its player to move.
+        let mut value = -value;
+        for &n in path.iter().rev() {
+            self.nodes[n].visits += 1;
+            self.nodes[n].value_sum += value;
+            value = -value;
+        }
+    }
+
+    fn select_child(&self, node: usize) -> usize {
+        let parent = &self.nodes[node];
+        let sqrt_visits = (parent.visits as f32).sqrt();
+        // The parent's value is for the player who moved into it, its children's for the other. The
+        // fewer of the likely moves have been tried, the less the untried ones are marked down.
+        let tried: f32 = parent.children.iter().map(|&c| &self.nodes[c]).filter(|c| c.visits > 0).map(|c| c.prior).sum();
+        let first_play = -parent.mean_value() - FPU_REDUCTION * tried.sqrt();
+        let score = |c: usize| {
+            let child = &self.nodes[c];
+            // Moves that end the game are worth their result from the start, so that a win is
+            // never overlooked however unlikely the evaluator thought it.
+            let value = match (child.visits, child.result) {
+                (0, Some(result)) => result,
+                (0, None) => first_play,
+                _ => child.mean_value(),
+            };
+            value + C_PUCT * child.prior * sqrt_visits / (1.0 + child.visits as f32)
+        };
+        *parent.children.iter()
+            .max_by(|&&a, &&b| score(a).total_cmp(&score(b)))
+            .expect("expanded node should have children")
+    }
+
+    fn expand(&mut self, node: usize) -> f32 {
+        let state = self.nodes[node].state;
+        let moves = state.legal_moves();
+        let priors = self.evaluator.policy(&state, &moves);
+        for (mv, prior) in moves.into_iter().zip(priors) {
+            let mut child = state;
+            child.apply_move(&mv);
+            self.nodes.push(Node::new(child, Some(mv), prior));
+            let index = self.nodes.len() - 1;
+            self.nodes[node].children.push(index);
+        }
+        self.nodes[node].expanded = true;
+        self.evaluator.value(&state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Knows nothing of the position, but is sure which move is best.
+    struct Preferring(Move);
+
+    impl Evaluator for Preferring {
+        fn value(&self, _state: &GameState) -> f32 {
+            0.0
+        }
+
+        fn policy(&self, _state: &GameState, moves: &[Move]) -> Vec<f32> {
+            let others = 0.1 / (moves.len() - 1) as f32;
+            moves.iter().map(|mv| if *mv == self.0 { 0.9 } else { others }).collect()
+        }
+    }
+
+    #[test]
+    fn priors_guide_the_search() {
+        let state = GameState::starting_state();
+        let moves = state.legal_moves();
+        let preferred = moves[moves.len() / 2];
+        // As many iterations as there are moves, which trying every move first would spend on
+        // trying each once.
+        let result = Mcts::new(&Preferring(preferred)).search(&state, moves.len() as u32);
+        assert_eq!(result.best_move, Some(preferred));
+        let visits = result.visits.iter().find(|(mv, _)| *mv == preferred).unwrap().1;
+        assert!(visits as usize > moves.len() / 2, "the preferred move had {visits} of {} visits", moves.len());
+    }
+}
@@ -0,0 +1,219 @@
+//! Searching games of more than two players.
+use crate::error::Error;
+use crate::game_state::{GameState, Move, Outcome, PlayerColor, MAX_PLAYERS};
+use crate::search::WIN_SCORE;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Instant;
+
+/// Strategy is what a search of a game of more than two players assumes the others will do.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Hash)]
+#[non_exhaustive]
+pub enum Strategy {
+    /// Max-n: every player plays the move best for themselves.
+    MaxN,
+    /// The other players gang up on the player searching, which allows alpha-beta pruning.
+    Paranoid,
+}
+
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Strategy::MaxN => write!(f, "max-n"),
+            Strategy::Paranoid => write!(f, "paranoid"),
+        }
+    }
+}
+
+impl FromStr for Strategy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "max-n" => Ok(Strategy::MaxN),
+            "paranoid" => Ok(Strategy::Paranoid),
+            _ => Err(Error::Parse(format!("unknown strategy {s:?}, expected max-n or paranoid"))),
+        }
+    }
+}
+
+// Scores for each player, indexed by PlayerColor.
+type Scores = [i32; MAX_PLAYERS];
+
+/// MultiSearchResult is the best move found, with its score and the line expected to follow.
+#[derive(Clone, Debug)]
+pub struct MultiSearchResult {
+    /// The best move, or None if the game is over.
+    pub best_move: Option<Move>,
+    /// The score for the player to move, on the same scale as the two player search.
+    pub score: i32,
+    /// The principal variation: the best move, then the moves expected in reply.
+    pub pv: Vec<Move>,
+    /// The depth searched.
+    pub depth: u8,
+    /// The positions searched.
+    pub nodes: u64,
+}
+
+/// MultiSearch is a fixed-depth search for games of any number of players. Each player's score
+/// comes from a line heuristic of their own: the squares they top in lines no one else tops,
+/// less the best of the other players' totals.
+pub struct MultiSearch {
+    strategy: Strategy,
+    nodes: u64,
+    deadline: Option<Instant>,
+    aborted: bool,
+}
+
+impl MultiSearch {
+    /// A search assuming the strategy of the other players.
+    pub fn new(strategy: Strategy) -> Self {
+        MultiSearch{strategy, nodes: 0, deadline: None, aborted: false}
+    }
+
+    /// Stops iterative deepening searches once the deadline passes.
+    pub fn with_deadline(mut self, deadline: Option<Instant>) -> Self {
+        self.deadline = deadline;
+        self
+    }
+
+    /// Searches to increasing depths up to `max_depth`, returning the deepest search that finished
+    /// before the deadline. The first ply always finishes, so there is a move if the game isn't over.
+    pub fn search_iterative(&mut self, state: &GameState, max_depth: u8) -> MultiSearchResult {
+        let deadline = self.deadline.take();
+        let mut result = self.search(state, 1.min(max_depth));
+        let mut nodes = result.nodes;
+        self.deadline = deadline;
+        for depth in 2..=max_depth {
+            if self.deadline.is_some_and(|d| Instant::now() >= d) {
+                break;
+            }
+            let deeper = self.search(state, depth);
+            nodes += deeper.nodes;
+            if self.aborted {
+                break;
+            }
+            result = deeper;
+        }
+        result.nodes = nodes;
+        result
+    }
+
+    /// Searches `depth` plies ahead. If the deadline passes first the result is unfinished, as
+    /// search_iterative() leaves out.
+    pub fn search(&mut self, state: &GameState, depth: u8) -> MultiSearchResult {
+        self.nodes = 0;
+        self.aborted = false;
+        let me = state.player_to_move();
+        let (score, pv) = match self.strategy {
+            Strategy::MaxN => {
+                let (scores, pv) = self.max_n(state, depth, 0);
+                (scores[me as usize], pv)
+            }
+            Strategy::Paranoid => self.paranoid(state, me, depth, 0, -WIN_SCORE - 1, WIN_SCORE + 1),
+        };
+        MultiSearchResult{best_move: pv.first().copied(), score, pv, depth, nodes: self.nodes}
+    }
+
+    // Counts a node, checking the deadline every so often.
+    fn visit(&mut self) -> bool {
+        self.nodes += 1;
+        if self.nodes.is_multiple_of(1024) && self.deadline.is_some_and(|d| Instant::now() >= d) {
+            self.aborted = true;
+        }
+        !self.aborted
+    }
+
+    fn max_n(&mut self, state: &GameState, depth: u8, ply: i32) -> (Scores, Vec<Move>) {
+        if !self.visit() || depth == 0 || state.is_over() {
+            return (leaf_scores(state, ply), Vec::new());
+        }
+        let me = state.player_to_move() as usize;
+        let mut best: Option<(Scores, Vec<Move>)> = None;
+        for mv in state.legal_moves() {
+            let mut child = *state;
+            child.apply_move(&mv);
+            let (scores, line) = self.max_n(&child, depth - 1, ply + 1);
+            if self.aborted {
+                return (scores, Vec::new());
+            }
+            if best.as_ref().is_none_or(|(b, _)| scores[me] > b[me]) {
+                best = Some((scores, std::iter::once(mv).chain(line).collect()));
+                // Nothing beats winning straight away.
+                if scores[me] >= WIN_SCORE - ply - 1 {
+                    break;
+                }
+            }
+        }
+        best.expect("a game that isn't over has moves")
+    }
+
+    // Scores are for `root`, who maximises while everyone else minimises.
+    fn paranoid(&mut self, state: &GameState, root: PlayerColor, depth: u8, ply: i32, mut alpha: i32, mut beta: i32) -> (i32, Vec<Move>) {
+        if !self.visit() || depth == 0 || state.is_over() {
+            return (leaf_scores(state, ply)[root as usize], Vec::new());
+        }
+        let maximising = state.player_to_move() == root;
+        let mut best = (if maximising { -WIN_SCORE - 1 } else { WIN_SCORE + 1 }, Vec::new());
+        for mv in state.legal_moves() {
+            let mut child = *state;
+            child.apply_move(&mv);
+            let (score, line) = self.paranoid(&child, root, depth - 1, ply + 1, alpha, beta);
+            if self.aborted {
+                return (score, Vec::new());
+            }
+            if (maximising && score > best.0) || (!maximising && score < best.0) {
+                best = (score, std::iter::once(mv).chain(line).collect());
+            }
+            if maximising {
+                alpha = alpha.max(score);
+            } else {
+                beta = beta.min(score);
+            }
+            if alpha >= beta {
+                break;
+            }
+        }
+        best
+    }
+}
+
+fn leaf_scores(state: &GameState, ply: i32) -> Scores {
+    let mut scores = [0; MAX_PLAYERS];
+    match state.outcome() {
+        Some(Outcome::Win(winner)) => {
+            for &p in state.players() {
+                scores[p as usize] = if p == winner { WIN_SCORE - ply } else { -(WIN_SCORE - ply) };
+            }
+        }
+        Some(Outcome::Draw) => (),
+        None => {
+            let heuristic = line_heuristic(state);
+            for &p in state.players() {
+                let best_other = state.players().iter().filter(|&&q| q != p).map(|&q| heuristic[q as usize]).max().unwrap_or(0);
+                scores[p as usize] = heuristic[p as usize] - best_other;
+            }
+        }
+    }
+    scores
+}
+
+// For each player, the sum of the squares of the number of squares they top in each winning line
+// no other player tops any of.
+fn line_heuristic(state: &GameState) -> Scores {
+    let top = state.board().top();
+    let mut totals = [0; MAX_PLAYERS];
+    for line in state.rules().winning_lines() {
+        let mut counts = [0; MAX_PLAYERS];
+        for pos in line.positions() {
+            if let Some(p) = PlayerColor::from_pile_top(top.pile(pos)) {
+                counts[p as usize] += 1;
+            }
+        }
+        let mut owners = counts.iter().enumerate().filter(|(_, &n)| n > 0);
+        if let (Some((p, &n)), None) = (owners.next(), owners.next()) {
+            totals[p] += n * n;
+        }
+    }
+    totals
+}
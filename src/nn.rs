@@ -0,0 +1,300 @@
+//! A small neural network evaluator, and how positions and moves are encoded for it.
+use crate::error::Error;
+use crate::evaluator::Evaluator;
+use crate::game_state::{GameState, Move};
+use crate::position::NUM_PILES;
+use crate::stone::Stone;
+use crate::stone_pile::PILE_CAPACITY;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::fs;
+use std::io;
+
+/// Each pile is encoded as one plane per stone kind (own, opponent, neutral) at every height,
+/// relative to the player to move, followed by the stones each player has left to place.
+pub const INPUT_SIZE: usize = NUM_PILES * PILE_CAPACITY * 3 + 2;
+
+/// Policy logits are indexed by the start and end square of a move. Moves sharing both
+/// squares share a logit.
+pub const POLICY_SIZE: usize = NUM_PILES * NUM_PILES;
+
+const FILE_HEADER: &str = "qawale-mlp 1";
+
+/// The network's input for the state, INPUT_SIZE values as described there.
+pub fn encode(state: &GameState) -> Vec<f32> {
+    let own = state.player_to_move().stone_color();
+    let mut input = vec![0.0; INPUT_SIZE];
+    for (pos, pile) in state.board().iter_piles() {
+        for (height, stone) in pile.iter().enumerate() {
+            let plane = match stone {
+                Stone::Neutral => 2,
+                s if s == own => 0,
+                _ => 1,
+            };
+            input[(pos.to_index() * PILE_CAPACITY + height) * 3 + plane] = 1.0;
+        }
+    }
+    let max = state.rules().stones_per_player as f32;
+    input[INPUT_SIZE - 2] = state.stones_left(state.player_to_move()) as f32 / max;
+    input[INPUT_SIZE - 1] = state.stones_left(state.next_player()) as f32 / max;
+    input
+}
+
+/// The policy logit for the move, from its start and end squares.
+pub fn policy_index(mv: &Move) -> usize {
+    mv.path.start().to_index() * NUM_PILES + mv.path.end().to_index()
+}
+
+/// TrainingSample is a position with the targets the network should learn for it, usually
+/// taken from self-play: the eventual result for the player to move and the search's visit
+/// distribution over the legal moves.
+#[derive(Clone, Debug)]
+pub struct TrainingSample {
+    /// The position.
+    pub state: GameState,
+    /// The result for the player to move: 1 for a win, -1 for a loss and 0 for a draw.
+    pub value: f32,
+    /// Each legal move with the share of the search's visits it had.
+    pub policy: Vec<(Move, f32)>,
+}
+
+/// Mlp is a small value/policy network with a single ReLU hidden layer, cheap enough to
+/// evaluate and train on the CPU.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mlp {
+    hidden: usize,
+    // Hidden layer, stored row-major as hidden x INPUT_SIZE.
+    w1: Vec<f32>,
+    b1: Vec<f32>,
+    // Value head.
+    wv: Vec<f32>,
+    bv: f32,
+    // Policy head, stored row-major as POLICY_SIZE x hidden.
+    wp: Vec<f32>,
+    bp: Vec<f32>,
+}
+
+struct Activations {
+    hidden: Vec<f32>,
+    value: f32,
+    logits: Vec<f32>,
+}
+
+impl Mlp {
+    /// A network of `hidden` hidden units with random weights, to be trained.
+    pub fn new(hidden: usize, rng: &mut impl Rng) -> Self {
+        let mut init = |fan_in: usize, n: usize| -> Vec<f32> {
+            let bound = (6.0 / fan_in as f32).sqrt();
+            (0..n).map(|_| rng.gen_range(-bound..bound)).collect()
+        };
+        Mlp {
+            hidden,
+            w1: init(INPUT_SIZE, hidden * INPUT_SIZE),
+            b1: vec![0.0; hidden],
+            wv: init(hidden, hidden),
+            bv: 0.0,
+            wp: init(hidden, POLICY_SIZE * hidden),
+            bp: vec![0.0; POLICY_SIZE],
+        }
+    }
+
+    /// Reads a network written by save.
+    pub fn load(path: &str) -> Result<Self, Error> {
+        let text = fs::read_to_string(path).map_err(Error::io(path))?;
+        let invalid = |msg: String| Error::Parse(format!("{path}: {msg}"));
+        let mut lines = text.lines();
+        if lines.next() != Some(FILE_HEADER) {
+            return Err(invalid(format!("expected header {FILE_HEADER:?}")));
+        }
+        let mut tokens = lines.flat_map(|l| l.split_whitespace());
+        let mut next_usize = |what: &str| -> Result<usize, Error> {
+            tokens.next()
+                .and_then(|t| t.parse().ok())
+                .ok_or_else(|| invalid(format!("bad {what}")))
+        };
+        let (inputs, hidden, policy) = (next_usize("input size")?, next_usize("hidden size")?, next_usize("policy size")?);
+        if inputs != INPUT_SIZE || policy != POLICY_SIZE {
+            return Err(invalid(format!("network is {inputs}x{hidden}x{policy}, want {INPUT_SIZE}xNx{POLICY_SIZE}")));
+        }
+        // The hidden size is read from the file, so the sizes of its layers may not fit.
+        let layer = |rows: usize| rows.checked_mul(hidden).ok_or_else(|| invalid(format!("hidden size {hidden} is too large")));
+        let (w1_size, wp_size) = (layer(INPUT_SIZE)?, layer(POLICY_SIZE)?);
+        let mut floats = tokens.map(|t| t.parse::<f32>());
+        let mut read = |n: usize| -> Result<Vec<f32>, Error> {
+            (0..n).map(|_| match floats.next() {
+                Some(Ok(x)) => Ok(x),
+                _ => Err(invalid("truncated or malformed weights".to_string())),
+            }).collect()
+        };
+        let w1 = read(w1_size)?;
+        let b1 = read(hidden)?;
+        let wv = read(hidden)?;
+        let bv = read(1)?[0];
+        let wp = read(wp_size)?;
+        let bp = read(POLICY_SIZE)?;
+        Ok(Mlp{hidden, w1, b1, wv, bv, wp, bp})
+    }
+
+    /// Writes the network's weights as text.
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let mut out = format!("{FILE_HEADER}\n{INPUT_SIZE} {} {POLICY_SIZE}\n", self.hidden);
+        for tensor in [&self.w1, &self.b1, &self.wv, &vec![self.bv], &self.wp, &self.bp] {
+            let row: Vec<String> = tensor.iter().map(|x| x.to_string()).collect();
+            out += &row.join(" ");
+            out.push('\n');
+        }
+        fs::write(path, out)
+    }
+
+    fn forward(&self, input: &[f32]) -> Activations {
+        let mut hidden = self.b1.clone();
+        for (j, h) in hidden.iter_mut().enumerate() {
+            let row = &self.w1[j * INPUT_SIZE..(j + 1) * INPUT_SIZE];
+            // Inputs are mostly zero, so skip straight over them.
+            *h += row.iter().zip(input).filter(|(_, &x)| x != 0.0).map(|(w, x)| w * x).sum::<f32>();
+            *h = h.max(0.0);
+        }
+        let value = (self.bv + dot(&self.wv, &hidden)).tanh();
+        let logits = (0..POLICY_SIZE)
+            .map(|k| self.bp[k] + dot(&self.wp[k * self.hidden..(k + 1) * self.hidden], &hidden))
+            .collect();
+        Activations{hidden, value, logits}
+    }
+
+    /// Trains with plain SGD on the value squared error plus the policy cross-entropy,
+    /// returning the mean loss over the final epoch.
+    pub fn train(&mut self, samples: &[TrainingSample], epochs: usize, learning_rate: f32, rng: &mut impl Rng) -> f32 {
+        let mut order: Vec<usize> = (0..samples.len()).collect();
+        let mut mean_loss = 0.0;
+        for _ in 0..epochs {
+            order.shuffle(rng);
+            let mut total = 0.0;
+            for &i in &order {
+                total += self.train_sample(&samples[i], learning_rate);
+            }
+            mean_loss = total / samples.len().max(1) as f32;
+        }
+        mean_loss
+    }
+
+    fn train_sample(&mut self, sample: &TrainingSample, lr: f32) -> f32 {
+        let input = encode(&sample.state);
+        let act = self.forward(&input);
+
+        let value_err = act.value - sample.value;
+        let mut loss = value_err * value_err;
+        let d_value = 2.0 * value_err * (1.0 - act.value * act.value);
+
+        let indices: Vec<usize> = sample.policy.iter().map(|(mv, _)| policy_index(mv)).collect();
+        let probs = softmax(indices.iter().map(|&k| act.logits[k]));
+        let mut d_logits = vec![0.0; POLICY_SIZE];
+        for ((&k, &p), (_, target)) in indices.iter().zip(&probs).zip(&sample.policy) {
+            loss -= target * p.max(1e-9).ln();
+            d_logits[k] += p - target;
+        }
+
+        let mut d_hidden: Vec<f32> = self.wv.iter().map(|w| w * d_value).collect();
+        for &k in &indices {
+            if d_logits[k] == 0.0 {
+                continue;
+            }
+            let row = &mut self.wp[k * self.hidden..(k + 1) * self.hidden];
+            for ((w, dh), h) in row.iter_mut().zip(d_hidden.iter_mut()).zip(&act.hidden) {
+                *dh += *w * d_logits[k];
+                *w -= lr * d_logits[k] * h;
+            }
+            self.bp[k] -= lr * d_logits[k];
+            // Shared logits have their whole gradient applied on the first visit.
+            d_logits[k] = 0.0;
+        }
+        for (w, h) in self.wv.iter_mut().zip(&act.hidden) {
+            *w -= lr * d_value * h;
+        }
+        self.bv -= lr * d_value;
+
+        for (j, dh) in d_hidden.iter().enumerate() {
+            if act.hidden[j] <= 0.0 {
+                continue;
+            }
+            let row = &mut self.w1[j * INPUT_SIZE..(j + 1) * INPUT_SIZE];
+            for (w, &x) in row.iter_mut().zip(&input) {
+                if x != 0.0 {
+                    *w -= lr * dh * x;
+                }
+            }
+            self.b1[j] -= lr * dh;
+        }
+        loss
+    }
+}
+
+impl Evaluator for Mlp {
+    fn value(&self, state: &GameState) -> f32 {
+        self.forward(&encode(state)).value
+    }
+
+    fn policy(&self, state: &GameState, moves: &[Move]) -> Vec<f32> {
+        let logits = self.forward(&encode(state)).logits;
+        softmax(moves.iter().map(|mv| logits[policy_index(mv)]))
+    }
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+fn softmax(logits: impl Iterator<Item = f32>) -> Vec<f32> {
+    let logits: Vec<f32> = logits.collect();
+    let max = logits.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+    let exps: Vec<f32> = logits.iter().map(|l| (l - max).exp()).collect();
+    let sum: f32 = exps.iter().sum();
+    exps.iter().map(|e| e / sum).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn temp_file(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("qawale-nn-{name}-{}.txt", std::process::id()));
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn save_and_load() {
+        let network = Mlp::new(8, &mut StdRng::seed_from_u64(1));
+        let path = temp_file("round-trip");
+        network.save(&path).unwrap();
+        let loaded = Mlp::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap(), network);
+    }
+
+    #[test]
+    fn sizes_too_large_are_refused() {
+        let path = temp_file("too-large");
+        std::fs::write(&path, format!("{FILE_HEADER}\n{INPUT_SIZE} {} {POLICY_SIZE}\n", usize::MAX / 2)).unwrap();
+        let loaded = Mlp::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(loaded, Err(Error::Parse(_))));
+    }
+
+    #[test]
+    fn training_lowers_the_loss() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let start = GameState::starting_state();
+        let moves = start.legal_moves();
+        let mut after = start;
+        after.apply_move(&moves[0]);
+        let samples = [
+            TrainingSample{state: start, value: 1.0, policy: vec![(moves[0], 0.75), (moves[1], 0.25)]},
+            TrainingSample{state: after, value: -1.0, policy: after.legal_moves().into_iter().take(1).map(|mv| (mv, 1.0)).collect()},
+        ];
+        let mut network = Mlp::new(16, &mut rng);
+        let first = network.train(&samples, 1, 0.01, &mut rng);
+        let last = network.train(&samples, 50, 0.01, &mut rng);
+        assert!(last < first / 2.0, "the loss went from {first} to {last}");
+    }
+}
@@ -1,89 +1,148 @@
-use crate::position::NUM_PILES;
-use crate::position::Position;
-use crate::position::Direction;
+//! Paths, along which a move spreads a pile's stones.
+use crate::error::Error;
+use crate::position::{Position, BOARD_SIZE};
+use crate::position::{Direction, Topology};
+use crate::rules::Rules;
 use crate::stone_pile::PILE_CAPACITY;
 use tinyvec::ArrayVec;
 use std::fmt;
 
+/// Path is the route a move spreads its pile along: a start square and a step per stone, one
+/// stone dropped on each square stepped to.
 #[derive(PartialEq, Eq, Copy, Clone, Debug, Hash)]
-pub struct Path {
-    start: Position,
+pub struct Path<const N: usize = { BOARD_SIZE as usize }> {
+    start: Position<N>,
     steps: ArrayVec<[Direction; PILE_CAPACITY]>,
-    end: Position,
+    end: Position<N>,
+    topology: Topology,
 }
 
-impl Path {
-    pub fn new(start: Position, steps: ArrayVec<[Direction; PILE_CAPACITY]>) -> Self {
+impl<const N: usize> Path<N> {
+    /// The path taking the steps from the start. Panics if a step leaves the board.
+    pub fn new(start: Position<N>, steps: ArrayVec<[Direction; PILE_CAPACITY]>, topology: Topology) -> Self {
         let mut end = start;
         for dir in steps {
-            end = end.step(dir);
+            end = end.step(dir, topology);
         }
-        Self{start, steps, end}
+        Self{start, steps, end, topology}
     }
 
-    pub fn new_empty(start: Position) -> Self {
-        Self{start, steps: ArrayVec::<[Direction; PILE_CAPACITY]>::new(), end: start}
+    /// The path that has not yet left the start.
+    pub fn new_empty(start: Position<N>, topology: Topology) -> Self {
+        Self{start, steps: ArrayVec::<[Direction; PILE_CAPACITY]>::new(), end: start, topology}
     }
 
-    pub fn start(&self) -> Position {
+    /// The square the pile is picked up from.
+    pub fn start(&self) -> Position<N> {
         self.start
     }
 
+    /// The steps, in order.
     pub fn steps(&self) -> &ArrayVec<[Direction; PILE_CAPACITY]> {
         &self.steps
     }
 
-    pub fn end(&self) -> Position {
+    /// The square the last step lands on, or the start if there are no steps.
+    pub fn end(&self) -> Position<N> {
         self.end
     }
 
+    /// The shape of the board the path is on.
+    pub fn topology(&self) -> Topology {
+        self.topology
+    }
+
+    /// Whether the path can go on in the direction without leaving the board.
     pub fn can_step(&self, dir: Direction) -> bool {
-        self.end.can_step(dir)
+        self.end.can_step(dir, self.topology)
     }
 
+    /// Adds a step to the end of the path. Panics if it leaves the board.
     pub fn step(&mut self, dir: Direction) {
-        self.end = self.end.step(dir);
+        self.end = self.end.step(dir, self.topology);
         self.steps.push(dir);
     }
 
+    /// Takes off the last step and returns it. Panics if there are no steps.
     pub fn pop_step(&mut self) -> Direction {
         let dir = self.steps.pop().expect("expect step to pop");
-        self.end = self.end.step(dir.reverse());
+        self.end = self.end.step(dir.reverse(), self.topology);
         dir
     }
 
+    /// The number of steps.
     pub fn len(&self) -> usize {
         self.steps.len()
     }
 
-    // This iterator will output len() positions, not including the start position.
-    pub fn iter_positions(&self) -> impl Iterator<Item = Position> + '_ {
-        PathPosIterator{path: &self, pos: self.start, step: 0}
+    /// Whether the path has no steps yet, only its start square.
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
     }
-}
 
-impl fmt::Display for Path {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        if self.len() == 0 {
-            return write!(f, "[empty-path]");
+    /// The compact notation for the path: the start square followed by a letter per step, e.g. "a1rrd".
+    pub fn notation(&self) -> String {
+        self.steps.iter().fold(self.start.name(), |acc, d| acc + &d.letter().to_string())
+    }
+
+    /// Parses compact path notation, checking only that the path stays on the board.
+    pub fn parse(notation: &str, topology: Topology) -> Result<Self, Error> {
+        if notation.len() < 2 || !notation.is_char_boundary(2) {
+            return Err(Error::Parse(format!("invalid path {notation:?}")));
+        }
+        let mut path = Self::new_empty(Position::parse(&notation[..2])?, topology);
+        for c in notation[2..].chars() {
+            let dir = Direction::from_letter(c).ok_or_else(|| Error::Parse(format!("invalid direction {c:?} in {notation:?}")))?;
+            if path.len() == PILE_CAPACITY {
+                return Err(Error::Parse(format!("path {notation:?} is too long")));
+            }
+            if !path.can_step(dir) {
+                return Err(Error::Invalid(format!("path {notation:?} leaves the board")));
+            }
+            path.step(dir);
         }
-        let mut rep = vec![String::from("."); NUM_PILES];
-        //rep[self.start.to_index()] = String::from("S");
+        Ok(path)
+    }
+
+    /// The path drawn on the board, indexed by square: the direction it leaves each square in, with
+    /// a barred arrow at the start, "x" at the end and "." off the path.
+    pub fn arrows(&self) -> Vec<String> {
+        let mut rep = vec![String::from("."); Position::<N>::NUM_PILES];
         let mut pos = self.start;
         for dir in self.steps {
             rep[pos.to_index()] = dir.to_string();
-            pos = pos.step(dir);
+            pos = pos.step(dir, self.topology);
+        }
+        if let Some(first) = self.steps.first() {
+            rep[self.start.to_index()] = match first {
+                Direction::Down => "↧",
+                Direction::Left => "↤",
+                Direction::Right => "↦",
+                Direction::Up => "↥",
+                Direction::DownRight => "⇘",
+                Direction::DownLeft => "⇙",
+                Direction::UpLeft => "⇖",
+                Direction::UpRight => "⇗",
+            }.to_string();
         }
-        rep[self.start.to_index()] = match self.steps[0] {
-            Direction::Down => "↧",
-            Direction::Left => "↤",
-            Direction::Right => "↦",
-            Direction::Up => "↥",
-        }.to_string();
         rep[self.end.to_index()] = String::from("x");
-        for (i, p) in rep.iter().enumerate() {
+        rep
+    }
+
+    /// This iterator will output len() positions, not including the start position.
+    pub fn iter_positions(&self) -> impl Iterator<Item = Position<N>> + '_ {
+        PathPosIterator{path: &self, pos: self.start, step: 0}
+    }
+}
+
+impl<const N: usize> fmt::Display for Path<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.len() == 0 {
+            return write!(f, "[empty-path]");
+        }
+        for (i, p) in self.arrows().iter().enumerate() {
             write!(f, "{}", p)?;
-            if Position::from_index(i).next_on_new_row() {
+            if Position::<N>::from_index(i).next_on_new_row() {
                 write!(f, "\n")?;
             }
         }
@@ -98,61 +157,81 @@ impl fmt::Display for Path {
     }
 }
 
-pub struct PathPosIterator<'a> {
-    path: &'a Path,
-    pos: Position,
+/// The squares a path steps to, in order, from Path::iter_positions.
+pub struct PathPosIterator<'a, const N: usize> {
+    path: &'a Path<N>,
+    pos: Position<N>,
     step: usize,
 }
 
-impl Iterator for PathPosIterator<'_> {
+impl<const N: usize> Iterator for PathPosIterator<'_, N> {
     // We can refer to this type using Self::Item
-    type Item = Position;
+    type Item = Position<N>;
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.step == self.path.len() {
             return None;
         }
-        self.pos = self.pos.step(self.path.steps[self.step]);
+        self.pos = self.pos.step(self.path.steps[self.step], self.path.topology);
         self.step += 1;
         Some(self.pos)
     }
 }
 
+/// PathGenerator lists the paths of one length from a square, as described for new.
 #[derive(PartialEq, Eq, Copy, Clone, Debug, Hash)]
-pub struct PathGenerator {
+pub struct PathGenerator<const N: usize = { BOARD_SIZE as usize }> {
     length: usize,
-    path: Path,
+    u_turns: bool,
+    diagonal_steps: bool,
+    path: Path<N>,
 }
 
-impl PathGenerator {
-    // PathGenerator iterates all the possible paths of a specified length starting at a specified position.
-    // Respects the confines of the board and rules of game (no u-turns).
-    pub fn new(start: Position, length: u8) -> PathGenerator {
+impl<const N: usize> PathGenerator<N> {
+    /// PathGenerator iterates all the possible paths of a specified length starting at a specified position.
+    /// Respects the confines of the board and rules of game (no u-turns, unless the rules allow them,
+    /// and no diagonal steps unless the rules allow them).
+    pub fn new(start: Position<N>, length: u8, rules: &Rules) -> Self {
         PathGenerator{
             length: length as usize,
-            path: Path::new_empty(start),
+            u_turns: rules.u_turns,
+            diagonal_steps: rules.diagonal_steps,
+            path: Path::new_empty(start, rules.topology),
         }
     }
 
+    // The directions the rules allow, in order, after `last` if given.
+    fn next_direction(&self, last: Option<Direction>) -> Option<Direction> {
+        let dir = match last {
+            Some(dir) => enum_iterator::next(&dir),
+            None => enum_iterator::first::<Direction>(),
+        };
+        // The diagonals come after all the orthogonal directions.
+        dir.filter(|d| self.diagonal_steps || !d.is_diagonal())
+    }
+
+    fn is_u_turn(&self, dir: Direction) -> bool {
+        !self.u_turns && !self.path.is_empty() && dir.reverse() == *self.path.steps.last().unwrap()
+    }
+
     fn complete_path(&mut self) {
         while self.path.len() < self.length {
-            for dir in enum_iterator::all::<Direction>() {
-                if !self.path.can_step(dir) {
-                    continue;
-                }
-                if self.path.len() > 0 && dir.reverse() == *self.path.steps.last().unwrap() {
+            let mut dir = self.next_direction(None);
+            while let Some(d) = dir {
+                if !self.path.can_step(d) || self.is_u_turn(d) {
+                    dir = self.next_direction(Some(d));
                     continue; // Can't do a u-turn.
                 }
-                self.path.step(dir);
+                self.path.step(d);
                 break;
             }
         }
     }
 }
 
-impl Iterator for PathGenerator {
+impl<const N: usize> Iterator for PathGenerator<N> {
     // We can refer to this type using Self::Item
-    type Item = Path;
+    type Item = Path<N>;
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.length == 0 {
@@ -166,7 +245,7 @@ impl Iterator for PathGenerator {
 
         let mut last_dir = self.path.pop_step();
         loop {
-            let dir = enum_iterator::next(&last_dir);
+            let dir = self.next_direction(Some(last_dir));
             if dir == None { // Tried all directions at this step, pop back up to prior step.
                 if self.path.len() == 0 {
                     return None;
@@ -179,7 +258,7 @@ impl Iterator for PathGenerator {
                 last_dir = dir;
                 continue;
             }
-            if self.path.len() > 0 && dir.reverse() == *self.path.steps.last().unwrap() {
+            if self.is_u_turn(dir) {
                 last_dir = dir;
                 continue; // Can't do a u-turn.
             }
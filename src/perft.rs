@@ -0,0 +1,113 @@
+use crate::board::Board;
+use crate::game_state::{Move, MoveGenerator, Outcome, PlayerColor};
+use crate::rules::Rules;
+
+/// DfsResults counts the boards reached by perft, and those won by each player.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DfsResults {
+    pub count: i32,
+    pub red_wins: i32,
+    pub white_wins: i32,
+}
+
+/// Counts the boards reached after `moves` more moves, starting with `player`, without stopping
+/// at wins, to check and time move generation.
+pub fn dfs<const N: usize>(board: Board<N>, player: PlayerColor, moves: i32, rules: &Rules, res: &mut DfsResults) {
+    if moves == 0 {
+        res.count += 1;
+        match rules.line_outcome(&board.top(), player.previous(rules.players)) {
+            Some(Outcome::Win(PlayerColor::Red)) => res.red_wins += 1,
+            Some(Outcome::Win(PlayerColor::White)) => res.white_wins += 1,
+            _ => (),
+        }
+        return;
+    }
+    let it = MoveGenerator::new(&board, rules);
+    for p in it {
+        let mut new_board = board;
+        new_board.apply_move(&Move {
+            new_stone: player.stone_color(),
+            path: p,
+        });
+        dfs(new_board, player.next(rules.players), moves - 1, rules, res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::Topology;
+
+    // The results after each of `depth` moves from the starting layout, as the perft command counts.
+    fn perft<const N: usize>(rules: &Rules, depth: i32) -> Vec<DfsResults> {
+        (1..=depth).map(|moves| {
+            let mut res = DfsResults::default();
+            dfs(Board::<N>::setup(rules), PlayerColor::White, moves, rules, &mut res);
+            res
+        }).collect()
+    }
+
+    fn counts(results: &[DfsResults]) -> Vec<i32> {
+        results.iter().map(|r| r.count).collect()
+    }
+
+    // Lines span the board, as in the perft command.
+    fn rules_for(size: u8) -> Rules {
+        Rules{line_length: size, ..Rules::standard()}
+    }
+
+    #[test]
+    fn standard_board() {
+        assert_eq!(counts(&perft::<4>(&Rules::standard(), 3)), [40, 2176, 143712]);
+    }
+
+    #[test]
+    #[ignore = "takes half a minute in a debug build"]
+    fn standard_board_four_moves() {
+        assert_eq!(perft::<4>(&Rules::standard(), 4)[3].count, 11129088);
+    }
+
+    #[test]
+    fn small_board() {
+        assert_eq!(counts(&perft::<3>(&rules_for(3), 3)), [32, 1344, 62784]);
+    }
+
+    #[test]
+    fn large_board() {
+        assert_eq!(counts(&perft::<5>(&rules_for(5), 3)), [40, 2152, 143600]);
+    }
+
+    // Each corner starts 81 paths of three steps in eight directions that never turn straight back.
+    #[test]
+    fn eight_way() {
+        assert_eq!(counts(&perft::<4>(&Rules::preset("eight-way").unwrap(), 2)), [324, 118848]);
+    }
+
+    #[test]
+    #[ignore = "takes over two minutes in a debug build"]
+    fn eight_way_three_moves() {
+        assert_eq!(perft::<4>(&Rules::preset("eight-way").unwrap(), 3)[2].count, 54658852);
+    }
+
+    // One neutral stone per corner keeps the moves few enough to reach the first wins, which lines of
+    // two give White on their second move.
+    fn torus_rules(wrapped_lines: bool) -> Rules {
+        Rules{topology: Topology::Torus, wrapped_lines, line_length: 2, neutrals: 1, ..Rules::standard()}
+    }
+
+    #[test]
+    fn torus() {
+        let standard = Rules{topology: Topology::Torus, ..Rules::standard()};
+        assert_eq!(counts(&perft::<4>(&standard, 2)), [144, 27936]);
+        let results = perft::<4>(&torus_rules(false), 3);
+        assert_eq!(counts(&results), [48, 3264, 292704]);
+        assert_eq!((results[2].white_wins, results[2].red_wins), (98984, 0));
+    }
+
+    #[test]
+    fn torus_with_wrapped_lines() {
+        let results = perft::<4>(&torus_rules(true), 3);
+        assert_eq!(counts(&results), [48, 3264, 292704]);
+        assert_eq!((results[2].white_wins, results[2].red_wins), (141576, 0));
+    }
+}
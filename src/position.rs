@@ -1,110 +1,243 @@
-
+//! Squares on the board, and the directions a path steps in between them.
+use crate::error::Error;
 use std::fmt;
 
 use enum_iterator::Sequence;
 
+/// The size of the standard board. Position and the types built on it are generic over the board
+/// size, defaulting to this.
 pub const BOARD_SIZE: u8 = 4;
+/// The number of squares, and so of piles, on the standard board.
 pub const NUM_PILES: usize = (BOARD_SIZE*BOARD_SIZE) as usize;
 
+/// Position is a square on an N by N board, numbered row by row from a1.
 #[derive(PartialEq, Eq, Copy, Clone, Debug, Hash, Default)]
-pub struct Position(u8);
+pub struct Position<const N: usize = { BOARD_SIZE as usize }>(u8);
+
+impl<const N: usize> Position<N> {
+    const SIZE: u8 = N as u8;
+    /// The number of squares on the board.
+    pub const NUM_PILES: usize = N * N;
 
-impl Position {
-    pub fn from_index(i: usize) -> Position {
+    /// The square numbered `i`, counting row by row from 0 for a1.
+    pub fn from_index(i: usize) -> Self {
         Position(i as u8)
     }
 
-    pub fn from_coord(x: u8, y: u8) -> Position {
-        Position(x + y*(BOARD_SIZE))
+    /// The square in column `x` and row `y`, both counted from 0.
+    pub fn from_coord(x: u8, y: u8) -> Self {
+        Position(x + y*(Self::SIZE))
     }
 
-    pub fn top_left() -> Position {
-        Position::from_coord(0, 0)
+    /// The corner square a1.
+    pub fn top_left() -> Self {
+        Self::from_coord(0, 0)
     }
 
-    pub fn top_right() -> Position {
-        Position::from_coord(0, BOARD_SIZE-1)
+    /// The corner square in column a and the last row, a4 on the standard board.
+    pub fn top_right() -> Self {
+        Self::from_coord(0, Self::SIZE-1)
     }
 
-    pub fn bottom_left() -> Position {
-        Position::from_coord(BOARD_SIZE-1, 0)
+    /// The corner square in the last column and row 1, d1 on the standard board.
+    pub fn bottom_left() -> Self {
+        Self::from_coord(Self::SIZE-1, 0)
     }
 
-    pub fn bottom_right() -> Position {
-        Position::from_coord(BOARD_SIZE-1, BOARD_SIZE-1)
+    /// The corner square in the last column and the last row, d4 on the standard board.
+    pub fn bottom_right() -> Self {
+        Self::from_coord(Self::SIZE-1, Self::SIZE-1)
     }
     
+    /// The column, counted from 0 for column a.
     pub fn x(&self) -> u8 {
-        self.0 % BOARD_SIZE
+        self.0 % Self::SIZE
     }
 
+    /// The row, counted from 0 for row 1.
     pub fn y(&self) -> u8 {
-        self.0 / BOARD_SIZE
+        self.0 / Self::SIZE
     }
 
+    /// The square's number, as taken by from_index.
     pub fn to_index(&self) -> usize {
         self.0 as usize
     }
 
-    // Returns true if the next position index (if there is one) is on the following row
+    /// Squares are named by column letter and row number, e.g. "a1" is (0,0) and "d4" is (3,3).
+    pub fn name(&self) -> String {
+        format!("{}{}", (b'a' + self.x()) as char, self.y() + 1)
+    }
+
+    /// Parses a square's name, e.g. "a1".
+    pub fn parse(name: &str) -> Result<Self, Error> {
+        let bytes = name.as_bytes();
+        if bytes.len() != 2 {
+            return Err(Error::Parse(format!("invalid square {name:?}")));
+        }
+        let (x, y) = (bytes[0].wrapping_sub(b'a'), bytes[1].wrapping_sub(b'1'));
+        if x >= Self::SIZE || y >= Self::SIZE {
+            return Err(Error::Parse(format!("invalid square {name:?}")));
+        }
+        Ok(Self::from_coord(x, y))
+    }
+
+    /// Returns true if the next position index (if there is one) is on the following row
     pub fn next_on_new_row(&self) -> bool {
-        (self.0 + 1)%BOARD_SIZE == 0 && (self.0 + 1) < (NUM_PILES as u8)
+        (self.0 + 1)%Self::SIZE == 0 && ((self.0 + 1) as usize) < Self::NUM_PILES
     }
 
-    pub fn can_step(&self, dir: Direction) -> bool {
+    /// The square dx columns and dy rows away, if it is on the board.
+    pub fn offset(&self, dx: i8, dy: i8) -> Option<Self> {
+        let (x, y) = (self.x() as i8 + dx, self.y() as i8 + dy);
+        let on_board = |c: i8| (0..Self::SIZE as i8).contains(&c);
+        (on_board(x) && on_board(y)).then(|| Self::from_coord(x as u8, y as u8))
+    }
+
+    /// Whether there is a square in the direction. On a torus there always is.
+    pub fn can_step(&self, dir: Direction, topology: Topology) -> bool {
+        if topology == Topology::Torus {
+            return true;
+        }
+        let (right, down, left, up) = (self.x() < Self::SIZE-1, self.y() < Self::SIZE-1, self.x() > 0, self.y() > 0);
         match dir {
-            Direction::Right => self.x() < BOARD_SIZE-1,
-            Direction::Down => self.y() < BOARD_SIZE-1,
-            Direction::Left => self.x() > 0,
-            Direction::Up => self.y() > 0,
+            Direction::Right => right,
+            Direction::Down => down,
+            Direction::Left => left,
+            Direction::Up => up,
+            Direction::DownRight => down && right,
+            Direction::DownLeft => down && left,
+            Direction::UpLeft => up && left,
+            Direction::UpRight => up && right,
         }
     }
 
-    pub fn step(&self, dir: Direction) -> Position {
+    /// The next square in the direction, which on a torus wraps around to the opposite edge.
+    pub fn step(&self, dir: Direction, topology: Topology) -> Self {
+        if topology == Topology::Torus {
+            let (dx, dy) = dir.vector();
+            let wrap = |c: u8, d: i8| (c as i8 + d).rem_euclid(Self::SIZE as i8) as u8;
+            return Self::from_coord(wrap(self.x(), dx), wrap(self.y(), dy));
+        }
         match dir {
             Direction::Right => {
-                if self.x() == BOARD_SIZE-1 { panic!("Trying to step {dir} from {self}"); }
-                Position::from_coord(self.x()+1, self.y())
+                if self.x() == Self::SIZE-1 { panic!("Trying to step {dir} from {self}"); }
+                Self::from_coord(self.x()+1, self.y())
             }
             Direction::Down => {
-                if self.y() == BOARD_SIZE-1 { panic!("Trying to step {dir} from {self}"); }
-                Position::from_coord(self.x(), self.y()+1)
+                if self.y() == Self::SIZE-1 { panic!("Trying to step {dir} from {self}"); }
+                Self::from_coord(self.x(), self.y()+1)
             }
             Direction::Left => {
                 if self.x() == 0 { panic!("Trying to step {dir} from {self}"); }
-                Position::from_coord(self.x()-1, self.y())
+                Self::from_coord(self.x()-1, self.y())
             }
             Direction::Up => {
                 if self.y() == 0 { panic!("Trying to step {dir} from {self}"); }
-                Position::from_coord(self.x(), self.y()-1)
+                Self::from_coord(self.x(), self.y()-1)
+            }
+            _ => {
+                let (dx, dy) = dir.vector();
+                self.offset(dx, dy).unwrap_or_else(|| panic!("Trying to step {dir} from {self}"))
             }
         }
     }
 }
 
-impl fmt::Display for Position {
+/// Topology is the shape of the board: flat with edges, or a torus where stepping off one edge
+/// comes back on at the opposite one.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Hash, Default)]
+#[non_exhaustive]
+pub enum Topology {
+    /// A board with edges that paths cannot step over.
+    #[default]
+    Flat,
+    /// A board whose opposite edges are joined.
+    Torus,
+}
+
+impl<const N: usize> fmt::Display for Position<N> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "({},{})", self.x(), self.y())
     }
 }
 
+/// The diagonal directions come last, so that iterating directions in order and stopping at the
+/// first diagonal gives just the orthogonal ones.
 #[derive(PartialEq, Eq, Copy, Clone, Debug, Hash, Default, Sequence)]
 #[repr(u8)]
 pub enum Direction {
+    /// The next column, written r.
     #[default]
     Right,
+    /// The next row, written d.
     Down,
+    /// The previous column, written l.
     Left,
+    /// The previous row, written u.
     Up,
+    /// The next column and row, written c.
+    DownRight,
+    /// The previous column and the next row, written z.
+    DownLeft,
+    /// The previous column and row, written q.
+    UpLeft,
+    /// The next column and the previous row, written e.
+    UpRight,
 }
 
 impl Direction {
+    /// The single letter used for the direction in move notation. Diagonal steps use the keys
+    /// around "s" on a keyboard: q and e for up, z and c for down.
+    pub fn letter(&self) -> char {
+        match self {
+            Direction::Right => 'r',
+            Direction::Down => 'd',
+            Direction::Left => 'l',
+            Direction::Up => 'u',
+            Direction::DownRight => 'c',
+            Direction::DownLeft => 'z',
+            Direction::UpLeft => 'q',
+            Direction::UpRight => 'e',
+        }
+    }
+
+    /// Whether the direction changes both the column and the row.
+    pub fn is_diagonal(&self) -> bool {
+        let (dx, dy) = self.vector();
+        dx != 0 && dy != 0
+    }
+
+    /// The change in column and row for a step in the direction.
+    pub fn vector(&self) -> (i8, i8) {
+        match self {
+            Direction::Right => (1, 0),
+            Direction::Down => (0, 1),
+            Direction::Left => (-1, 0),
+            Direction::Up => (0, -1),
+            Direction::DownRight => (1, 1),
+            Direction::DownLeft => (-1, 1),
+            Direction::UpLeft => (-1, -1),
+            Direction::UpRight => (1, -1),
+        }
+    }
+
+    /// The direction written as the letter, if any.
+    pub fn from_letter(c: char) -> Option<Direction> {
+        enum_iterator::all::<Direction>().find(|d| d.letter() == c)
+    }
+
+    /// The opposite direction.
     pub fn reverse(&self) -> Direction {
         match self {
             Direction::Right => Direction::Left,
             Direction::Down => Direction::Up,
             Direction::Left => Direction::Right,
             Direction::Up => Direction::Down,
+            Direction::DownRight => Direction::UpLeft,
+            Direction::DownLeft => Direction::UpRight,
+            Direction::UpLeft => Direction::DownRight,
+            Direction::UpRight => Direction::DownLeft,
         }
     }
 }
@@ -116,6 +249,10 @@ impl fmt::Display for Direction {
             Direction::Down => write!(f, "↓"),
             Direction::Left => write!(f, "←"),
             Direction::Up => write!(f, "↑"),
+            Direction::DownRight => write!(f, "↘"),
+            Direction::DownLeft => write!(f, "↙"),
+            Direction::UpLeft => write!(f, "↖"),
+            Direction::UpRight => write!(f, "↗"),
         }
     }
 }
\ No newline at end of file
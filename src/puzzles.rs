@@ -0,0 +1,139 @@
+//! Win-in-n puzzles found in the positions of self-play games.
+use crate::agent::AgentSpec;
+use crate::error::Error;
+use crate::evaluator::Evaluator;
+use crate::game_state::{GameState, Move};
+use crate::match_runner::{play_game, random_opening};
+use crate::search::{is_win_score, Search, WIN_SCORE};
+use crate::symmetry::canonical;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::collections::HashSet;
+
+/// Puzzle is a position where the player to move has exactly one move that forces a win in
+/// `moves_to_win` of their own moves, and none that wins sooner.
+#[derive(Clone, Debug)]
+pub struct Puzzle {
+    /// The position to solve.
+    pub state: GameState,
+    /// The number of their own moves the player to move needs to win, counting the last.
+    pub moves_to_win: u8,
+    /// The winning line, alternating with the opponent's best defence.
+    pub solution: Vec<Move>,
+}
+
+impl Puzzle {
+    /// The puzzle as a single line: `<position string>; <moves to win>; <solution>`.
+    pub fn line(&self) -> String {
+        let solution: Vec<String> = self.solution.iter().map(|mv| mv.to_string()).collect();
+        format!("{}; {}; {}", self.state.position_string(), self.moves_to_win, solution.join(" "))
+    }
+}
+
+/// PuzzleConfig is the puzzles to generate and the games to look for them in.
+#[derive(Clone, Debug)]
+pub struct PuzzleConfig {
+    /// The moves to win of every puzzle.
+    pub moves_to_win: u8,
+    /// The puzzles wanted.
+    pub count: usize,
+    /// Self-play games to sample positions from, at most.
+    pub max_games: u32,
+    /// The agent spec playing both sides of the self-play games.
+    pub agent: String,
+    /// The seed for the first game; each game after uses the next.
+    pub seed: u64,
+}
+
+/// Checks whether the state is a win-in-n puzzle, returning it in its canonical orientation.
+pub fn find_win_in(state: &GameState, n: u8, evaluator: &dyn Evaluator) -> Option<Puzzle> {
+    if state.is_over() || n == 0 || n > state.moves_left().div_ceil(2) {
+        return None;
+    }
+    let plies = 2 * n - 1;
+    let target = WIN_SCORE - plies as i32;
+    // A quick search rules out most positions before the more expensive check for uniqueness.
+    if Search::new(evaluator).search(state, plies).score != target {
+        return None;
+    }
+    let lines = Search::new(evaluator).search_multipv(state, plies, usize::MAX);
+    // The search only sees wins within `plies`, so any other winning move also wins in n. Moves
+    // that are the same up to the board's symmetry count as one, where the rules are symmetric.
+    let winning: HashSet<u64> = lines.iter().take_while(|line| is_win_score(line.score) && line.score > 0).map(|line| {
+        let mut child = *state;
+        child.apply_move(&line.moves[0]);
+        canonical(&child).0
+    }).collect();
+    if winning.len() > 1 {
+        return None;
+    }
+    let symmetry = canonical(state).1;
+    Some(Puzzle {
+        state: symmetry.apply_state(state),
+        moves_to_win: n,
+        solution: lines[0].moves.iter().map(|mv| symmetry.apply_move(mv)).collect(),
+    })
+}
+
+/// Plays self-play games and collects distinct puzzles from their positions, until there are
+/// `count` of them or `max_games` have been played.
+pub fn generate(config: &PuzzleConfig, evaluator: &dyn Evaluator, mut progress: impl FnMut(u32, &[Puzzle])) -> Result<Vec<Puzzle>, Error> {
+    // Each side's moves are the most a win can take.
+    let most = GameState::starting_state().moves_left().div_ceil(2);
+    if !(1..=most).contains(&config.moves_to_win) {
+        return Err(Error::Invalid(format!("the moves to win must be from 1 to {most}")));
+    }
+    let spec = AgentSpec::parse(&config.agent)?;
+    let mut puzzles = Vec::new();
+    let mut seen = HashSet::new();
+    for game in 0..config.max_games {
+        let seed = config.seed.wrapping_add(game as u64);
+        let mut white = spec.build(seed);
+        let mut red = spec.build(seed.wrapping_add(1));
+        let opening = random_opening(2, &mut StdRng::seed_from_u64(seed))?;
+        let record = play_game([white.as_mut(), red.as_mut()], &GameState::starting_state(), &opening);
+        for state in record.states() {
+            if !seen.insert(canonical(&state).0) {
+                continue;
+            }
+            if let Some(puzzle) = find_win_in(&state, config.moves_to_win, evaluator) {
+                puzzles.push(puzzle);
+                if puzzles.len() == config.count {
+                    return Ok(puzzles);
+                }
+            }
+        }
+        progress(game + 1, &puzzles);
+    }
+    Ok(puzzles)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::LineEvaluator;
+    use crate::game_state::Outcome;
+    use crate::rules::Rules;
+
+    #[test]
+    fn win_in_two() {
+        let state = GameState::parse_position("WW,n,WW,R/Rn,,nW,/Rn,nR,,/R,nn,n, W", Rules::standard()).unwrap();
+        assert!(find_win_in(&state, 1, &LineEvaluator).is_none());
+        let puzzle = find_win_in(&state, 2, &LineEvaluator).unwrap();
+        assert_eq!(puzzle.moves_to_win, 2);
+        // Red's defence depends on the evaluator, but not White's winning move.
+        assert_eq!(puzzle.state, state);
+        assert_eq!(puzzle.solution.len(), 3);
+        assert_eq!(puzzle.solution[0].to_string(), "c1rdl");
+        let mut after = puzzle.state;
+        for mv in &puzzle.solution {
+            after.apply_move(mv);
+        }
+        assert_eq!(after.outcome(), Some(Outcome::Win(state.player_to_move())));
+    }
+
+    #[test]
+    fn no_win_from_the_start() {
+        assert!(find_win_in(&GameState::starting_state(), 2, &LineEvaluator).is_none());
+    }
+}
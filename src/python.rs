@@ -0,0 +1,337 @@
+//! Python bindings, built with the `python` feature. The shared library is then the extension
+//! module `qawale`: copied or linked as `qawale.so` (`qawale.pyd` on Windows) somewhere on the
+//! Python path, it is used as
+//!
+//! ```python
+//! import qawale
+//!
+//! state = qawale.GameState()  # or GameState("nn,,,nn/,,,/,,,/nn,,,nn W", rules="three")
+//! state.play("a1rrr")
+//! result = qawale.search(state, depth=4)
+//! print(result.best_move, result.score, state.legal_moves())
+//! samples = qawale.selfplay(games=10, iterations=200)
+//! ```
+//!
+//! Moves, positions and rules are strings in the same notations as the command line, and players
+//! are their letters. Invalid ones raise ValueError.
+use crate::agent::{Agent, AgentSpec};
+use crate::cli;
+use crate::evaluator::{Evaluator, LineEvaluator};
+use crate::game_state::{GameState, Outcome};
+use crate::mcts::Mcts;
+use crate::nn::{self, Mlp, TrainingSample, POLICY_SIZE};
+use crate::rules::Rules;
+use crate::search::Search;
+use crate::selfplay::{run_selfplay, SelfPlayConfig};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use std::collections::HashMap;
+use std::thread;
+use std::time::Instant;
+
+fn value_error(e: impl ToString) -> PyErr {
+    PyValueError::new_err(e.to_string())
+}
+
+// The network at `weights`, or none for the LineEvaluator.
+fn load_network(weights: Option<&str>) -> PyResult<Option<Mlp>> {
+    weights.map(|path| Mlp::load(path).map_err(value_error)).transpose()
+}
+
+fn evaluator(network: &Option<Mlp>) -> &(dyn Evaluator + Sync) {
+    match network {
+        Some(network) => network,
+        None => &LineEvaluator,
+    }
+}
+
+// Search and MCTS score a position for the player to move against a single opponent.
+fn check_two_players(state: &GameState, function: &str) -> PyResult<()> {
+    if state.players().len() > 2 {
+        return Err(value_error(format!("{function} is only for two player games, use an Agent such as paranoid:depth=3")));
+    }
+    Ok(())
+}
+
+fn outcome_string(outcome: Option<Outcome>) -> Option<String> {
+    match outcome? {
+        Outcome::Win(p) => Some(p.letter().to_string()),
+        Outcome::Draw => Some("draw".to_string()),
+    }
+}
+
+/// A game in progress: the board, whose turn it is and the stones each player has left.
+#[pyclass(name = "GameState", module = "qawale", eq, from_py_object)]
+#[derive(Clone, PartialEq)]
+struct PyGameState {
+    state: GameState,
+}
+
+#[pymethods]
+impl PyGameState {
+    /// The position given as a position string, or the starting position, played by the rules.
+    #[new]
+    #[pyo3(signature = (position=None, rules=None))]
+    fn new(position: Option<&str>, rules: Option<&str>) -> PyResult<Self> {
+        let rules: Rules = rules.unwrap_or("standard").parse().map_err(value_error)?;
+        let state = match position {
+            Some(position) => GameState::parse_position(position, rules).map_err(value_error)?,
+            None => GameState::starting_state_with(rules).map_err(value_error)?,
+        };
+        Ok(PyGameState{state})
+    }
+
+    #[getter]
+    fn position(&self) -> String {
+        self.state.position_string()
+    }
+
+    #[getter]
+    fn rules(&self) -> String {
+        self.state.rules().to_string()
+    }
+
+    #[getter]
+    fn to_move(&self) -> char {
+        self.state.player_to_move().letter()
+    }
+
+    /// The players, in turn order.
+    #[getter]
+    fn players(&self) -> Vec<char> {
+        self.state.players().iter().map(|p| p.letter()).collect()
+    }
+
+    #[getter]
+    fn stones_left(&self) -> HashMap<char, u8> {
+        self.state.players().iter().map(|&p| (p.letter(), self.state.stones_left(p))).collect()
+    }
+
+    #[getter]
+    fn moves_left(&self) -> u8 {
+        self.state.moves_left()
+    }
+
+    #[getter]
+    fn is_over(&self) -> bool {
+        self.state.is_over()
+    }
+
+    /// The winner's letter, "draw", or None while the game goes on.
+    #[getter]
+    fn outcome(&self) -> Option<String> {
+        outcome_string(self.state.outcome())
+    }
+
+    /// The legal moves in compact notation, none once the game is over.
+    fn legal_moves(&self) -> Vec<String> {
+        self.state.legal_moves().iter().map(|mv| mv.to_string()).collect()
+    }
+
+    /// Plays a move in compact notation.
+    fn play(&mut self, notation: &str) -> PyResult<()> {
+        let mv = self.state.parse_move(notation).map_err(value_error)?;
+        self.state.apply_move(&mv);
+        Ok(())
+    }
+
+    /// The state after a move, leaving this one as it is.
+    fn after(&self, notation: &str) -> PyResult<Self> {
+        let mut next = self.clone();
+        next.play(notation)?;
+        Ok(next)
+    }
+
+    fn copy(&self) -> Self {
+        self.clone()
+    }
+
+    /// The position as the network's input features, from the point of view of the player to move.
+    fn encode(&self) -> Vec<f32> {
+        nn::encode(&self.state)
+    }
+
+    fn __str__(&self) -> String {
+        format!("{}\n{:?} to move", self.state.board(), self.state.player_to_move())
+    }
+
+    fn __repr__(&self) -> String {
+        format!("GameState({:?}, rules={:?})", self.state.position_string(), self.state.rules().to_string())
+    }
+}
+
+/// The best move found by `search`, with its score for the player to move and the expected line.
+#[pyclass(name = "SearchResult", module = "qawale", get_all)]
+struct PySearchResult {
+    best_move: Option<String>,
+    score: i32,
+    pv: Vec<String>,
+    depth: u8,
+    nodes: u64,
+}
+
+/// Searches a two player game for the best move, to `depth` moves ahead (at least one) or, with a
+/// time limit in seconds, as deep as it gets in time up to `depth`. The GIL is released while searching.
+#[pyfunction]
+#[pyo3(signature = (state, depth=4, time=None, weights=None))]
+fn search(py: Python<'_>, state: &PyGameState, depth: u8, time: Option<f64>, weights: Option<&str>) -> PyResult<PySearchResult> {
+    check_two_players(&state.state, "search")?;
+    // A search of no moves would find no move to play.
+    let depth = depth.max(1);
+    let time = time.map(cli::seconds).transpose().map_err(value_error)?;
+    let network = load_network(weights)?;
+    let state = state.state;
+    let result = py.detach(|| {
+        let mut search = Search::new(evaluator(&network));
+        match time {
+            Some(time) => search.with_deadline(Some(Instant::now() + time)).search_iterative(&state, depth),
+            None => search.search(&state, depth),
+        }
+    });
+    Ok(PySearchResult{
+        best_move: result.best_move.map(|mv| mv.to_string()),
+        score: result.score,
+        pv: result.pv.iter().map(|mv| mv.to_string()).collect(),
+        depth: result.depth,
+        nodes: result.nodes,
+    })
+}
+
+/// The move found by `mcts`, with the visits to every legal move and the value of the position
+/// for the player to move.
+#[pyclass(name = "MctsResult", module = "qawale", get_all)]
+struct PyMctsResult {
+    best_move: Option<String>,
+    visits: HashMap<String, u32>,
+    value: f32,
+}
+
+/// Runs Monte Carlo tree search on the position of a two player game. The GIL is released while searching.
+#[pyfunction]
+#[pyo3(signature = (state, iterations=1000, weights=None))]
+fn mcts(py: Python<'_>, state: &PyGameState, iterations: u32, weights: Option<&str>) -> PyResult<PyMctsResult> {
+    check_two_players(&state.state, "mcts")?;
+    let network = load_network(weights)?;
+    let state = state.state;
+    let result = py.detach(|| Mcts::new(evaluator(&network)).search(&state, iterations));
+    Ok(PyMctsResult{
+        best_move: result.best_move.map(|mv| mv.to_string()),
+        visits: result.visits.iter().map(|(mv, n)| (mv.to_string(), *n)).collect(),
+        value: result.value,
+    })
+}
+
+/// One of the bots the command line plays with, from a spec such as "greedy", "search:depth=4",
+/// "mcts:iterations=2000,weights=net.txt" or "paranoid:depth=3".
+#[pyclass(name = "Agent", module = "qawale", unsendable)]
+struct PyAgent {
+    spec: AgentSpec,
+    agent: Box<dyn Agent>,
+}
+
+#[pymethods]
+impl PyAgent {
+    #[new]
+    #[pyo3(signature = (spec, seed=0))]
+    fn new(spec: &str, seed: u64) -> PyResult<Self> {
+        let spec = AgentSpec::parse(spec).map_err(value_error)?;
+        let agent = spec.build(seed);
+        Ok(PyAgent{spec, agent})
+    }
+
+    #[getter]
+    fn name(&self) -> String {
+        self.agent.name()
+    }
+
+    /// The agent's move for the player to move.
+    fn choose_move(&mut self, state: &PyGameState) -> PyResult<String> {
+        if state.state.is_over() {
+            return Err(value_error("the game is over".to_string()));
+        }
+        self.spec.check_players(state.state.players().len()).map_err(value_error)?;
+        Ok(self.agent.choose_move(&state.state).to_string())
+    }
+
+    /// Tells the agent a move was played from the state, by any player.
+    fn notify_move(&mut self, state: &PyGameState, notation: &str) -> PyResult<()> {
+        let mv = state.state.parse_move(notation).map_err(value_error)?;
+        self.agent.notify_move(&state.state, &mv);
+        Ok(())
+    }
+
+    /// Tells the agent the game has finished in the state.
+    fn game_over(&mut self, state: &PyGameState) -> PyResult<()> {
+        let outcome = state.state.outcome().ok_or_else(|| value_error("the game is not over".to_string()))?;
+        self.agent.game_over(&state.state, outcome);
+        Ok(())
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Agent({:?})", self.agent.name())
+    }
+}
+
+/// A position from self-play with the targets the network learns for it: the game's result for
+/// the player to move and the search's share of visits to each move.
+#[pyclass(name = "TrainingSample", module = "qawale")]
+struct PyTrainingSample {
+    sample: TrainingSample,
+}
+
+#[pymethods]
+impl PyTrainingSample {
+    #[getter]
+    fn state(&self) -> PyGameState {
+        PyGameState{state: self.sample.state}
+    }
+
+    #[getter]
+    fn value(&self) -> f32 {
+        self.sample.value
+    }
+
+    #[getter]
+    fn policy(&self) -> HashMap<String, f32> {
+        self.sample.policy.iter().map(|(mv, p)| (mv.to_string(), *p)).collect()
+    }
+
+    /// The network's input features for the position.
+    fn encode(&self) -> Vec<f32> {
+        nn::encode(&self.sample.state)
+    }
+
+    /// The policy as the network's output vector, indexed by each move's start and end square.
+    fn policy_target(&self) -> Vec<f32> {
+        let mut target = vec![0.0; POLICY_SIZE];
+        for (mv, p) in &self.sample.policy {
+            target[nn::policy_index(mv)] += p;
+        }
+        target
+    }
+}
+
+/// Plays games of MCTS against itself, across `threads` threads (by default one per core), and
+/// returns a training sample for every position. The GIL is released while playing.
+#[pyfunction]
+#[pyo3(signature = (games=20, iterations=400, sampling_plies=4, threads=None, seed=0, weights=None))]
+fn selfplay(py: Python<'_>, games: u32, iterations: u32, sampling_plies: u8, threads: Option<usize>, seed: u64, weights: Option<&str>) -> PyResult<Vec<PyTrainingSample>> {
+    let network = load_network(weights)?;
+    let threads = threads.unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()));
+    let config = SelfPlayConfig{games, iterations, sampling_plies, threads, seed};
+    let samples = py.detach(|| run_selfplay(&config, evaluator(&network), |_| {}));
+    Ok(samples.into_iter().map(|sample| PyTrainingSample{sample}).collect())
+}
+
+#[pymodule]
+fn qawale(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyGameState>()?;
+    m.add_class::<PySearchResult>()?;
+    m.add_class::<PyMctsResult>()?;
+    m.add_class::<PyAgent>()?;
+    m.add_class::<PyTrainingSample>()?;
+    m.add_function(wrap_pyfunction!(search, m)?)?;
+    m.add_function(wrap_pyfunction!(mcts, m)?)?;
+    m.add_function(wrap_pyfunction!(selfplay, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,208 @@
+//! Game records: the moves of a game and its result, in a plain text format.
+use crate::error::Error;
+use crate::game_state::{GameState, Move, Outcome, PlayerColor};
+use crate::rules::Rules;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+
+/// GameRecord is a finished (or abandoned) game from the starting position, written as a few
+/// tag lines followed by the moves in compact notation, e.g.
+///
+/// [White "search-d3"]
+/// [Red "greedy"]
+/// [Result "1-0"]
+/// a1rrd d4uul ...
+///
+/// Moves may be numbered ("1." tokens are ignored) and annotated with {comments}. Games not played
+/// by the standard rules have a [Rules "..."] tag before the moves, and games not started from the
+/// rules' starting position a [Setup "..."] tag giving its position string after that.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GameRecord {
+    /// The name of the player who played White.
+    pub white: String,
+    /// The name of the player who played Red.
+    pub red: String,
+    /// The rules the game was played by.
+    pub rules: Rules,
+    /// The position the game started from, if not the rules' starting position.
+    pub setup: Option<GameState>,
+    /// The moves played, in order.
+    pub moves: Vec<Move>,
+    /// How the game ended, or None if it was abandoned.
+    pub outcome: Option<Outcome>,
+}
+
+impl GameRecord {
+    /// A record of a game yet to be played by the standard rules.
+    pub fn new(white: String, red: String) -> Self {
+        GameRecord{white, red, rules: Rules::standard(), setup: None, moves: Vec::new(), outcome: None}
+    }
+
+    /// Every state of the game in order, from the start up to and including the final position.
+    pub fn states(&self) -> Vec<GameState> {
+        let mut state = self.start();
+        let mut states = vec![state];
+        for mv in &self.moves {
+            state.apply_move(mv);
+            states.push(state);
+        }
+        states
+    }
+
+    /// The state the game started from.
+    ///
+    /// # Panics
+    /// If there is no setup and the rules were set to ones that cannot be played. Parsed records
+    /// and those started with set_start always have rules that can.
+    pub fn start(&self) -> GameState {
+        self.setup.unwrap_or_else(|| GameState::starting_state_with(self.rules).expect("the record's rules should be valid"))
+    }
+
+    /// Sets where the game starts from, leaving out the setup if it is the usual one.
+    pub fn set_start(&mut self, start: &GameState) {
+        self.rules = *start.rules();
+        self.setup = Some(*start).filter(|s| GameState::starting_state_with(self.rules).ok() != Some(*s));
+    }
+
+    /// The state after the last move.
+    pub fn final_state(&self) -> GameState {
+        *self.states().last().unwrap()
+    }
+
+    /// Parses one game, checking every move is legal.
+    pub fn parse(text: &str) -> Result<GameRecord, Error> {
+        let mut record = GameRecord::new(String::new(), String::new());
+        let mut state = GameState::starting_state();
+        let text = strip_comments(text);
+        for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            if let Some(tag) = line.strip_prefix('[') {
+                let (key, value) = parse_tag(tag).ok_or_else(|| Error::Parse(format!("invalid tag line {line:?}")))?;
+                match key {
+                    "White" => record.white = value.to_string(),
+                    "Red" => record.red = value.to_string(),
+                    "Result" => record.outcome = parse_result(value)?,
+                    "Rules" if record.moves.is_empty() && record.setup.is_none() => {
+                        record.rules = value.parse()?;
+                        state = GameState::starting_state_with(record.rules)?;
+                    }
+                    "Rules" => return Err(Error::Parse("the Rules tag must come before the Setup tag and the moves".to_string())),
+                    "Setup" if record.moves.is_empty() => {
+                        state = GameState::parse_position(value, record.rules)?;
+                        record.setup = Some(state);
+                    }
+                    "Setup" => return Err(Error::Parse("the Setup tag must come before the moves".to_string())),
+                    _ => (),
+                }
+                continue;
+            }
+            for token in line.split_whitespace().filter(|t| !t.ends_with('.')) {
+                let mv = state.parse_move(token)?;
+                state.apply_move(&mv);
+                record.moves.push(mv);
+            }
+        }
+        Ok(record)
+    }
+
+    /// Reads every game in a file; games are separated by blank lines.
+    pub fn read_all(path: &str) -> Result<Vec<GameRecord>, Error> {
+        let text = fs::read_to_string(path).map_err(Error::io(path))?;
+        split_games(&text).map(|g| GameRecord::parse(g).map_err(|e| e.at(path))).collect()
+    }
+
+    /// Adds the game to the end of a file, creating it if need be.
+    pub fn append_to(&self, path: &str) -> io::Result<()> {
+        let mut file = fs::OpenOptions::new().create(true).append(true).open(path)?;
+        writeln!(file, "{self}")
+    }
+}
+
+impl fmt::Display for GameRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "[White \"{}\"]", self.white)?;
+        writeln!(f, "[Red \"{}\"]", self.red)?;
+        writeln!(f, "[Result \"{}\"]", result_string(self.outcome))?;
+        if self.rules != Rules::standard() {
+            writeln!(f, "[Rules \"{}\"]", self.rules)?;
+        }
+        if let Some(setup) = &self.setup {
+            writeln!(f, "[Setup \"{}\"]", setup.position_string())?;
+        }
+        let moves: Vec<String> = self.moves.iter().map(|mv| mv.to_string()).collect();
+        writeln!(f, "{}", moves.join(" "))
+    }
+}
+
+fn strip_comments(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut depth = 0;
+    for c in text.chars() {
+        match c {
+            '{' => depth += 1,
+            '}' if depth > 0 => depth -= 1,
+            _ if depth == 0 => out.push(c),
+            _ => (),
+        }
+    }
+    out
+}
+
+fn split_games(text: &str) -> impl Iterator<Item = &str> {
+    text.split("\n\n").filter(|g| !g.trim().is_empty())
+}
+
+fn parse_tag(tag: &str) -> Option<(&str, &str)> {
+    let (key, rest) = tag.strip_suffix(']')?.split_once(' ')?;
+    Some((key, rest.trim().strip_prefix('"')?.strip_suffix('"')?))
+}
+
+/// The outcome as a Result tag gives it: "1-0" for a win for White, "0-1" for Red, "1/2-1/2" for a
+/// draw and "*" for a game not finished.
+pub fn result_string(outcome: Option<Outcome>) -> &'static str {
+    match outcome {
+        Some(Outcome::Win(PlayerColor::White)) => "1-0",
+        Some(Outcome::Win(PlayerColor::Red)) => "0-1",
+        // Games of more players score each in turn order.
+        Some(Outcome::Win(PlayerColor::Blue)) => "0-0-1",
+        Some(Outcome::Win(PlayerColor::Green)) => "0-0-0-1",
+        Some(Outcome::Draw) => "1/2-1/2",
+        None => "*",
+    }
+}
+
+fn parse_result(result: &str) -> Result<Option<Outcome>, Error> {
+    match result {
+        "1-0" => Ok(Some(Outcome::Win(PlayerColor::White))),
+        "0-1" => Ok(Some(Outcome::Win(PlayerColor::Red))),
+        "0-0-1" => Ok(Some(Outcome::Win(PlayerColor::Blue))),
+        "0-0-0-1" => Ok(Some(Outcome::Win(PlayerColor::Green))),
+        "1/2-1/2" => Ok(Some(Outcome::Draw)),
+        "*" => Ok(None),
+        _ => Err(Error::Parse(format!("invalid result {result:?}"))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let text = "[White \"a\"]\n[Red \"b\"]\n[Result \"*\"]\n[Rules \"three-player\"]\na1rrr d4lll\n";
+        let record = GameRecord::parse(text).unwrap();
+        assert_eq!(record.rules, Rules::preset("three-player").unwrap());
+        assert_eq!(record.moves.len(), 2);
+        assert_eq!(record.to_string(), text);
+    }
+
+    #[test]
+    fn rules_that_cannot_be_played() {
+        for rules in ["standard:neutrals=30", "standard:players=9"] {
+            let tag = format!("[Rules \"{rules}\"]");
+            let refused = |text: &str| matches!(GameRecord::parse(text), Err(Error::Invalid(_)));
+            assert!(refused(&format!("{tag}\na1rrr")), "{rules}");
+            assert!(refused(&format!("{tag}\n[Setup \"nn,,,nn/,,,/,,,/nn,,,nn W\"]")), "{rules}");
+        }
+    }
+}
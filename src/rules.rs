@@ -0,0 +1,264 @@
+//! The rules a game is played by, and the versions of the game they allow.
+use crate::board::{lines, BoardTop, Line};
+use crate::error::Error;
+use crate::game_state::{Outcome, PlayerColor, MAX_PLAYERS, STONES_PER_PLAYER};
+use crate::position::{Position, Topology};
+use crate::stone_pile::PILE_CAPACITY;
+use std::fmt;
+use std::str::FromStr;
+
+/// Layout is where the neutral stones are piled at the start of the game.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Hash)]
+#[non_exhaustive]
+pub enum Layout {
+    /// The four corners, written "corners".
+    Corners,
+    /// The four squares around the centre of the board, written "centre".
+    Centre,
+}
+
+impl Layout {
+    /// The squares the neutral stones start on.
+    pub fn squares<const N: usize>(&self) -> [Position<N>; 4] {
+        match self {
+            Layout::Corners => [Position::top_left(), Position::top_right(), Position::bottom_left(), Position::bottom_right()],
+            Layout::Centre => {
+                // On odd sized boards these are the centre's diagonal neighbours.
+                let (low, high) = ((N as u8 - 1) / 2, N as u8 / 2);
+                let (low, high) = if low == high { (low - 1, high + 1) } else { (low, high) };
+                [Position::from_coord(low, low), Position::from_coord(high, low), Position::from_coord(low, high), Position::from_coord(high, high)]
+            }
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Layout::Corners => "corners",
+            Layout::Centre => "centre",
+        }
+    }
+}
+
+/// SimultaneousLines decides the game when a move leaves both players topping a line.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Hash)]
+#[non_exhaustive]
+pub enum SimultaneousLines {
+    /// Whoever tops the first line found, checking rows, then columns, then diagonals. Written
+    /// "first".
+    FirstFound,
+    /// The player who moved, written "mover".
+    Mover,
+    /// The mover's opponent; with more than two players, the first to move after the mover who
+    /// tops a line. Written "opponent".
+    Opponent,
+    /// Nobody, written "draw".
+    Draw,
+}
+
+impl SimultaneousLines {
+    fn name(&self) -> &'static str {
+        match self {
+            SimultaneousLines::FirstFound => "first",
+            SimultaneousLines::Mover => "mover",
+            SimultaneousLines::Opponent => "opponent",
+            SimultaneousLines::Draw => "draw",
+        }
+    }
+}
+
+/// Rules are the choices that vary between versions of the game. They are written as a preset
+/// name, optionally followed by options overriding it, e.g. "standard:line=3,diagonals=no".
+///
+/// New options are added as new versions of the game come up, so Rules are built from a preset or
+/// parsed rather than written out field by field.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Hash)]
+#[non_exhaustive]
+pub struct Rules {
+    /// Two, three or four players, taking turns in the order White, Red, Blue, Green.
+    pub players: u8,
+    /// Where the neutral stones start.
+    pub layout: Layout,
+    /// How many neutral stones start on each square of the layout.
+    pub neutrals: u8,
+    /// How many stones each player has to place; the game is drawn once the player to move has
+    /// none left.
+    pub stones_per_player: u8,
+    /// How many squares in a row a player must top to win.
+    pub line_length: u8,
+    /// Whether diagonal lines win as well as rows and columns.
+    pub diagonals: bool,
+    /// Whether a path may step straight back to the square it just left.
+    pub u_turns: bool,
+    /// Whether stones may be distributed diagonally as well as along rows and columns.
+    pub diagonal_steps: bool,
+    /// Who wins when a move leaves more than one player topping a line.
+    pub simultaneous: SimultaneousLines,
+    /// The shape of the board.
+    pub topology: Topology,
+    /// Whether lines on a torus may wrap around its edges.
+    pub wrapped_lines: bool,
+}
+
+const PRESETS: [&str; 9] = ["standard", "no-diagonals", "u-turns", "centre", "three", "torus", "eight-way", "three-player", "four-player"];
+
+impl Rules {
+    /// The rules of the original game, the "standard" preset.
+    pub const fn standard() -> Rules {
+        Rules {
+            players: 2,
+            layout: Layout::Corners,
+            neutrals: 2,
+            stones_per_player: STONES_PER_PLAYER,
+            line_length: 4,
+            diagonals: true,
+            u_turns: false,
+            diagonal_steps: false,
+            simultaneous: SimultaneousLines::FirstFound,
+            topology: Topology::Flat,
+            wrapped_lines: false,
+        }
+    }
+
+    /// The names of the presets.
+    pub fn presets() -> &'static [&'static str] {
+        &PRESETS
+    }
+
+    /// The rules of the named preset, if there is one.
+    pub fn preset(name: &str) -> Option<Rules> {
+        let standard = Rules::standard();
+        match name {
+            "standard" => Some(standard),
+            "no-diagonals" => Some(Rules{diagonals: false, ..standard}),
+            "u-turns" => Some(Rules{u_turns: true, ..standard}),
+            "centre" => Some(Rules{layout: Layout::Centre, ..standard}),
+            // Three in a row wins, with fewer stones to keep the game short.
+            "three" => Some(Rules{line_length: 3, stones_per_player: 6, ..standard}),
+            "torus" => Some(Rules{topology: Topology::Torus, wrapped_lines: true, ..standard}),
+            "eight-way" => Some(Rules{diagonal_steps: true, ..standard}),
+            // Fewer stones each, so that every stone in the game still fits in one pile.
+            "three-player" => Some(Rules{players: 3, stones_per_player: 5, ..standard}),
+            "four-player" => Some(Rules{players: 4, stones_per_player: 4, ..standard}),
+            _ => None,
+        }
+    }
+
+    /// Checks the rules can be played on an N by N board.
+    pub fn validate<const N: usize>(&self) -> Result<(), Error> {
+        if self.players < 2 || self.players as usize > MAX_PLAYERS {
+            return Err(Error::Invalid(format!("there must be from 2 to {MAX_PLAYERS} players")));
+        }
+        if self.line_length < 2 || self.line_length as usize > N {
+            return Err(Error::Invalid(format!("line length must be from 2 to {N}")));
+        }
+        if self.neutrals == 0 {
+            return Err(Error::Invalid("there must be at least one neutral stone per pile".to_string()));
+        }
+        if self.wrapped_lines && self.topology != Topology::Torus {
+            return Err(Error::Invalid("only lines on a torus can wrap".to_string()));
+        }
+        if self.stones_per_player == 0 {
+            return Err(Error::Invalid("players need at least one stone".to_string()));
+        }
+        let total = 4 * self.neutrals as usize + self.players as usize * self.stones_per_player as usize;
+        if total > PILE_CAPACITY {
+            return Err(Error::Invalid(format!("{total} stones is more than the {PILE_CAPACITY} a pile can hold")));
+        }
+        Ok(())
+    }
+
+    /// Whether rotating or reflecting the board keeps the result of every position. Under
+    /// SimultaneousLines::FirstFound it needn't: when two players top lines, which is found first
+    /// depends on which way round the board is.
+    pub fn symmetric(&self) -> bool {
+        self.simultaneous != SimultaneousLines::FirstFound
+    }
+
+    /// The lines that win the game when topped by one player.
+    pub fn winning_lines<const N: usize>(&self) -> impl Iterator<Item = Line<N>> {
+        lines(self.line_length as usize, self.diagonals, self.wrapped_lines)
+    }
+
+    /// The outcome decided by the lines on the board, if any, after `mover` made the last move.
+    pub fn line_outcome<const N: usize>(&self, top: &BoardTop<N>, mover: PlayerColor) -> Option<Outcome> {
+        if self.simultaneous == SimultaneousLines::FirstFound {
+            return top.line_winner(self, None).map(Outcome::Win);
+        }
+        // The players topping a line, in turn order from the mover.
+        let mut topping = std::iter::successors(Some(mover), |p| Some(p.next(self.players)))
+            .take(self.players as usize)
+            .filter(|&p| top.line_winner(self, Some(p)).is_some());
+        match (topping.next(), topping.next()) {
+            (None, _) => None,
+            (Some(p), None) => Some(Outcome::Win(p)),
+            (Some(first), Some(second)) => match self.simultaneous {
+                SimultaneousLines::Opponent if first == mover => Some(Outcome::Win(second)),
+                SimultaneousLines::Draw => Some(Outcome::Draw),
+                _ => Some(Outcome::Win(first)),
+            },
+        }
+    }
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules::standard()
+    }
+}
+
+impl fmt::Display for Rules {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(name) = PRESETS.iter().find(|&&name| Rules::preset(name) == Some(*self)) {
+            return write!(f, "{name}");
+        }
+        let yes_no = |b: bool| if b { "yes" } else { "no" };
+        let topology = match self.topology {
+            Topology::Flat => "flat",
+            Topology::Torus => "torus",
+        };
+        write!(f, "standard:players={},layout={},neutrals={},stones={},line={},diagonals={},u-turns={},diagonal-steps={},simultaneous={},topology={topology},wrapped-lines={}",
+            self.players, self.layout.name(), self.neutrals, self.stones_per_player, self.line_length, yes_no(self.diagonals),
+            yes_no(self.u_turns), yes_no(self.diagonal_steps), self.simultaneous.name(), yes_no(self.wrapped_lines))
+    }
+}
+
+impl FromStr for Rules {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, options) = s.split_once(':').unwrap_or((s, ""));
+        let mut rules = Rules::preset(name)
+            .ok_or_else(|| Error::Parse(format!("unknown rules {name:?}, expected one of {}", PRESETS.join(", "))))?;
+        for option in options.split(',').filter(|o| !o.is_empty()) {
+            let (key, value) = option.split_once('=').ok_or_else(|| Error::Parse(format!("invalid rules option {option:?}")))?;
+            let invalid = || Error::Parse(format!("invalid value {value:?} for rules option {key}"));
+            let number = || value.parse::<u8>().map_err(|_| invalid());
+            let flag = || match value {
+                "yes" => Ok(true),
+                "no" => Ok(false),
+                _ => Err(invalid()),
+            };
+            match key {
+                "players" => rules.players = number()?,
+                "layout" => rules.layout = [Layout::Corners, Layout::Centre].into_iter()
+                    .find(|l| l.name() == value).ok_or_else(invalid)?,
+                "neutrals" => rules.neutrals = number()?,
+                "stones" => rules.stones_per_player = number()?,
+                "line" => rules.line_length = number()?,
+                "diagonals" => rules.diagonals = flag()?,
+                "u-turns" => rules.u_turns = flag()?,
+                "diagonal-steps" => rules.diagonal_steps = flag()?,
+                "simultaneous" => rules.simultaneous = [SimultaneousLines::FirstFound, SimultaneousLines::Mover, SimultaneousLines::Opponent, SimultaneousLines::Draw]
+                    .into_iter().find(|r| r.name() == value).ok_or_else(invalid)?,
+                "topology" => rules.topology = match value {
+                    "flat" => Topology::Flat,
+                    "torus" => Topology::Torus,
+                    _ => return Err(invalid()),
+                },
+                "wrapped-lines" => rules.wrapped_lines = flag()?,
+                _ => return Err(Error::Parse(format!("unknown rules option {key:?}"))),
+            }
+        }
+        Ok(rules)
+    }
+}
@@ -0,0 +1,466 @@
+//! The alpha-beta search for the best move in a two player game.
+use crate::evaluator::Evaluator;
+use crate::game_state::{GameState, Move, Outcome, PlayerColor, MAX_PLAYERS};
+use crate::path::Path;
+use crate::position::NUM_PILES;
+use crate::tactics::{consequence, Consequence};
+use crate::zobrist::state_hash;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+
+/// Scores are from the point of view of the player to move. A won game scores WIN_SCORE less the
+/// number of plies it takes to reach, so quicker wins are preferred and slower losses resisted.
+pub const WIN_SCORE: i32 = 10_000;
+
+// Evaluator values in [-1, 1] are scaled to at most this magnitude, well clear of WIN_SCORE.
+const EVAL_SCALE: f32 = 1000.0;
+
+/// Whether the score is for a forced win or loss rather than an estimate.
+pub fn is_win_score(score: i32) -> bool {
+    score.abs() > WIN_SCORE - 100
+}
+
+/// SearchResult is the best move found, with its score and the line expected to follow.
+#[derive(Clone, Debug)]
+pub struct SearchResult {
+    /// The best move, or None if the game is over.
+    pub best_move: Option<Move>,
+    /// The score for the player to move.
+    pub score: i32,
+    /// The expected line of play, starting with the best move. It stops short of the search's
+    /// depth where the transposition table no longer has the rest.
+    pub pv: Vec<Move>,
+    /// The deepest search that finished.
+    pub depth: u8,
+    /// The positions searched.
+    pub nodes: u64,
+}
+
+/// PvLine is one of the top moves from a multi-PV search, with its score and expected continuation.
+#[derive(Clone, Debug)]
+pub struct PvLine {
+    /// The score of the line's first move, for the player to move.
+    pub score: i32,
+    /// The line's first move, then the moves expected in reply.
+    pub moves: Vec<Move>,
+}
+
+impl PvLine {
+    /// The line in compact notation, optionally followed by the arrow diagram of its first move.
+    pub fn display(&self, diagram: bool) -> String {
+        let moves: Vec<String> = self.moves.iter().map(|mv| mv.to_string()).collect();
+        let mut out = format!("{:>6} {}", self.score, moves.join(" "));
+        if let (true, Some(first)) = (diagram, self.moves.first()) {
+            out += &format!("\n{}", first.path);
+        }
+        out
+    }
+}
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+enum Bound {
+    Exact,
+    // The score is at least this much; the search failed high.
+    Lower,
+    // The score is at most this much; the search failed low.
+    Upper,
+}
+
+/// The number of transposition table buckets, of two entries each, unless set with
+/// Search::with_table_size.
+pub const DEFAULT_TABLE_SIZE: usize = 1 << 17;
+
+#[derive(Copy, Clone, Debug)]
+struct TableEntry {
+    key: u64,
+    // The search that stored the entry, counted by the table.
+    generation: u8,
+    depth: u8,
+    score: i32,
+    bound: Bound,
+    best: Option<Path>,
+}
+
+// TranspositionTable is a fixed number of buckets indexed by position hash, each with two slots.
+// A new entry goes in the first slot if that holds one from an earlier search or one searched no
+// deeper, and otherwise in the second, so the deep entries along the principal variation survive
+// the many shallow ones.
+struct TranspositionTable {
+    buckets: Vec<[Option<TableEntry>; 2]>,
+    size: usize,
+    generation: u8,
+}
+
+impl TranspositionTable {
+    fn new(size: usize) -> Self {
+        // The slots are only allocated once something is stored, so searches that never get that far
+        // cost nothing.
+        TranspositionTable{buckets: Vec::new(), size: size.max(1).next_power_of_two(), generation: 0}
+    }
+
+    fn bucket(&self, key: u64) -> usize {
+        key as usize & (self.size - 1)
+    }
+
+    fn get(&self, key: u64) -> Option<&TableEntry> {
+        self.buckets.get(self.bucket(key))?.iter().flatten().find(|e| e.key == key)
+    }
+
+    fn insert(&mut self, mut entry: TableEntry) {
+        if self.buckets.is_empty() {
+            self.buckets = vec![[None; 2]; self.size];
+        }
+        entry.generation = self.generation;
+        let bucket = self.bucket(entry.key);
+        let [deep, recent] = &mut self.buckets[bucket];
+        let keep_deep = deep.is_some_and(|old| old.generation == entry.generation && old.depth > entry.depth);
+        if !keep_deep {
+            *deep = Some(entry);
+        } else {
+            *recent = Some(entry);
+        }
+        // A position only keeps its latest entry.
+        if recent.is_some_and(|old| old.key == entry.key) && !keep_deep {
+            *recent = None;
+        }
+    }
+
+    fn new_search(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+    }
+}
+
+/// Search is a fixed-depth negamax search with alpha-beta pruning. Unless turned off, it keeps a
+/// transposition table of positions already searched, and orders the moves to try those most
+/// likely to cause a cutoff first: immediate wins, the table's best move, killer moves, moves with
+/// a good history, and last of all moves that uncover the opponent's stones.
+pub struct Search<'a> {
+    evaluator: &'a dyn Evaluator,
+    nodes: u64,
+    ordering: bool,
+    table: Option<TranspositionTable>,
+    // Two quiet moves per ply that recently caused a cutoff.
+    killers: Vec<[Option<Path>; 2]>,
+    // Cutoffs caused by each start and end square pair, per player, weighted by depth.
+    history: [[u32; NUM_PILES * NUM_PILES]; MAX_PLAYERS],
+    deadline: Option<Instant>,
+    // Set from another thread to abandon the search, as if the deadline had passed.
+    stop: Option<&'a AtomicBool>,
+    aborted: bool,
+}
+
+impl<'a> Search<'a> {
+    /// A search with the evaluator, move ordering and a table of DEFAULT_TABLE_SIZE entries.
+    pub fn new(evaluator: &'a dyn Evaluator) -> Self {
+        Search {
+            evaluator,
+            nodes: 0,
+            ordering: true,
+            table: Some(TranspositionTable::new(DEFAULT_TABLE_SIZE)),
+            killers: Vec::new(),
+            history: [[0; NUM_PILES * NUM_PILES]; MAX_PLAYERS],
+            deadline: None,
+            stop: None,
+            aborted: false,
+        }
+    }
+
+    /// Stops iterative deepening searches once the deadline passes.
+    pub fn with_deadline(mut self, deadline: Option<Instant>) -> Self {
+        self.deadline = deadline;
+        self
+    }
+
+    /// Stops iterative deepening searches once the flag is set, e.g. from another thread.
+    pub fn with_stop(mut self, stop: &'a AtomicBool) -> Self {
+        self.stop = Some(stop);
+        self
+    }
+
+    /// Turns move ordering on or off, to measure what it saves.
+    pub fn with_ordering(mut self, ordering: bool) -> Self {
+        self.ordering = ordering;
+        self
+    }
+
+    /// Sets the number of transposition table buckets, rounded up to a power of two, or turns the
+    /// table off with 0.
+    pub fn with_table_size(mut self, entries: usize) -> Self {
+        self.table = (entries > 0).then(|| TranspositionTable::new(entries));
+        self
+    }
+
+    /// Searches `depth` plies ahead. A depth of 0 just evaluates the state, without picking a move.
+    pub fn search(&mut self, state: &GameState, depth: u8) -> SearchResult {
+        self.nodes = 0;
+        self.new_search();
+        if depth == 0 {
+            return SearchResult{best_move: None, score: self.leaf_score(state, 0), pv: Vec::new(), depth, nodes: 1};
+        }
+        let (score, best_move) = self.negamax(state, depth, 0, -WIN_SCORE - 1, WIN_SCORE + 1);
+        let pv = match best_move {
+            Some(mv) => self.principal_variation(state, mv, depth),
+            None => Vec::new(),
+        };
+        SearchResult{best_move, score, pv, depth, nodes: self.nodes}
+    }
+
+    /// Searches to increasing depths up to `max_depth`, returning the deepest search that finished
+    /// before the deadline. The first ply always finishes, so there is a move if the game isn't over.
+    pub fn search_iterative(&mut self, state: &GameState, max_depth: u8) -> SearchResult {
+        let deadline = self.deadline.take();
+        let mut result = self.search(state, 1.min(max_depth));
+        let mut nodes = result.nodes;
+        self.deadline = deadline;
+        for depth in 2..=max_depth {
+            if self.deadline.is_some_and(|d| Instant::now() >= d) || is_win_score(result.score) {
+                break;
+            }
+            let deeper = self.search(state, depth);
+            nodes += deeper.nodes;
+            if self.aborted {
+                self.aborted = false;
+                break;
+            }
+            result = deeper;
+        }
+        result.nodes = nodes;
+        result
+    }
+
+    /// Searches every move to `depth` with a full window, returning the best `count` lines, best first.
+    /// If the deadline passes or the search is stopped, the lines are unfinished: see aborted().
+    pub fn search_multipv(&mut self, state: &GameState, depth: u8, count: usize) -> Vec<PvLine> {
+        self.nodes = 0;
+        self.new_search();
+        self.aborted = false;
+        let depth = depth.max(1);
+        let mut lines: Vec<PvLine> = self.ordered_moves(state, 0, None).into_iter().map(|(mv, child, _)| {
+            let score = -self.negamax(&child, depth - 1, 1, -WIN_SCORE - 1, WIN_SCORE + 1).0;
+            PvLine{score, moves: self.principal_variation(state, mv, depth)}
+        }).collect();
+        lines.sort_by_key(|line| -line.score);
+        lines.truncate(count);
+        lines
+    }
+
+    /// The positions searched so far.
+    pub fn nodes(&self) -> u64 {
+        self.nodes
+    }
+
+    /// Whether the last multi-PV search was cut short.
+    pub fn aborted(&self) -> bool {
+        self.aborted
+    }
+
+    // Follows the table's best moves after the first, for at most `depth` moves in all.
+    fn principal_variation(&self, state: &GameState, first: Move, depth: u8) -> Vec<Move> {
+        let mut pv = vec![first];
+        let mut state = *state;
+        state.apply_move(&first);
+        while pv.len() < depth as usize && !state.is_over() {
+            let Some(path) = self.table_entry(state_hash(&state)).and_then(|e| e.best) else { break };
+            let mv = Move{new_stone: state.player_to_move().stone_color(), path};
+            if !state.legal_moves().contains(&mv) {
+                break; // A hash collision.
+            }
+            state.apply_move(&mv);
+            pv.push(mv);
+        }
+        pv
+    }
+
+    fn negamax(&mut self, state: &GameState, depth: u8, ply: i32, mut alpha: i32, beta: i32) -> (i32, Option<Move>) {
+        self.nodes += 1;
+        if self.nodes.is_multiple_of(1024) && (self.deadline.is_some_and(|d| Instant::now() >= d) || self.stop.is_some_and(|s| s.load(Ordering::Relaxed))) {
+            self.aborted = true;
+        }
+        if self.aborted {
+            return (0, None);
+        }
+        if depth == 0 || state.is_over() {
+            return (self.leaf_score(state, ply), None);
+        }
+        let key = state_hash(state);
+        let mut table_move = None;
+        if let Some(entry) = self.table_entry(key) {
+            table_move = entry.best;
+            let score = from_table(entry.score, ply);
+            let usable = match entry.bound {
+                Bound::Exact => true,
+                Bound::Lower => score >= beta,
+                Bound::Upper => score <= alpha,
+            };
+            // The root always searches, so it has a move to return.
+            if entry.depth >= depth && usable && ply > 0 {
+                return (score, None);
+            }
+        }
+
+        let moves = self.ordered_moves(state, ply, table_move);
+        // Nothing can beat winning straight away, so there's no need to search further.
+        if let Some((mv, child, _)) = moves.first() {
+            if consequence(child, state.player_to_move()) == Consequence::Wins {
+                let score = WIN_SCORE - ply - 1;
+                self.store(TableEntry{key, generation: 0, depth, score: to_table(score, ply), bound: Bound::Exact, best: Some(mv.path)});
+                return (score, Some(*mv));
+            }
+        }
+
+        let original_alpha = alpha;
+        let mut best = (-WIN_SCORE - 1, None);
+        for (mv, child, quiet) in moves {
+            let (score, _) = self.negamax(&child, depth - 1, ply + 1, -beta, -alpha);
+            let score = -score;
+            if self.aborted {
+                return (0, None);
+            }
+            if score > best.0 {
+                best = (score, Some(mv));
+            }
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                if self.ordering && quiet {
+                    self.record_cutoff(state.player_to_move(), &mv, depth, ply);
+                }
+                break;
+            }
+        }
+
+        let bound = if best.0 <= original_alpha {
+            Bound::Upper
+        } else if best.0 >= beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        self.store(TableEntry{key, generation: 0, depth, score: to_table(best.0, ply), bound, best: best.1.map(|mv| mv.path)});
+        best
+    }
+
+    fn new_search(&mut self) {
+        if let Some(table) = &mut self.table {
+            table.new_search();
+        }
+    }
+
+    fn table_entry(&self, key: u64) -> Option<&TableEntry> {
+        self.table.as_ref()?.get(key)
+    }
+
+    fn store(&mut self, entry: TableEntry) {
+        if let Some(table) = &mut self.table {
+            table.insert(entry);
+        }
+    }
+
+    // The legal moves with the states they lead to, in the order to search them, each flagged as
+    // quiet if it doesn't end the game.
+    fn ordered_moves(&self, state: &GameState, ply: i32, table_move: Option<Path>) -> Vec<(Move, GameState, bool)> {
+        let me = state.player_to_move();
+        let mut moves: Vec<_> = state.legal_moves().into_iter().map(|mv| {
+            let mut child = *state;
+            child.apply_move(&mv);
+            (mv, child)
+        }).collect();
+        if !self.ordering {
+            return moves.into_iter().map(|(mv, child)| (mv, child, !child.is_over())).collect();
+        }
+
+        let killers = self.killers.get(ply as usize).copied().unwrap_or_default();
+        let opponent_tops = |s: &GameState| count_tops(s, state.next_player());
+        let tops_before = opponent_tops(state);
+        let rank = |(mv, child): &(Move, GameState)| {
+            let tier = match consequence(child, me) {
+                Consequence::Wins => 4,
+                Consequence::HandsLine => 0,
+                _ if Some(mv.path) == table_move => 3,
+                _ if killers.contains(&Some(mv.path)) => 2,
+                _ => 1,
+            };
+            let uncovered = opponent_tops(child).saturating_sub(tops_before);
+            (tier, self.history[me as usize][history_index(mv)], -(uncovered as i32))
+        };
+        moves.sort_by_cached_key(|m| std::cmp::Reverse(rank(m)));
+        moves.into_iter().map(|(mv, child)| (mv, child, !child.is_over())).collect()
+    }
+
+    fn record_cutoff(&mut self, player: PlayerColor, mv: &Move, depth: u8, ply: i32) {
+        let ply = ply as usize;
+        if self.killers.len() <= ply {
+            self.killers.resize(ply + 1, [None, None]);
+        }
+        let killers = &mut self.killers[ply];
+        if killers[0] != Some(mv.path) {
+            killers[1] = killers[0];
+            killers[0] = Some(mv.path);
+        }
+        let history = &mut self.history[player as usize][history_index(mv)];
+        *history = history.saturating_add(depth as u32 * depth as u32);
+    }
+
+    fn leaf_score(&self, state: &GameState, ply: i32) -> i32 {
+        match state.outcome() {
+            Some(outcome) => terminal_score(state, outcome, ply),
+            None => (self.evaluator.value(state) * EVAL_SCALE) as i32,
+        }
+    }
+}
+
+/// The score of a finished game for the player to move in `state`, reached `ply` plies from the
+/// search's root.
+pub fn terminal_score(state: &GameState, outcome: Outcome, ply: i32) -> i32 {
+    match outcome {
+        Outcome::Draw => 0,
+        Outcome::Win(p) if p == state.player_to_move() => WIN_SCORE - ply,
+        Outcome::Win(_) => -(WIN_SCORE - ply),
+    }
+}
+
+// Win scores count plies from the root, but the table stores them counting from the entry's own
+// position so they stay correct when it is reached at a different ply.
+fn to_table(score: i32, ply: i32) -> i32 {
+    if !is_win_score(score) { score } else if score > 0 { score + ply } else { score - ply }
+}
+
+fn from_table(score: i32, ply: i32) -> i32 {
+    if !is_win_score(score) { score } else if score > 0 { score - ply } else { score + ply }
+}
+
+fn history_index(mv: &Move) -> usize {
+    mv.path.start().to_index() * NUM_PILES + mv.path.end().to_index()
+}
+
+fn count_tops(state: &GameState, player: PlayerColor) -> usize {
+    let top = player.pile_top();
+    state.board().top().iter_piles().filter(|(_, &t)| t == top).count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::LineEvaluator;
+    use crate::rules::Rules;
+
+    #[test]
+    fn multipv_lines() {
+        let states = [
+            GameState::starting_state(),
+            GameState::parse_position("W,W,,W/,WW,,/nn,,,nn/nn,,,nn R", Rules::standard()).unwrap(),
+        ];
+        for state in states {
+            let result = Search::new(&LineEvaluator).search(&state, 3);
+            let lines = Search::new(&LineEvaluator).search_multipv(&state, 3, 5);
+            assert_eq!(lines.len(), 5);
+            assert!(lines.windows(2).all(|pair| pair[0].score >= pair[1].score));
+            assert_eq!(lines[0].score, result.score);
+            // Moves of equal score may come in either order.
+            let best: Vec<Move> = lines.iter().filter(|line| line.score == result.score).map(|line| line.moves[0]).collect();
+            assert!(best.contains(&result.best_move.unwrap()));
+            let firsts: Vec<Move> = lines.iter().map(|line| line.moves[0]).collect();
+            assert!(firsts.iter().enumerate().all(|(i, mv)| !firsts[..i].contains(mv)));
+            assert!(lines.iter().all(|line| (1..=3).contains(&line.moves.len())));
+        }
+    }
+}
@@ -0,0 +1,95 @@
+//! Self-play games between MCTS agents, producing training data for the network.
+use crate::evaluator::Evaluator;
+use crate::game_state::{GameState, Move, Outcome};
+use crate::mcts::Mcts;
+use crate::nn::TrainingSample;
+use crate::record::GameRecord;
+use rand::rngs::StdRng;
+use rand::Rng;
+use rand::SeedableRng;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+/// SelfPlayConfig is the games to play and how hard to search in them.
+#[derive(Clone, Debug)]
+pub struct SelfPlayConfig {
+    /// The games to play.
+    pub games: u32,
+    /// MCTS iterations per move.
+    pub iterations: u32,
+    /// Moves are sampled in proportion to their visits for this many plies, then the most visited
+    /// is played, so games vary without throwing away the search later on.
+    pub sampling_plies: u8,
+    /// The threads to play the games on.
+    pub threads: usize,
+    /// The seed for the first game; each game after uses the next.
+    pub seed: u64,
+}
+
+/// Plays a game of MCTS against itself, returning its record and a training sample for every
+/// position: the search's visit distribution and the game's result for the player to move.
+pub fn play_selfplay_game(evaluator: &dyn Evaluator, config: &SelfPlayConfig, rng: &mut impl Rng) -> (GameRecord, Vec<TrainingSample>) {
+    let name = format!("mcts-{}", config.iterations);
+    let mut record = GameRecord::new(name.clone(), name);
+    let mut state = GameState::starting_state();
+    let mut positions: Vec<(GameState, Vec<(Move, f32)>)> = Vec::new();
+    while !state.is_over() {
+        let result = Mcts::new(evaluator).search(&state, config.iterations);
+        let total: u32 = result.visits.iter().map(|&(_, n)| n).sum();
+        let policy: Vec<(Move, f32)> = result.visits.iter().map(|&(mv, n)| (mv, n as f32 / total.max(1) as f32)).collect();
+        let mut mv = result.best_move.expect("game should not be over");
+        if record.moves.len() < config.sampling_plies as usize && total > 0 {
+            let mut choice = rng.gen_range(0..total);
+            for &(candidate, n) in &result.visits {
+                if choice < n {
+                    mv = candidate;
+                    break;
+                }
+                choice -= n;
+            }
+        }
+        positions.push((state, policy));
+        state.apply_move(&mv);
+        record.moves.push(mv);
+    }
+    record.outcome = state.outcome();
+    let samples = positions.into_iter().map(|(state, policy)| {
+        let value = match record.outcome {
+            Some(Outcome::Win(p)) if p == state.player_to_move() => 1.0,
+            Some(Outcome::Win(_)) => -1.0,
+            _ => 0.0,
+        };
+        TrainingSample{state, value, policy}
+    }).collect();
+    (record, samples)
+}
+
+/// Plays the games across `threads` threads, calling `progress` with each finished game's record.
+pub fn run_selfplay(config: &SelfPlayConfig, evaluator: &(dyn Evaluator + Sync), mut progress: impl FnMut(&GameRecord)) -> Vec<TrainingSample> {
+    let next_game = AtomicU32::new(0);
+    let (sender, receiver) = mpsc::channel();
+    let mut samples = Vec::new();
+    thread::scope(|scope| {
+        for _ in 0..config.threads.max(1) {
+            let sender = sender.clone();
+            let next_game = &next_game;
+            scope.spawn(move || loop {
+                let game = next_game.fetch_add(1, Ordering::Relaxed);
+                if game >= config.games {
+                    break;
+                }
+                let mut rng = StdRng::seed_from_u64(config.seed.wrapping_add(game as u64));
+                if sender.send(play_selfplay_game(evaluator, config, &mut rng)).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+        for (record, game_samples) in receiver {
+            progress(&record);
+            samples.extend(game_samples);
+        }
+    });
+    samples
+}
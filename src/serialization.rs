@@ -0,0 +1,147 @@
+// Serialize and Deserialize for the core types, each as the string it is written as elsewhere:
+//   Stone, PlayerColor  its letter, e.g. "W"
+//   Direction           its move notation letter, e.g. "r"
+//   Position            its square name, e.g. "a1"
+//   StonePile           its stones from bottom to top, e.g. "nnW"
+//   Board               the board part of a position string, e.g. "nn,,,nn/,,,/,,,/nn,,,nn"
+//   Path                its compact notation, e.g. "a1rrr", after "torus:" on a torus
+//   Move                its path followed by the stone placed, e.g. "a1rrr W"
+use crate::board::Board;
+use crate::error::Error;
+use crate::game_state::{Move, PlayerColor};
+use crate::path::Path;
+use crate::position::{Direction, Position, Topology};
+use crate::stone::Stone;
+use crate::stone_pile::StonePile;
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+
+const TORUS_PREFIX: &str = "torus:";
+
+fn deserialize_with<'de, D: Deserializer<'de>, T>(deserializer: D, parse: impl FnOnce(&str) -> Result<T, Error>) -> Result<T, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    parse(&text).map_err(de::Error::custom)
+}
+
+// A single character, as the letters are written.
+fn letter(text: &str, what: &str) -> Result<char, Error> {
+    let mut chars = text.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(c),
+        _ => Err(Error::Parse(format!("invalid {what} {text:?}"))),
+    }
+}
+
+impl Serialize for Stone {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Stone {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_with(deserializer, |text| Stone::from_letter(letter(text, "stone")?).ok_or_else(|| Error::Parse(format!("unknown stone {text:?}"))))
+    }
+}
+
+impl Serialize for PlayerColor {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_char(self.letter())
+    }
+}
+
+impl<'de> Deserialize<'de> for PlayerColor {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_with(deserializer, |text| PlayerColor::from_letter(letter(text, "player")?).ok_or_else(|| Error::Parse(format!("unknown player {text:?}"))))
+    }
+}
+
+impl Serialize for Direction {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_char(self.letter())
+    }
+}
+
+impl<'de> Deserialize<'de> for Direction {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_with(deserializer, |text| Direction::from_letter(letter(text, "direction")?).ok_or_else(|| Error::Parse(format!("unknown direction {text:?}"))))
+    }
+}
+
+impl<const N: usize> Serialize for Position<N> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.name())
+    }
+}
+
+impl<'de, const N: usize> Deserialize<'de> for Position<N> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_with(deserializer, Position::parse)
+    }
+}
+
+impl Serialize for StonePile {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.iter().map(|s| s.to_string()).collect::<String>())
+    }
+}
+
+impl<'de> Deserialize<'de> for StonePile {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_with(deserializer, StonePile::parse)
+    }
+}
+
+impl<const N: usize> Serialize for Board<N> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.position_string())
+    }
+}
+
+impl<'de, const N: usize> Deserialize<'de> for Board<N> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_with(deserializer, Board::parse_position)
+    }
+}
+
+fn path_string<const N: usize>(path: &Path<N>) -> String {
+    match path.topology() {
+        Topology::Flat => path.notation(),
+        Topology::Torus => format!("{TORUS_PREFIX}{}", path.notation()),
+    }
+}
+
+fn parse_path<const N: usize>(text: &str) -> Result<Path<N>, Error> {
+    match text.strip_prefix(TORUS_PREFIX) {
+        Some(notation) => Path::parse(notation, Topology::Torus),
+        None => Path::parse(text, Topology::Flat),
+    }
+}
+
+impl<const N: usize> Serialize for Path<N> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&path_string(self))
+    }
+}
+
+impl<'de, const N: usize> Deserialize<'de> for Path<N> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_with(deserializer, parse_path)
+    }
+}
+
+impl<const N: usize> Serialize for Move<N> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("{} {}", path_string(&self.path), self.new_stone))
+    }
+}
+
+impl<'de, const N: usize> Deserialize<'de> for Move<N> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_with(deserializer, |text| {
+            let (path, stone) = text.split_once(' ').ok_or_else(|| Error::Parse(format!("invalid move {text:?}, expected a path and a stone")))?;
+            let new_stone = Stone::from_letter(letter(stone, "stone")?).ok_or_else(|| Error::Parse(format!("unknown stone {stone:?}")))?;
+            Ok(Move{new_stone, path: parse_path(path)?})
+        })
+    }
+}
@@ -0,0 +1,272 @@
+//! An HTTP server answering questions about positions in JSON, built with the `server` feature.
+use crate::error::Error;
+use crate::evaluator::Evaluator;
+use crate::game_state::{GameState, Move, Outcome};
+use crate::multiplayer::{MultiSearch, Strategy};
+use crate::rules::Rules;
+use crate::search::Search;
+use crate::tactics::Threats;
+use serde_json::{json, Value};
+use std::io::{self, Read};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver, TrySendError};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+// Request bodies are positions and a few options, so anything much bigger is a mistake.
+const MAX_BODY: u64 = 64 * 1024;
+
+/// ServerConfig is where the server listens and how much work it takes on.
+#[derive(Clone, Debug)]
+pub struct ServerConfig {
+    /// The address to listen on, e.g. "127.0.0.1:8080"; port 0 picks a free port.
+    pub address: String,
+    /// How many requests are handled at once.
+    pub threads: usize,
+    /// Requests arriving while this many are already waiting are turned away with a 503.
+    pub max_queued: usize,
+    /// The longest a request may take from arriving to being answered, including any search.
+    pub timeout: Duration,
+    /// The deepest search a request may ask for.
+    pub max_depth: u8,
+}
+
+/// Serves the JSON API until the process is killed. Every endpoint takes a POST of a JSON object
+/// giving the position, in the same way as the command line:
+///   {"rules": "standard", "position": "nn,,,nn/,,,/,,,/nn,,,nn W", "moves": ["a1rrr"]}
+/// all of which are optional, along with the endpoint's own options:
+///   /legal-moves  the position and its legal moves
+///   /apply        "move": the position after it
+///   /bestmove     "depth", "time_ms", "strategy": the best move found
+///   /analyze      "depth", "multipv", "time_ms": the top lines and the threats in the position
+pub fn serve(config: &ServerConfig, evaluator: &(dyn Evaluator + Sync)) -> Result<(), Error> {
+    let server = Server::http(&config.address).map_err(|e| Error::io(&config.address)(io::Error::other(e)))?;
+    // The address as bound, with the port chosen if it was given as 0.
+    match server.server_addr().to_ip() {
+        Some(address) => println!("listening on http://{address}"),
+        None => println!("listening on http://{}", config.address),
+    }
+    let (sender, receiver) = mpsc::sync_channel::<(Instant, Request)>(config.max_queued);
+    let receiver = Mutex::new(receiver);
+    thread::scope(|scope| {
+        for _ in 0..config.threads.max(1) {
+            let receiver = &receiver;
+            scope.spawn(move || worker(receiver, config, evaluator));
+        }
+        for request in server.incoming_requests() {
+            if let Err(TrySendError::Full((_, request)) | TrySendError::Disconnected((_, request))) = sender.try_send((Instant::now(), request)) {
+                respond(request, 503, json!({"error": "the server is busy"}));
+            }
+        }
+    });
+    Ok(())
+}
+
+fn worker(receiver: &Mutex<Receiver<(Instant, Request)>>, config: &ServerConfig, evaluator: &(dyn Evaluator + Sync)) {
+    loop {
+        let next = receiver.lock().expect("no worker should panic holding the queue").recv();
+        let Ok((arrived, mut request)) = next else { return };
+        let deadline = arrived + config.timeout;
+        if Instant::now() >= deadline {
+            respond(request, 503, json!({"error": "timed out waiting for a free worker"}));
+            continue;
+        }
+        // A bug that panics on one request shouldn't take the worker down with it.
+        let handled = panic::catch_unwind(AssertUnwindSafe(|| handle(&mut request, config, evaluator, deadline)));
+        let (status, body) = match handled {
+            Ok(Ok(body)) => (200, body),
+            Ok(Err((status, e))) => (status, json!({"error": e})),
+            Err(_) => (500, json!({"error": "internal error"})),
+        };
+        respond(request, status, body);
+    }
+}
+
+fn respond(request: Request, status: u16, body: Value) {
+    let header = Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).expect("header should be valid");
+    // The client may have gone away, and there is no one else to tell.
+    let _ = request.respond(Response::from_string(body.to_string()).with_status_code(status).with_header(header));
+}
+
+fn handle(request: &mut Request, config: &ServerConfig, evaluator: &dyn Evaluator, deadline: Instant) -> Result<Value, (u16, String)> {
+    let path = request.url().split('?').next().unwrap_or_default().to_string();
+    if !["/legal-moves", "/apply", "/bestmove", "/analyze"].contains(&path.as_str()) {
+        return Err((404, format!("unknown endpoint {path}")));
+    }
+    if *request.method() != Method::Post {
+        return Err((405, format!("{path} only takes POST requests")));
+    }
+    let mut text = String::new();
+    request.as_reader().take(MAX_BODY + 1).read_to_string(&mut text).map_err(|e| (400, e.to_string()))?;
+    if text.len() as u64 > MAX_BODY {
+        return Err((413, format!("requests must be at most {MAX_BODY} bytes")));
+    }
+    let body: Value = serde_json::from_str(&text).map_err(|e| (400, format!("invalid JSON: {e}")))?;
+    let bad_request = |e: String| (400, e);
+    let state = state_from_json(&body).map_err(bad_request)?;
+    let options = Options{body: &body, config, deadline};
+    match path.as_str() {
+        "/legal-moves" => Ok(legal_moves(&state)),
+        "/apply" => apply(state, &options).map_err(bad_request),
+        "/bestmove" => best_move(&state, &options, evaluator).map_err(bad_request),
+        _ => analyze(&state, &options, evaluator).map_err(bad_request),
+    }
+}
+
+// The endpoint's options from the request body, bounded by the server's limits.
+struct Options<'a> {
+    body: &'a Value,
+    config: &'a ServerConfig,
+    deadline: Instant,
+}
+
+impl Options<'_> {
+    fn string(&self, key: &str) -> Result<Option<&str>, String> {
+        match self.body.get(key) {
+            None | Some(Value::Null) => Ok(None),
+            Some(v) => v.as_str().map(Some).ok_or_else(|| format!("{key} should be a string")),
+        }
+    }
+
+    fn number(&self, key: &str, default: u64) -> Result<u64, String> {
+        match self.body.get(key) {
+            None | Some(Value::Null) => Ok(default),
+            Some(v) => v.as_u64().ok_or_else(|| format!("{key} should be a whole number")),
+        }
+    }
+
+    fn depth(&self, default: u8) -> Result<u8, String> {
+        Ok(self.number("depth", default as u64)?.clamp(1, self.config.max_depth as u64) as u8)
+    }
+
+    // The request's own time limit, if it is sooner than the server's.
+    fn deadline(&self) -> Result<Instant, String> {
+        let deadline = match self.body.get("time_ms") {
+            None | Some(Value::Null) => self.deadline,
+            Some(_) => self.deadline.min(Instant::now() + Duration::from_millis(self.number("time_ms", 0)?)),
+        };
+        Ok(deadline)
+    }
+}
+
+// The position given in the body, as state_from_args reads it from the command line.
+fn state_from_json(body: &Value) -> Result<GameState, String> {
+    if !body.is_object() {
+        return Err("the request should be a JSON object".to_string());
+    }
+    let options = |key: &str| body.get(key).filter(|v| !v.is_null());
+    let text = |key: &str, v: &Value| v.as_str().map(str::to_string).ok_or_else(|| format!("{key} should be a string"));
+    let rules: Rules = match options("rules") {
+        Some(v) => text("rules", v)?.parse()?,
+        None => Rules::standard(),
+    };
+    let mut state = match options("position") {
+        Some(v) => GameState::parse_position(&text("position", v)?, rules)?,
+        None => GameState::starting_state_with(rules)?,
+    };
+    if let Some(moves) = options("moves") {
+        for notation in moves.as_array().ok_or("moves should be a list")? {
+            let mv = state.parse_move(&text("moves", notation)?)?;
+            state.apply_move(&mv);
+        }
+    }
+    Ok(state)
+}
+
+fn state_json(state: &GameState) -> Value {
+    let (over, winner) = match state.outcome() {
+        Some(Outcome::Win(p)) => (true, Some(p.letter().to_string())),
+        Some(Outcome::Draw) => (true, None),
+        None => (false, None),
+    };
+    let stones_left: serde_json::Map<String, Value> = state.players().iter()
+        .map(|&p| (p.letter().to_string(), json!(state.stones_left(p))))
+        .collect();
+    json!({
+        "position": state.position_string(),
+        "rules": state.rules().to_string(),
+        "to_move": state.player_to_move().letter().to_string(),
+        "stones_left": stones_left,
+        "over": over,
+        "winner": winner,
+    })
+}
+
+fn moves_json(moves: &[Move]) -> Value {
+    moves.iter().map(|mv| mv.to_string()).collect()
+}
+
+fn legal_moves(state: &GameState) -> Value {
+    let mut out = state_json(state);
+    out["legal_moves"] = moves_json(&state.legal_moves());
+    out
+}
+
+fn apply(mut state: GameState, options: &Options) -> Result<Value, String> {
+    let notation = options.string("move")?.ok_or("missing move")?;
+    let mv = state.parse_move(notation)?;
+    state.apply_move(&mv);
+    Ok(legal_moves(&state))
+}
+
+fn best_move(state: &GameState, options: &Options, evaluator: &dyn Evaluator) -> Result<Value, String> {
+    if state.is_over() {
+        return Err("the game is over".to_string());
+    }
+    let depth = options.depth(4)?;
+    let deadline = options.deadline()?;
+    let (best_move, score, pv, depth, nodes) = if state.players().len() > 2 {
+        let strategy: Strategy = options.string("strategy")?.unwrap_or("paranoid").parse()?;
+        let result = MultiSearch::new(strategy).with_deadline(Some(deadline)).search_iterative(state, depth);
+        (result.best_move, result.score, result.pv, result.depth, result.nodes)
+    } else {
+        let result = Search::new(evaluator).with_deadline(Some(deadline)).search_iterative(state, depth);
+        (result.best_move, result.score, result.pv, result.depth, result.nodes)
+    };
+    Ok(json!({
+        "move": best_move.map(|mv| mv.to_string()),
+        "score": score,
+        "pv": moves_json(&pv),
+        "depth": depth,
+        "nodes": nodes,
+    }))
+}
+
+fn analyze(state: &GameState, options: &Options, evaluator: &dyn Evaluator) -> Result<Value, String> {
+    if state.players().len() > 2 {
+        return Err("analysis is only for two player games".to_string());
+    }
+    if state.is_over() {
+        return Err("the game is over".to_string());
+    }
+    let depth = options.depth(3)?;
+    let count = options.number("multipv", 3)?.max(1) as usize;
+    let deadline = options.deadline()?;
+    // Deepens until the deadline, keeping the lines from the deepest search that finished.
+    let mut search = Search::new(evaluator).with_deadline(Some(deadline));
+    let mut lines = Vec::new();
+    let mut reached = 0;
+    for d in 1..=depth {
+        if d > 1 && Instant::now() >= deadline {
+            break;
+        }
+        let deeper = search.search_multipv(state, d, count);
+        if search.aborted() && d > 1 {
+            break;
+        }
+        (lines, reached) = (deeper, d);
+    }
+    let threats = Threats::find(state);
+    Ok(json!({
+        "depth": reached,
+        "lines": lines.iter().map(|line| json!({"score": line.score, "moves": moves_json(&line.moves)})).collect::<Vec<_>>(),
+        "threats": {
+            "winning": moves_json(&threats.winning),
+            "hands_line": moves_json(&threats.hands_line),
+            "allows_win": threats.allows_win.iter().map(|(mv, reply)| json!({"move": mv.to_string(), "reply": reply.to_string()})).collect::<Vec<_>>(),
+            "safe": moves_json(&threats.safe),
+        },
+    }))
+}
@@ -0,0 +1,250 @@
+//! Laying out a position to start a game from, by hand or at random.
+use crate::board::Board;
+use crate::error::Error;
+use crate::game_state::{GameState, PlayerColor, MAX_PLAYERS};
+use crate::position::{Position, BOARD_SIZE, NUM_PILES};
+use crate::rules::Rules;
+use crate::stone::Stone;
+use crate::stone_pile::{DISPLAY_WIDTH, PILE_CAPACITY};
+use rand::seq::index;
+use rand::Rng;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+/// SetupBuilder lays out a position to start a game from, pile by pile. Nothing is checked until
+/// it is built, when the stones must add up under the rules: all of the game's neutral stones on
+/// the board, and no player with more stones on it than they start with.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SetupBuilder {
+    rules: Rules,
+    // Each pile's stones from bottom to top, indexed by square.
+    piles: Vec<Vec<Stone>>,
+    to_move: PlayerColor,
+}
+
+impl SetupBuilder {
+    /// An empty board with White to move.
+    pub fn new(rules: Rules) -> Self {
+        SetupBuilder{rules, piles: vec![Vec::new(); NUM_PILES], to_move: PlayerColor::White}
+    }
+
+    /// The piles, rules and player to move of the state.
+    pub fn from_state(state: &GameState) -> Self {
+        let piles = state.board().iter_piles().map(|(_, pile)| pile.iter().collect()).collect();
+        SetupBuilder{rules: *state.rules(), piles, to_move: state.player_to_move()}
+    }
+
+    /// Replaces the pile on the square with the stones, listed from bottom to top.
+    pub fn pile(mut self, pos: Position, stones: &[Stone]) -> Self {
+        self.piles[pos.to_index()] = stones.to_vec();
+        self
+    }
+
+    /// Adds a stone on top of the pile on the square.
+    pub fn stone(mut self, pos: Position, stone: Stone) -> Self {
+        self.piles[pos.to_index()].push(stone);
+        self
+    }
+
+    /// Empties every pile.
+    pub fn clear(mut self) -> Self {
+        self.piles.iter_mut().for_each(Vec::clear);
+        self
+    }
+
+    /// Sets whose turn it is.
+    pub fn player_to_move(mut self, player: PlayerColor) -> Self {
+        self.to_move = player;
+        self
+    }
+
+    /// The position laid out, if it is one the rules allow. Each player has left the stones they
+    /// start with less those on the board.
+    pub fn build(&self) -> Result<GameState, Error> {
+        self.rules.validate::<{ BOARD_SIZE as usize }>()?;
+        let players = PlayerColor::in_turn_order(self.rules.players);
+        if !players.contains(&self.to_move) {
+            return Err(Error::Invalid(format!("{:?} is not playing", self.to_move)));
+        }
+        let mut board = Board::default();
+        for (i, pile) in self.piles.iter().enumerate() {
+            let pos = Position::from_index(i);
+            if pile.len() > PILE_CAPACITY {
+                return Err(Error::Invalid(format!("the pile on {} has more than {PILE_CAPACITY} stones", pos.name())));
+            }
+            pile.iter().for_each(|&stone| board.add_stone(pos, stone));
+        }
+        let neutrals = board.count_stones(Stone::Neutral);
+        if neutrals != 4 * self.rules.neutrals {
+            return Err(Error::Invalid(format!("there are {neutrals} neutral stones, but the rules have {}", 4 * self.rules.neutrals)));
+        }
+        let mut stones_left = [0; MAX_PLAYERS];
+        for player in PlayerColor::ALL {
+            let placed = board.count_stones(player.stone_color());
+            if players.contains(&player) {
+                stones_left[player as usize] = self.rules.stones_per_player.checked_sub(placed)
+                    .ok_or_else(|| Error::Invalid(format!("{player:?} has {placed} stones, but starts with {}", self.rules.stones_per_player)))?;
+            } else if placed > 0 {
+                return Err(Error::Invalid(format!("{player:?} is not playing")));
+            }
+        }
+        Ok(GameState::new(board, self.to_move, stones_left, self.rules))
+    }
+}
+
+// Shown like a board, but without needing the piles to fit.
+impl fmt::Display for SetupBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, pile) in self.piles.iter().enumerate() {
+            let stones: String = if pile.is_empty() { ".".to_string() } else { pile.iter().map(|s| s.to_string()).collect() };
+            write!(f, "[{stones:.<DISPLAY_WIDTH$}]")?;
+            write!(f, "{}", if Position::<{ BOARD_SIZE as usize }>::from_index(i).next_on_new_row() || i == NUM_PILES - 1 { "\n" } else { " " })?;
+        }
+        write!(f, "{:?} to move", self.to_move)
+    }
+}
+
+/// The starting position under the rules, but with the neutral piles on squares picked at random
+/// instead of by the layout, so openings can't just be learned by heart.
+pub fn random_setup(rules: Rules, rng: &mut impl Rng) -> Result<GameState, Error> {
+    let squares = rules.layout.squares::<{ BOARD_SIZE as usize }>().len();
+    let mut builder = SetupBuilder::new(rules);
+    for i in index::sample(rng, NUM_PILES, squares) {
+        builder = builder.pile(Position::from_index(i), &vec![Stone::Neutral; rules.neutrals as usize]);
+    }
+    builder.build()
+}
+
+const EDITOR_HELP: &str = "\
+<square> <stones>  set a pile, bottom to top, e.g. \"a1 nnR\"; \"a1 .\" empties it
+turn <W|R|B|G>     set the player to move
+clear              empty the board
+reset              go back to the rules' starting position
+random             pile the neutral stones on random squares
+done               check the setup and print its position string
+quit               leave without a setup";
+
+/// Edits the setup with commands read from stdin, returning the position once it is done and
+/// valid, or None if the editor is quit.
+pub fn edit(mut builder: SetupBuilder, rng: &mut impl Rng) -> Option<GameState> {
+    let stdin = io::stdin();
+    println!("{EDITOR_HELP}");
+    loop {
+        println!("{builder}");
+        print!("setup> ");
+        io::stdout().flush().expect("should flush stdout");
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).expect("should read stdin") == 0 {
+            return None;
+        }
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let result = match words[..] {
+            [] => Ok(builder.clone()),
+            ["help"] => {
+                println!("{EDITOR_HELP}");
+                Ok(builder.clone())
+            }
+            ["quit"] => return None,
+            ["done"] => match builder.build() {
+                Ok(state) => return Some(state),
+                Err(e) => Err(e),
+            },
+            ["clear"] => Ok(builder.clone().clear()),
+            ["reset"] => GameState::starting_state_with(builder.rules).map(|state| SetupBuilder::from_state(&state)),
+            ["random"] => random_setup(builder.rules, rng).map(|state| SetupBuilder::from_state(&state)),
+            ["turn", player] => parse_player(player).map(|p| builder.clone().player_to_move(p)),
+            [square, stones] => Position::parse(square)
+                .and_then(|pos| Ok(builder.clone().pile(pos, &parse_stones(stones)?))),
+            _ => Err(Error::Parse(format!("unknown command {:?}, try help", line.trim()))),
+        };
+        match result {
+            Ok(edited) => builder = edited,
+            Err(e) => println!("{e}"),
+        }
+    }
+}
+
+fn parse_player(letter: &str) -> Result<PlayerColor, Error> {
+    let mut chars = letter.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => PlayerColor::from_letter(c),
+        _ => None,
+    }.ok_or_else(|| Error::Parse(format!("unknown player {letter:?}")))
+}
+
+fn parse_stones(stones: &str) -> Result<Vec<Stone>, Error> {
+    if stones == "." {
+        return Ok(Vec::new());
+    }
+    stones.chars().map(|c| match c {
+        'n' => Ok(Stone::Neutral),
+        _ => PlayerColor::from_letter(c).map(|p| p.stone_color()).ok_or_else(|| Error::Parse(format!("unknown stone {c:?}"))),
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn at(name: &str) -> Position {
+        Position::parse(name).unwrap()
+    }
+
+    fn refused(builder: SetupBuilder, why: &str) {
+        match builder.build() {
+            Err(Error::Invalid(message)) => assert_eq!(message, why),
+            other => panic!("expected {why:?}, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn starting_position() {
+        let start = GameState::starting_state();
+        assert_eq!(SetupBuilder::from_state(&start).build().unwrap(), start);
+        let state = SetupBuilder::from_state(&start).stone(at("b2"), Stone::White).player_to_move(PlayerColor::Red).build().unwrap();
+        assert_eq!(state.position_string(), "nn,,,nn/,W,,/,,,/nn,,,nn R");
+        assert_eq!(state.moves_left(), start.moves_left() - 1);
+    }
+
+    #[test]
+    fn neutral_stones() {
+        let start = SetupBuilder::from_state(&GameState::starting_state());
+        refused(start.clone().pile(at("a1"), &[Stone::Neutral]), "there are 7 neutral stones, but the rules have 8");
+        refused(start.clone().stone(at("b2"), Stone::Neutral), "there are 9 neutral stones, but the rules have 8");
+        refused(start.clear(), "there are 0 neutral stones, but the rules have 8");
+    }
+
+    #[test]
+    fn player_stones() {
+        let start = SetupBuilder::from_state(&GameState::starting_state());
+        assert!(start.clone().pile(at("b2"), &[Stone::Red; 8]).build().is_ok());
+        refused(start.clone().pile(at("b2"), &[Stone::Red; 9]), "Red has 9 stones, but starts with 8");
+        refused(start.pile(at("b2"), &[Stone::Blue]), "Blue is not playing");
+    }
+
+    #[test]
+    fn player_to_move() {
+        let start = SetupBuilder::from_state(&GameState::starting_state());
+        refused(start.clone().player_to_move(PlayerColor::Green), "Green is not playing");
+        let three = SetupBuilder::from_state(&GameState::starting_state_with(Rules::preset("three-player").unwrap()).unwrap());
+        assert_eq!(three.player_to_move(PlayerColor::Blue).build().unwrap().player_to_move(), PlayerColor::Blue);
+    }
+
+    #[test]
+    fn pile_capacity() {
+        let start = SetupBuilder::from_state(&GameState::starting_state());
+        refused(start.pile(at("b2"), &[Stone::White; PILE_CAPACITY + 1]), &format!("the pile on b2 has more than {PILE_CAPACITY} stones"));
+    }
+
+    #[test]
+    fn random_setups() {
+        let mut rng = StdRng::seed_from_u64(1);
+        for rules in [Rules::standard(), Rules::preset("three-player").unwrap()] {
+            let state = random_setup(rules, &mut rng).unwrap();
+            assert_eq!(state.board().count_stones(Stone::Neutral), 4 * rules.neutrals);
+            assert_eq!(state.moves_left(), GameState::starting_state_with(rules).unwrap().moves_left());
+        }
+    }
+}
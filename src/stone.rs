@@ -1,12 +1,36 @@
+//! The stones: neutral, and one colour per player.
 use std::fmt;
 
+/// Stone is a neutral stone or a player's stone, written as its letter: n, R, W, B or G.
 #[derive(PartialEq, Eq, Copy, Clone, Debug, Hash, Default)]
 #[repr(u8)]
 pub enum Stone {
+    /// The stones piled on the board at the start, which belong to nobody.
     #[default]
     Neutral,
+    /// Red's stones.
     Red,
+    /// White's stones.
     White,
+    // The extra colours for games of three or four players.
+    /// Blue's stones, in games of three or four players.
+    Blue,
+    /// Green's stones, in games of four players.
+    Green,
+}
+
+impl Stone {
+    /// The stone written as the letter its Display gives.
+    pub fn from_letter(c: char) -> Option<Stone> {
+        match c {
+            'n' => Some(Stone::Neutral),
+            'R' => Some(Stone::Red),
+            'W' => Some(Stone::White),
+            'B' => Some(Stone::Blue),
+            'G' => Some(Stone::Green),
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Display for Stone {
@@ -15,6 +39,8 @@ impl fmt::Display for Stone {
             Stone::Neutral => write!(f, "n"),
             Stone::Red => write!(f, "R"),
             Stone::White => write!(f, "W"),
+            Stone::Blue => write!(f, "B"),
+            Stone::Green => write!(f, "G"),
         }
     }
 }
@@ -1,56 +1,127 @@
+//! Piles of stones, as found on each square.
+use crate::error::Error;
 use crate::stone::Stone;
 use tinyvec::ArrayVec;
 use std::fmt;
 
-#[derive(PartialEq, Eq, Copy, Clone, Debug, Hash, Default)]
-pub struct StonePile(ArrayVec<[Stone; PILE_CAPACITY]>);
+/// StonePile is a pile of stones, bottom first. Piles are copied with every board, so the stones
+/// are packed three bits each rather than a byte each.
+#[derive(PartialEq, Eq, Copy, Clone, Hash, Default)]
+#[repr(C, packed)]
+pub struct StonePile {
+    // Stone i is in bits 3i to 3i+2. Bits above the pile's height are always clear.
+    stones: u128,
+    height: u8,
+}
+
+/// The most stones a pile can hold: those of the standard game, 8 neutrals and 8 for each of
+/// two players. Rules::validate refuses rules, for any number of players, with more stones than
+/// this in all, so that every stone in a game fits in one pile.
+pub const PILE_CAPACITY: usize = 24;
 
-pub const PILE_CAPACITY: usize = 8;
+/// Piles are padded to this width when displayed, so boards stay aligned.
+pub const DISPLAY_WIDTH: usize = 8;
+
+const STONE_BITS: u32 = 3;
+const STONE_MASK: u128 = (1 << STONE_BITS) - 1;
 
 impl StonePile {
+    /// The stone on top of the pile, which is what counts towards lines.
     pub fn top(&self) -> PileTop {
-        match self.0.last() {
-            None => PileTop::Empty,
-            Some(Stone::Red) => PileTop::RedStone,
-            Some(Stone::White) => PileTop::WhiteStone,
-            Some(Stone::Neutral) => PileTop::NeutralStone,
+        match self.height {
+            0 => PileTop::Empty,
+            h => match self.get(h - 1) {
+                Stone::Red => PileTop::RedStone,
+                Stone::White => PileTop::WhiteStone,
+                Stone::Neutral => PileTop::NeutralStone,
+                Stone::Blue => PileTop::BlueStone,
+                Stone::Green => PileTop::GreenStone,
+            },
         }
     }
 
+    /// The number of stones in the pile.
     pub fn height(&self) -> u8 {
-        self.0.len() as u8
+        self.height
     }
 
+    /// Puts a stone on top of the pile. Panics if the pile already holds PILE_CAPACITY stones.
     pub fn add_stone(&mut self, s: Stone) {
-        self.0.push(s)
+        if self.height as usize == PILE_CAPACITY {
+            panic!("pile {self} is full")
+        }
+        self.stones |= (s as u128) << (self.height as u32 * STONE_BITS);
+        self.height += 1;
     }
     
+    /// The stones from bottom to top.
+    pub fn iter(&self) -> impl Iterator<Item = Stone> + '_ {
+        (0..self.height).map(|i| self.get(i))
+    }
+
+    fn get(&self, i: u8) -> Stone {
+        match (self.stones >> (i as u32 * STONE_BITS)) & STONE_MASK {
+            0 => Stone::Neutral,
+            1 => Stone::Red,
+            2 => Stone::White,
+            3 => Stone::Blue,
+            _ => Stone::Green,
+        }
+    }
+
+    /// Parses a pile written as its stones from bottom to top, e.g. "nnW".
+    pub fn parse(stones: &str) -> Result<Self, Error> {
+        if stones.len() > PILE_CAPACITY {
+            return Err(Error::Parse("pile too tall".to_string()));
+        }
+        let mut pile = StonePile::default();
+        for c in stones.chars() {
+            pile.add_stone(Stone::from_letter(c).ok_or_else(|| Error::Parse(format!("unknown stone {c:?}")))?);
+        }
+        Ok(pile)
+    }
+
+    /// Picks up the whole pile, leaving the square empty, and returns its stones from bottom to top.
     pub fn take_pile(&mut self) -> ArrayVec<[Stone; PILE_CAPACITY]> {
-        let mut pile = ArrayVec::<[Stone; PILE_CAPACITY]>::new();
-        (self.0, pile) = (pile, self.0);
+        let pile = self.iter().collect();
+        *self = StonePile::default();
         pile
     }
 }
 
+impl fmt::Debug for StonePile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
 impl fmt::Display for StonePile {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        if self.0.is_empty() {
-            return write!(f, "[{:.<PILE_CAPACITY$}]", PileTop::Empty.to_string())
+        if self.height == 0 {
+            return write!(f, "[{:.<DISPLAY_WIDTH$}]", PileTop::Empty.to_string())
         }
-        let stack = self.0.iter().fold(String::new(), |acc, &arg| acc + &arg.to_string());
-        write!(f, "[{:.<PILE_CAPACITY$}]", stack)
+        let stack = self.iter().fold(String::new(), |acc, arg| acc + &arg.to_string());
+        write!(f, "[{:.<DISPLAY_WIDTH$}]", stack)
     }
 }
 
-
+/// PileTop is what can be seen of a pile from above: its top stone, if it has any.
 #[derive(PartialEq, Eq, Copy, Clone, Debug, Hash, Default)]
 #[repr(u8)]
 pub enum PileTop {
+    /// The square has no stones.
     #[default]
     Empty,
+    /// Red's stone is on top.
     RedStone,
+    /// White's stone is on top.
     WhiteStone,
+    /// A neutral stone is on top.
     NeutralStone,
+    /// Blue's stone is on top.
+    BlueStone,
+    /// Green's stone is on top.
+    GreenStone,
 }
 
 impl fmt::Display for PileTop {
@@ -60,6 +131,8 @@ impl fmt::Display for PileTop {
             PileTop::RedStone => write!(f, "R"),
             PileTop::WhiteStone => write!(f, "W"),
             PileTop::NeutralStone => write!(f, "n"),
+            PileTop::BlueStone => write!(f, "B"),
+            PileTop::GreenStone => write!(f, "G"),
         }
     }
 }
\ No newline at end of file
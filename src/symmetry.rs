@@ -0,0 +1,139 @@
+//! The rotations and reflections of the board, and the canonical orientation of a position.
+use crate::board::Board;
+use crate::game_state::{GameState, Move, PlayerColor};
+use crate::path::Path;
+use crate::position::{Direction, Position, BOARD_SIZE};
+use crate::zobrist::state_hash;
+
+/// Symmetry is one of the eight rotations and reflections of the board, which all preserve the
+/// moves of the game, and its results where Rules::symmetric says so. It transposes the board if bit 2 is set, then mirrors horizontally if bit 0
+/// is set and vertically if bit 1 is set.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Hash, Default)]
+pub struct Symmetry(u8);
+
+impl Symmetry {
+    /// The symmetry that leaves the board as it is.
+    pub fn identity() -> Symmetry {
+        Symmetry(0)
+    }
+
+    /// All eight symmetries, starting with the identity.
+    pub fn all() -> impl Iterator<Item = Symmetry> {
+        (0..8).map(Symmetry)
+    }
+
+    /// The symmetry that undoes this one.
+    pub fn inverse(self) -> Symmetry {
+        Symmetry::all()
+            .find(|s| s.apply_position(self.apply_position(Position::from_coord(1, 0))) == Position::from_coord(1, 0)
+                && s.apply_position(self.apply_position(Position::from_coord(0, 1))) == Position::from_coord(0, 1))
+            .expect("every symmetry has an inverse")
+    }
+
+    fn apply_vector(self, (mut x, mut y): (i8, i8), max: i8) -> (i8, i8) {
+        if self.0 & 4 != 0 {
+            (x, y) = (y, x);
+        }
+        if self.0 & 1 != 0 {
+            x = max - x;
+        }
+        if self.0 & 2 != 0 {
+            y = max - y;
+        }
+        (x, y)
+    }
+
+    /// The square the symmetry takes the square to.
+    pub fn apply_position(self, pos: Position) -> Position {
+        let (x, y) = self.apply_vector((pos.x() as i8, pos.y() as i8), BOARD_SIZE as i8 - 1);
+        Position::from_coord(x as u8, y as u8)
+    }
+
+    /// The direction the symmetry turns the direction into.
+    pub fn apply_direction(self, dir: Direction) -> Direction {
+        // Mirroring a vector negates it, rather than reflecting it about the board's centre.
+        let target = self.apply_vector(dir.vector(), 0);
+        enum_iterator::all::<Direction>().find(|d| d.vector() == target).unwrap()
+    }
+
+    /// The path moved by the symmetry.
+    pub fn apply_path(self, path: &Path) -> Path {
+        let mut out = Path::new_empty(self.apply_position(path.start()), path.topology());
+        for &dir in path.steps() {
+            out.step(self.apply_direction(dir));
+        }
+        out
+    }
+
+    /// The move moved by the symmetry.
+    pub fn apply_move(self, mv: &Move) -> Move {
+        Move{new_stone: mv.new_stone, path: self.apply_path(&mv.path)}
+    }
+
+    /// The board with every pile moved by the symmetry.
+    pub fn apply_board(self, board: &Board) -> Board {
+        let mut out = Board::default();
+        for (pos, pile) in board.iter_piles() {
+            for stone in pile.iter() {
+                out.add_stone(self.apply_position(pos), stone);
+            }
+        }
+        out
+    }
+
+    /// The state with its board moved by the symmetry.
+    pub fn apply_state(self, state: &GameState) -> GameState {
+        let stones_left = PlayerColor::ALL.map(|p| state.stones_left(p));
+        GameState::new(self.apply_board(state.board()), state.player_to_move(), stones_left, *state.rules())
+    }
+}
+
+/// Picks the symmetry mapping the state to its canonical orientation, the one with the smallest
+/// hash, and returns the canonical state's hash along with it. Where the rules aren't symmetric,
+/// the orientations of a position can have different results, so each is its own canonical one.
+pub fn canonical(state: &GameState) -> (u64, Symmetry) {
+    if !state.rules().symmetric() {
+        return (state_hash(state), Symmetry::identity());
+    }
+    Symmetry::all()
+        .map(|s| (state_hash(&s.apply_state(state)), s))
+        .min_by_key(|&(hash, _)| hash)
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_state::Outcome;
+    use crate::rules::Rules;
+
+    // White tops row 1 and Red row 3, as after a move by Red spreading its stones.
+    const BOTH_LINES: &str = "nnW,nnW,nnW,nnW/,,,/R,R,R,R/,,, W";
+
+    fn flip(state: &GameState) -> GameState {
+        Symmetry(2).apply_state(state)
+    }
+
+    #[test]
+    fn first_found_lines_depend_on_orientation() {
+        let state = GameState::parse_position(BOTH_LINES, Rules::standard()).unwrap();
+        assert_eq!(state.outcome(), Some(Outcome::Win(PlayerColor::White)));
+        assert_eq!(flip(&state).outcome(), Some(Outcome::Win(PlayerColor::Red)));
+        assert_eq!(canonical(&state), (state_hash(&state), Symmetry::identity()));
+        assert_ne!(canonical(&state).0, canonical(&flip(&state)).0);
+    }
+
+    #[test]
+    fn symmetric_rules() {
+        let rules: Rules = "standard:simultaneous=mover".parse().unwrap();
+        assert!(rules.symmetric() && !Rules::standard().symmetric());
+        let state = GameState::parse_position(BOTH_LINES, rules).unwrap();
+        let mut start = GameState::starting_state_with(rules).unwrap();
+        start.apply_move(&start.parse_move("a1rrr").unwrap());
+        for symmetry in Symmetry::all() {
+            assert_eq!(symmetry.apply_state(&state).outcome(), Some(Outcome::Win(PlayerColor::Red)));
+            assert_eq!(canonical(&symmetry.apply_state(&start)).0, canonical(&start).0);
+            assert_eq!(symmetry.inverse().apply_state(&symmetry.apply_state(&start)), start);
+        }
+    }
+}
@@ -0,0 +1,120 @@
+//! What a move immediately does: whether it wins, or lets the opponent win.
+use crate::game_state::{GameState, Move, Outcome, PlayerColor};
+
+/// Consequence is what a move immediately does for the player who made it.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Hash)]
+pub enum Consequence {
+    /// The move tops a line for the mover.
+    Wins,
+    /// Distributing the stones completed a line for the opponent.
+    HandsLine,
+    /// The move leaves the next player without stones.
+    Draws,
+    /// The game goes on.
+    Continues,
+}
+
+/// The consequence of the move that led to `after`, for the player who made it.
+pub fn consequence(after: &GameState, mover: PlayerColor) -> Consequence {
+    match after.outcome() {
+        Some(Outcome::Win(p)) if p == mover => Consequence::Wins,
+        Some(Outcome::Win(_)) => Consequence::HandsLine,
+        Some(Outcome::Draw) => Consequence::Draws,
+        None => Consequence::Continues,
+    }
+}
+
+/// The consequence of playing the move in the state, for the player to move.
+pub fn move_consequence(state: &GameState, mv: &Move) -> Consequence {
+    let mut after = *state;
+    after.apply_move(mv);
+    consequence(&after, state.player_to_move())
+}
+
+/// The moves that win straight away for the player to move.
+pub fn winning_moves(state: &GameState) -> Vec<Move> {
+    state.legal_moves().into_iter().filter(|mv| move_consequence(state, mv) == Consequence::Wins).collect()
+}
+
+/// A reply that wins for the opponent straight after the move, if the game goes on.
+pub fn winning_reply(state: &GameState, mv: &Move) -> Option<Move> {
+    let mut after = *state;
+    after.apply_move(mv);
+    if after.is_over() {
+        return None;
+    }
+    winning_moves(&after).into_iter().next()
+}
+
+/// Threats sorts every legal move in a position by what it leads to within the next two plies.
+#[derive(Clone, Debug, Default)]
+pub struct Threats {
+    /// The moves that win.
+    pub winning: Vec<Move>,
+    /// The moves that complete a line for the opponent, and so lose.
+    pub hands_line: Vec<Move>,
+    /// Each move paired with an opponent reply that wins.
+    pub allows_win: Vec<(Move, Move)>,
+    /// The moves that neither win nor let the opponent win straight after.
+    pub safe: Vec<Move>,
+}
+
+impl Threats {
+    /// Sorts the legal moves of the state.
+    pub fn find(state: &GameState) -> Threats {
+        let mut threats = Threats::default();
+        for mv in state.legal_moves() {
+            match move_consequence(state, &mv) {
+                Consequence::Wins => threats.winning.push(mv),
+                Consequence::HandsLine => threats.hands_line.push(mv),
+                Consequence::Draws => threats.safe.push(mv),
+                Consequence::Continues => match winning_reply(state, &mv) {
+                    Some(reply) => threats.allows_win.push((mv, reply)),
+                    None => threats.safe.push(mv),
+                },
+            }
+        }
+        threats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::Rules;
+
+    // White needs c1 to finish the top row. From b2, with two White stones, Red can be made to
+    // put one there.
+    const POSITION: &str = "W,W,,W/,WW,,/nn,,,nn/nn,,,nn";
+
+    fn notations(moves: &[Move]) -> Vec<String> {
+        moves.iter().map(|mv| mv.path.notation()).collect()
+    }
+
+    #[test]
+    fn threats() {
+        let state = GameState::parse_position(&format!("{POSITION} R"), Rules::standard()).unwrap();
+        let threats = Threats::find(&state);
+        assert!(threats.winning.is_empty());
+        assert_eq!(notations(&threats.hands_line), ["b2urd"]);
+        assert_eq!(notations(&threats.safe), ["a1rr", "a1rd", "a1dr", "b2lur", "a3uur", "a4uuu"]);
+        for (mv, reply) in &threats.allows_win {
+            let mut after = state;
+            after.apply_move(mv);
+            assert!(winning_moves(&after).contains(reply), "{} does not answer {}", reply.path.notation(), mv.path.notation());
+        }
+        let sorted = threats.winning.len() + threats.hands_line.len() + threats.allows_win.len() + threats.safe.len();
+        assert_eq!(sorted, state.legal_moves().len());
+    }
+
+    #[test]
+    fn winning_moves_win() {
+        let state = GameState::parse_position(&format!("{POSITION} W"), Rules::standard()).unwrap();
+        let threats = Threats::find(&state);
+        assert_eq!(notations(&threats.winning), ["b2rur", "b2rul", "b2ldd", "b2urr", "b2urd", "d3luu", "d3ulu"]);
+        assert!(threats.hands_line.is_empty() && threats.allows_win.is_empty());
+        let b2rur = state.parse_move("b2rur").unwrap();
+        assert_eq!(move_consequence(&state, &b2rur), Consequence::Wins);
+        assert_eq!(winning_reply(&state, &b2rur), None);
+    }
+}
@@ -0,0 +1,212 @@
+use crate::game_state::GameState;
+use crate::match_runner::{self, MatchConfig, MatchStats};
+use crate::rules::Rules;
+use std::fmt::Write as _;
+use std::fs;
+
+/// TournamentConfig describes a round robin between agent specs, or a gauntlet in which the
+/// first agent plays each of the others.
+#[derive(Clone, Debug)]
+pub struct TournamentConfig {
+    pub agents: Vec<String>,
+    /// The rules of every game, which must be for two players.
+    pub rules: Rules,
+    pub gauntlet: bool,
+    pub games_per_pair: u32,
+    pub threads: usize,
+    pub opening_plies: u8,
+    pub random_setup: bool,
+    pub seed: u64,
+    pub records: Option<String>,
+}
+
+/// Rating is an agent's fitted Elo, relative to the field's average, with a 95% margin.
+#[derive(Clone, Debug)]
+pub struct Rating {
+    pub agent: String,
+    pub elo: f64,
+    pub margin: f64,
+    pub games: u32,
+    pub score: f64,
+}
+
+/// TournamentResults holds the results of every pairing, indexed by agent.
+pub struct TournamentResults {
+    pub agents: Vec<String>,
+    /// `results[i][j]` is from the point of view of agent i against agent j.
+    pub results: Vec<Vec<MatchStats>>,
+}
+
+pub fn run_tournament(config: &TournamentConfig, mut progress: impl FnMut(&str, &str, &MatchStats)) -> Result<TournamentResults, String> {
+    let n = config.agents.len();
+    if n < 2 {
+        return Err("a tournament needs at least two agents".to_string());
+    }
+    match_runner::check_rules(&config.rules)?;
+    match_runner::check_opening(&GameState::starting_state_with(config.rules)?, config.opening_plies)?;
+    let pairings: Vec<(usize, usize)> = (0..n)
+        .flat_map(|i| (i + 1..n).map(move |j| (i, j)))
+        .filter(|&(i, _)| !config.gauntlet || i == 0)
+        .collect();
+    let mut results = vec![vec![MatchStats::default(); n]; n];
+    for (i, j) in pairings {
+        let match_config = MatchConfig {
+            agents: [config.agents[i].clone(), config.agents[j].clone()],
+            rules: config.rules,
+            games: config.games_per_pair,
+            threads: config.threads,
+            opening_plies: config.opening_plies,
+            random_setup: config.random_setup,
+            seed: config.seed.wrapping_add((i * n + j) as u64 * config.games_per_pair as u64),
+            records: config.records.clone(),
+            sprt: None,
+        };
+        let stats = match_runner::run_match(&match_config, |_, _| {})?;
+        progress(&config.agents[i], &config.agents[j], &stats);
+        results[i][j] = stats;
+        results[j][i] = MatchStats{wins: stats.losses, losses: stats.wins, draws: stats.draws};
+    }
+    Ok(TournamentResults{agents: config.agents.clone(), results})
+}
+
+impl TournamentResults {
+    /// Fits a Bradley-Terry model to the results with the MM algorithm, counting draws as half a
+    /// win each. Like BayesElo, every pairing that was played gets one extra virtual draw as a prior,
+    /// so agents that never won or never lost still get finite ratings.
+    pub fn ratings(&self) -> Vec<Rating> {
+        let n = self.agents.len();
+        let played = |i: usize, j: usize| self.results[i][j].games() > 0;
+        let games = |i: usize, j: usize| if played(i, j) { self.results[i][j].games() as f64 + 1.0 } else { 0.0 };
+        let points = |i: usize, j: usize| if played(i, j) { self.results[i][j].score() * self.results[i][j].games() as f64 + 0.5 } else { 0.0 };
+
+        let mut strength = vec![1.0; n];
+        for _ in 0..1000 {
+            let mut next = vec![0.0; n];
+            for i in 0..n {
+                let won: f64 = (0..n).map(|j| points(i, j)).sum();
+                let expected: f64 = (0..n).filter(|&j| j != i).map(|j| games(i, j) / (strength[i] + strength[j])).sum();
+                next[i] = if expected > 0.0 { won / expected } else { strength[i] };
+            }
+            // Keep the geometric mean at 1, so the average rating is 0.
+            let mean = next.iter().map(|s: &f64| s.ln()).sum::<f64>() / n as f64;
+            next.iter_mut().for_each(|s| *s /= mean.exp());
+            let change = next.iter().zip(&strength).map(|(a, b)| (a - b).abs()).fold(0.0, f64::max);
+            strength = next;
+            if change < 1e-9 {
+                break;
+            }
+        }
+
+        let elo_per_ln = 400.0 / std::f64::consts::LN_10;
+        let mut ratings: Vec<Rating> = (0..n).map(|i| {
+            // The observed Fisher information of agent i's log-strength gives its standard error.
+            let information: f64 = (0..n).filter(|&j| j != i).map(|j| {
+                let p = strength[i] / (strength[i] + strength[j]);
+                games(i, j) * p * (1.0 - p)
+            }).sum();
+            let games_played: u32 = self.results[i].iter().map(|s| s.games()).sum();
+            let points: f64 = self.results[i].iter().map(|s| s.score() * s.games() as f64).sum();
+            Rating {
+                agent: self.agents[i].clone(),
+                elo: strength[i].ln() * elo_per_ln,
+                margin: 1.96 * elo_per_ln / information.max(1e-9).sqrt(),
+                games: games_played,
+                score: points / games_played.max(1) as f64,
+            }
+        }).collect();
+        ratings.sort_by(|a, b| b.elo.total_cmp(&a.elo));
+        ratings
+    }
+
+    pub fn rating_table(&self) -> String {
+        let width = self.agents.iter().map(|a| a.len()).max().unwrap_or(0).max(5);
+        let mut out = format!("{:>4} {:<width$} {:>7} {:>6} {:>6} {:>6}\n", "Rank", "Agent", "Elo", "+/-", "Games", "Score");
+        for (rank, r) in self.ratings().iter().enumerate() {
+            writeln!(out, "{:>4} {:<width$} {:>7.1} {:>6.1} {:>6} {:>5.1}%", rank + 1, r.agent, r.elo, r.margin, r.games, r.score * 100.0).unwrap();
+        }
+        out
+    }
+
+    pub fn ratings_csv(&self) -> String {
+        let mut out = String::from("rank,agent,elo,margin,games,score\n");
+        for (rank, r) in self.ratings().iter().enumerate() {
+            writeln!(out, "{},\"{}\",{:.1},{:.1},{},{:.4}", rank + 1, r.agent.replace('"', "\"\""), r.elo, r.margin, r.games, r.score).unwrap();
+        }
+        out
+    }
+
+    /// A table of each agent's points against each opponent, out of the games played, with agents
+    /// numbered by their row.
+    pub fn crosstable(&self) -> String {
+        let n = self.agents.len();
+        let width = self.agents.iter().map(|a| a.len()).max().unwrap_or(0);
+        let cell = 11;
+        let mut out = format!("{:>3} {:<width$}", "", "");
+        for j in 0..n {
+            write!(out, " {:>cell$}", j + 1).unwrap();
+        }
+        out.push('\n');
+        for i in 0..n {
+            write!(out, "{:>3} {:<width$}", i + 1, self.agents[i]).unwrap();
+            for j in 0..n {
+                let stats = &self.results[i][j];
+                let text = if i == j || stats.games() == 0 {
+                    "-".to_string()
+                } else {
+                    format!("{}/{}", stats.score() * stats.games() as f64, stats.games())
+                };
+                write!(out, " {text:>cell$}").unwrap();
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    pub fn write_csv(&self, path: &str) -> Result<(), String> {
+        fs::write(path, self.ratings_csv()).map_err(|e| format!("{path}: {e}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Results from the wins, losses and draws of agent i against agent j, given for i < j only.
+    fn results(agents: &[&str], scores: &[(usize, usize, [u32; 3])]) -> TournamentResults {
+        let n = agents.len();
+        let mut results = vec![vec![MatchStats::default(); n]; n];
+        for &(i, j, [wins, losses, draws]) in scores {
+            results[i][j] = MatchStats{wins, losses, draws};
+            results[j][i] = MatchStats{wins: losses, losses: wins, draws};
+        }
+        TournamentResults{agents: agents.iter().map(|a| a.to_string()).collect(), results}
+    }
+
+    #[test]
+    fn two_agents() {
+        // With the virtual draw, a scores 8.5 of 11 against b, so is 8.5/2.5 times as strong.
+        let ratings = results(&["a", "b"], &[(0, 1, [8, 2, 0])]).ratings();
+        let elo = 400.0 * (8.5f64 / 2.5).log10() / 2.0;
+        assert_eq!((ratings[0].agent.as_str(), ratings[1].agent.as_str()), ("a", "b"));
+        assert!((ratings[0].elo - elo).abs() < 1e-6 && (ratings[1].elo + elo).abs() < 1e-6, "{ratings:?}");
+        assert_eq!((ratings[0].games, ratings[0].score), (10, 0.8));
+    }
+
+    #[test]
+    fn round_robin() {
+        let agents = ["weak", "strong", "middling", "unbeaten"];
+        let ratings = results(&agents, &[
+            (0, 1, [1, 9, 0]),
+            (0, 2, [3, 5, 2]),
+            (0, 3, [0, 10, 0]),
+            (1, 2, [6, 2, 2]),
+            (1, 3, [0, 6, 4]),
+            (2, 3, [0, 8, 2]),
+        ]).ratings();
+        let order: Vec<&str> = ratings.iter().map(|r| r.agent.as_str()).collect();
+        assert_eq!(order, ["unbeaten", "strong", "middling", "weak"]);
+        assert!(ratings.iter().all(|r| r.elo.is_finite() && r.margin.is_finite() && r.margin > 0.0));
+        let mean = ratings.iter().map(|r| r.elo).sum::<f64>() / ratings.len() as f64;
+        assert!(mean.abs() < 1e-6, "ratings should average 0, not {mean}");
+    }
+}
@@ -0,0 +1,495 @@
+//! A full-screen terminal UI for playing and analysing games, built with the `tui` feature.
+use crate::agent::Agent;
+use crate::error::Error;
+use crate::evaluator::Evaluator;
+use crate::game_state::{GameState, Move, Outcome, PlayerColor};
+use crate::path::Path;
+use crate::position::{Direction, Position, NUM_PILES};
+use crate::search::{is_win_score, Search, SearchResult};
+use crate::stone::Stone;
+use crate::stone_pile::DISPLAY_WIDTH;
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{Attribute, Color, Print, ResetColor, SetAttribute, SetBackgroundColor, SetForegroundColor};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+use std::io::{self, Stdout, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+// The analysis stops deepening here, since the transposition table grows with every ply.
+const MAX_ANALYSIS_DEPTH: u8 = 8;
+
+// Each square is an arrow for the path being drawn followed by its pile.
+const CELL_WIDTH: u16 = DISPLAY_WIDTH as u16 + 4;
+const BOARD_TOP: u16 = 3;
+const MOVES_LEFT: u16 = 2 + 4 * CELL_WIDTH + 4;
+
+const KEYS: &str = "arrows/rdlu(czqe) move  enter pick up/play  backspace undo  esc cancel  g engine move  a analysis  Q quit";
+
+/// TuiOptions are who plays what in the terminal UI.
+pub struct TuiOptions {
+    /// Plays for the players in `automatic` as soon as it is their turn, and for anyone when asked.
+    pub engine: Box<dyn Agent>,
+    /// The players the engine plays for; people play the others.
+    pub automatic: Vec<PlayerColor>,
+    /// Used by the live analysis, which only runs in two player games.
+    pub evaluator: Arc<dyn Evaluator + Send + Sync>,
+}
+
+// Puts the terminal in raw mode on the alternate screen, and puts it back when dropped, even if
+// the UI panics.
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn new() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        execute!(io::stdout(), EnterAlternateScreen, Hide)?;
+        Ok(TerminalGuard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), ResetColor, Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+// A search of the current position on a background thread, deepening a ply at a time until it is
+// stopped or dropped.
+struct Analysis {
+    stop: Arc<AtomicBool>,
+    results: Receiver<SearchResult>,
+}
+
+impl Analysis {
+    fn start(state: GameState, evaluator: Arc<dyn Evaluator + Send + Sync>) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let (sender, results) = mpsc::channel();
+        let flag = Arc::clone(&stop);
+        thread::spawn(move || {
+            let mut search = Search::new(&*evaluator).with_stop(&flag);
+            for depth in 1..=state.moves_left().min(MAX_ANALYSIS_DEPTH) {
+                let result = search.search(&state, depth);
+                // A stopped search's result is only partly searched.
+                if flag.load(Ordering::Relaxed) {
+                    break;
+                }
+                let done = is_win_score(result.score);
+                if sender.send(result).is_err() || done {
+                    break;
+                }
+            }
+        });
+        Analysis{stop, results}
+    }
+}
+
+impl Drop for Analysis {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+struct Tui {
+    options: TuiOptions,
+    state: GameState,
+    // The states before each move played, for taking moves back.
+    history: Vec<GameState>,
+    moves: Vec<Move>,
+    cursor: Position,
+    // The path being drawn from the picked up pile, if any.
+    drawing: Option<Path>,
+    analysing: bool,
+    analysis: Option<Analysis>,
+    latest: Option<SearchResult>,
+    message: String,
+}
+
+/// Plays a game from `start` in a full-screen terminal UI, returning the moves played.
+pub fn run(start: GameState, options: TuiOptions) -> Result<Vec<Move>, Error> {
+    let mut tui = Tui::new(start, options);
+    let guard = TerminalGuard::new().map_err(Error::io("the terminal"))?;
+    tui.restart_analysis();
+    let result = tui.event_loop(&mut io::stdout());
+    drop(guard);
+    result.map_err(Error::io("the terminal"))?;
+    Ok(tui.moves)
+}
+
+impl Tui {
+    fn new(start: GameState, options: TuiOptions) -> Self {
+        Tui {
+            options,
+            state: start,
+            history: Vec::new(),
+            moves: Vec::new(),
+            cursor: Position::top_left(),
+            drawing: None,
+            analysing: start.players().len() == 2,
+            analysis: None,
+            latest: None,
+            message: String::new(),
+        }
+    }
+
+    fn event_loop(&mut self, out: &mut Stdout) -> io::Result<()> {
+        // Redrawing clears the screen, so only redraw when something has changed.
+        let mut dirty = true;
+        loop {
+            if !self.state.is_over() && self.options.automatic.contains(&self.state.player_to_move()) {
+                self.message = format!("{:?} is thinking...", self.state.player_to_move());
+                self.draw(out)?;
+                self.engine_move();
+                continue;
+            }
+            if let Some(analysis) = &self.analysis {
+                while let Ok(result) = analysis.results.try_recv() {
+                    self.latest = Some(result);
+                    dirty = true;
+                }
+            }
+            if dirty {
+                self.draw(out)?;
+                dirty = false;
+            }
+            if !event::poll(Duration::from_millis(100))? {
+                continue;
+            }
+            let event = event::read()?;
+            dirty = true;
+            let Event::Key(key) = event else { continue };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            if key.code == KeyCode::Char('Q') || (key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL)) {
+                return Ok(());
+            }
+            self.message.clear();
+            self.handle_key(key);
+        }
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) {
+        let dir = match key.code {
+            KeyCode::Right => Some(Direction::Right),
+            KeyCode::Down => Some(Direction::Down),
+            KeyCode::Left => Some(Direction::Left),
+            KeyCode::Up => Some(Direction::Up),
+            KeyCode::Char(c) => Direction::from_letter(c),
+            _ => None,
+        };
+        if let Some(dir) = dir {
+            match self.drawing {
+                Some(_) => self.extend_path(dir),
+                None if self.cursor.can_step(dir, self.state.rules().topology) => {
+                    self.cursor = self.cursor.step(dir, self.state.rules().topology);
+                }
+                None => (),
+            }
+            return;
+        }
+        match key.code {
+            KeyCode::Enter | KeyCode::Char(' ') => match self.drawing {
+                Some(path) => self.play_path(path),
+                None => self.pick_up(),
+            },
+            KeyCode::Backspace => match &mut self.drawing {
+                Some(path) if !path.is_empty() => {
+                    path.pop_step();
+                    self.cursor = path.end();
+                }
+                Some(path) => {
+                    self.cursor = path.start();
+                    self.drawing = None;
+                }
+                None => self.take_back(),
+            },
+            KeyCode::Esc => {
+                if let Some(path) = self.drawing.take() {
+                    self.cursor = path.start();
+                }
+            }
+            KeyCode::Char('g') if self.drawing.is_none() => {
+                if self.state.is_over() {
+                    self.message = "the game is over".to_string();
+                } else {
+                    self.engine_move();
+                }
+            }
+            KeyCode::Char('a') => {
+                self.analysing = !self.analysing;
+                self.restart_analysis();
+            }
+            _ => (),
+        }
+    }
+
+    // The stones in hand while drawing: the picked up pile with the player's stone on top, less
+    // those already dropped along the path, bottom first.
+    fn hand(&self, path: &Path) -> Vec<Stone> {
+        let mut hand: Vec<Stone> = self.state.board().pile(path.start()).iter().collect();
+        hand.push(self.state.player_to_move().stone_color());
+        hand.split_off(path.len())
+    }
+
+    fn pick_up(&mut self) {
+        if self.state.is_over() {
+            self.message = "the game is over".to_string();
+        } else if !self.state.legal_moves().iter().any(|mv| mv.path.start() == self.cursor) {
+            self.message = format!("there are no moves from {}", self.cursor.name());
+        } else {
+            self.drawing = Some(Path::new_empty(self.cursor, self.state.rules().topology));
+        }
+    }
+
+    fn extend_path(&mut self, dir: Direction) {
+        let Some(mut path) = self.drawing else { return };
+        let rules = *self.state.rules();
+        if self.hand(&path).is_empty() {
+            self.message = "every stone is down, press enter to play".to_string();
+        } else if dir.is_diagonal() && !rules.diagonal_steps {
+            self.message = "the rules don't allow diagonal steps".to_string();
+        } else if !rules.u_turns && path.steps().last() == Some(&dir.reverse()) {
+            self.message = "the rules don't allow u-turns".to_string();
+        } else if !path.can_step(dir) {
+            self.message = "that leaves the board".to_string();
+        } else {
+            path.step(dir);
+            self.cursor = path.end();
+            self.drawing = Some(path);
+        }
+    }
+
+    fn play_path(&mut self, path: Path) {
+        let left = self.hand(&path).len();
+        if left > 0 {
+            self.message = format!("{left} more stone{} to drop", if left == 1 { "" } else { "s" });
+            return;
+        }
+        match self.state.legal_moves().into_iter().find(|mv| mv.path == path) {
+            Some(mv) => {
+                self.drawing = None;
+                self.apply_move(mv);
+            }
+            None => self.message = format!("{} is not a legal move", path.notation()),
+        }
+    }
+
+    fn engine_move(&mut self) {
+        let mv = self.options.engine.choose_move(&self.state);
+        self.message = format!("{:?} played {mv}", self.state.player_to_move());
+        self.apply_move(mv);
+    }
+
+    fn apply_move(&mut self, mv: Move) {
+        self.options.engine.notify_move(&self.state, &mv);
+        self.history.push(self.state);
+        self.state.apply_move(&mv);
+        self.moves.push(mv);
+        self.cursor = mv.path.end();
+        if let Some(outcome) = self.state.outcome() {
+            self.options.engine.game_over(&self.state, outcome);
+        }
+        self.restart_analysis();
+    }
+
+    // Takes back moves until it is a turn the engine doesn't play automatically.
+    fn take_back(&mut self) {
+        while let Some(previous) = self.history.pop() {
+            self.state = previous;
+            self.moves.pop();
+            if !self.options.automatic.contains(&self.state.player_to_move()) {
+                break;
+            }
+        }
+        self.restart_analysis();
+    }
+
+    fn restart_analysis(&mut self) {
+        self.analysis = None;
+        self.latest = None;
+        if self.analysing && self.state.players().len() == 2 && !self.state.is_over() {
+            self.analysis = Some(Analysis::start(self.state, Arc::clone(&self.options.evaluator)));
+        }
+    }
+
+    fn draw(&self, out: &mut Stdout) -> io::Result<()> {
+        queue!(out, Clear(ClearType::All), MoveTo(0, 0), SetAttribute(Attribute::Bold), Print("Qawale"), SetAttribute(Attribute::Reset))?;
+        queue!(out, Print(format!("  {}", self.state.rules())), MoveTo(0, 1), Print(self.status()))?;
+        self.draw_board(out)?;
+        self.draw_moves(out)?;
+        let mut row = BOARD_TOP + 6;
+        if let Some(path) = &self.drawing {
+            let hand: String = self.hand(path).iter().map(|s| s.to_string()).collect();
+            queue!(out, MoveTo(0, row), Print(format!("path {}  hand {}", path.notation(), if hand.is_empty() { "empty" } else { &hand })))?;
+        }
+        row += 2;
+        queue!(out, MoveTo(0, row), SetAttribute(Attribute::Bold), Print("Analysis"), SetAttribute(Attribute::Reset))?;
+        for (i, line) in self.analysis_lines().iter().enumerate() {
+            queue!(out, MoveTo(0, row + 1 + i as u16), Print(line))?;
+        }
+        row += 5;
+        queue!(out, MoveTo(0, row), SetForegroundColor(Color::DarkGrey), Print(KEYS), ResetColor)?;
+        queue!(out, MoveTo(0, row + 1), Print(&self.message))?;
+        out.flush()
+    }
+
+    fn status(&self) -> String {
+        let stones: Vec<String> = self.state.players().iter()
+            .map(|&p| format!("{} {}", p.letter(), self.state.stones_left(p)))
+            .collect();
+        let status = match self.state.outcome() {
+            Some(Outcome::Win(p)) => format!("{p:?} wins"),
+            Some(Outcome::Draw) => "Draw".to_string(),
+            None => format!("{:?} to move", self.state.player_to_move()),
+        };
+        format!("{status}   stones left: {}", stones.join("  "))
+    }
+
+    fn draw_board(&self, out: &mut Stdout) -> io::Result<()> {
+        let arrows = match &self.drawing {
+            Some(path) => path.arrows(),
+            None => vec![".".to_string(); NUM_PILES],
+        };
+        let on_path = |pos: Position| self.drawing.is_some_and(|path| path.start() == pos || path.iter_positions().any(|p| p == pos));
+        queue!(out, MoveTo(0, BOARD_TOP))?;
+        for x in 0..4 {
+            queue!(out, Print(format!("  {:^width$}", (b'a' + x) as char, width = CELL_WIDTH as usize - 2)))?;
+        }
+        for (pos, pile) in self.state.board().iter_piles() {
+            let (x, y) = (2 + pos.x() as u16 * CELL_WIDTH, BOARD_TOP + 1 + pos.y() as u16);
+            if pos.x() == 0 {
+                queue!(out, MoveTo(0, y), Print(pos.y() + 1))?;
+            }
+            queue!(out, MoveTo(x, y))?;
+            if pos == self.cursor {
+                queue!(out, SetAttribute(Attribute::Reverse))?;
+            } else if on_path(pos) {
+                queue!(out, SetBackgroundColor(Color::DarkBlue))?;
+            }
+            let arrow = &arrows[pos.to_index()];
+            queue!(out, Print(if arrow == "." { " " } else { arrow.as_str() }), Print("["))?;
+            for stone in pile.iter() {
+                queue!(out, SetForegroundColor(stone_color(stone)), Print(stone), SetForegroundColor(Color::Reset))?;
+            }
+            let padding = DISPLAY_WIDTH.saturating_sub(pile.height().max(1) as usize);
+            let empty = if pile.height() == 0 { "." } else { "" };
+            queue!(out, Print(format!("{empty}{}]", ".".repeat(padding))), SetAttribute(Attribute::Reset), ResetColor)?;
+        }
+        Ok(())
+    }
+
+    fn draw_moves(&self, out: &mut Stdout) -> io::Result<()> {
+        queue!(out, MoveTo(MOVES_LEFT, BOARD_TOP), SetAttribute(Attribute::Bold), Print("Moves"), SetAttribute(Attribute::Reset))?;
+        let (_, height) = terminal::size()?;
+        let room = height.saturating_sub(BOARD_TOP + 2).max(1) as usize;
+        let skip = self.moves.len().saturating_sub(room);
+        for (i, (mv, before)) in self.moves.iter().zip(&self.history).enumerate().skip(skip) {
+            let player = before.player_to_move();
+            queue!(out, MoveTo(MOVES_LEFT, BOARD_TOP + 1 + (i - skip) as u16), Print(format!("{:>3}. ", i + 1)),
+                SetForegroundColor(stone_color(player.stone_color())), Print(player.letter()), ResetColor, Print(format!(" {mv}")))?;
+        }
+        Ok(())
+    }
+
+    fn analysis_lines(&self) -> Vec<String> {
+        if self.state.players().len() != 2 {
+            return vec!["only for two player games".to_string()];
+        }
+        if !self.analysing {
+            return vec!["off".to_string()];
+        }
+        if self.state.is_over() {
+            return vec!["the game is over".to_string()];
+        }
+        let Some(result) = &self.latest else { return vec!["thinking...".to_string()] };
+        let pv: Vec<String> = result.pv.iter().map(|mv| mv.to_string()).collect();
+        vec![
+            format!("depth {}  nodes {}", result.depth, result.nodes),
+            format!("score {:+} for {:?}", result.score, self.state.player_to_move()),
+            format!("line  {}", pv.join(" ")),
+        ]
+    }
+}
+
+fn stone_color(stone: Stone) -> Color {
+    match stone {
+        Stone::Neutral => Color::DarkGrey,
+        Stone::Red => Color::Red,
+        Stone::White => Color::White,
+        Stone::Blue => Color::Blue,
+        Stone::Green => Color::Green,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::AgentSpec;
+    use crate::evaluator::LineEvaluator;
+
+    // A UI for the starting position with the cursor on a1, never touching the terminal.
+    fn tui() -> Tui {
+        let options = TuiOptions{engine: AgentSpec::parse("random").unwrap().build(1), automatic: Vec::new(), evaluator: Arc::new(LineEvaluator)};
+        let mut tui = Tui::new(GameState::starting_state(), options);
+        tui.analysing = false;
+        tui.pick_up();
+        tui
+    }
+
+    fn hand(tui: &Tui) -> String {
+        tui.hand(&tui.drawing.unwrap()).iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn drawing_a_path() {
+        let mut tui = tui();
+        assert_eq!(hand(&tui), "nnW");
+        tui.extend_path(Direction::Right);
+        tui.extend_path(Direction::Down);
+        assert_eq!(hand(&tui), "W");
+        assert_eq!(tui.cursor.name(), "b2");
+        assert_eq!(tui.drawing.unwrap().notation(), "a1rd");
+        tui.extend_path(Direction::Right);
+        assert_eq!(hand(&tui), "");
+        tui.extend_path(Direction::Right);
+        assert_eq!(tui.message, "every stone is down, press enter to play");
+        assert_eq!(tui.drawing.unwrap().notation(), "a1rdr");
+    }
+
+    #[test]
+    fn steps_refused() {
+        let mut tui = tui();
+        tui.extend_path(Direction::Up);
+        assert_eq!(tui.message, "that leaves the board");
+        tui.extend_path(Direction::DownRight);
+        assert_eq!(tui.message, "the rules don't allow diagonal steps");
+        tui.extend_path(Direction::Right);
+        tui.extend_path(Direction::Left);
+        assert_eq!(tui.message, "the rules don't allow u-turns");
+        assert_eq!(tui.drawing.unwrap().notation(), "a1r");
+    }
+
+    #[test]
+    fn playing_a_path() {
+        let mut tui = tui();
+        tui.extend_path(Direction::Right);
+        tui.play_path(tui.drawing.unwrap());
+        assert_eq!(tui.message, "2 more stones to drop");
+        tui.extend_path(Direction::Right);
+        tui.play_path(tui.drawing.unwrap());
+        assert_eq!(tui.message, "1 more stone to drop");
+        assert!(tui.moves.is_empty());
+        tui.extend_path(Direction::Down);
+        tui.play_path(tui.drawing.unwrap());
+        assert_eq!(tui.drawing, None);
+        assert_eq!(tui.moves.iter().map(|mv| mv.to_string()).collect::<Vec<_>>(), ["a1rrd"]);
+        assert_eq!(tui.state.position_string(), ",n,n,nn/,,W,/,,,/nn,,,nn R");
+        assert_eq!(tui.cursor.name(), "c2");
+    }
+}
@@ -0,0 +1,48 @@
+use crate::board::Board;
+use crate::game_state::{GameState, PlayerColor};
+use crate::stone::Stone;
+use crate::position::NUM_PILES;
+use crate::stone_pile::PILE_CAPACITY;
+
+// Zobrist hashing with keys derived from a fixed function of the stone's square, height and colour,
+// so hashes are stable between runs and can be stored in files such as the opening book.
+fn key(index: u64) -> u64 {
+    // SplitMix64 finaliser.
+    let mut z = index.wrapping_add(1).wrapping_mul(0x9E3779B97F4A7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+fn stone_key(square: usize, height: usize, stone: Stone) -> u64 {
+    let slot = square * PILE_CAPACITY + height;
+    match stone as usize {
+        // The colours of games of more than two players get keys after all the others, so that
+        // the keys of two player games stay the same.
+        s if s < 3 => key((slot * 3 + s) as u64),
+        s => key((NUM_PILES * PILE_CAPACITY * 3 + slot * 2 + s - 3) as u64),
+    }
+}
+
+const RED_TO_MOVE_KEY: u64 = 0x5851F42D4C957F2D;
+const BLUE_TO_MOVE_KEY: u64 = 0x14057B7EF767814F;
+const GREEN_TO_MOVE_KEY: u64 = 0x2545F4914F6CDD1D;
+
+pub fn board_hash(board: &Board) -> u64 {
+    let mut hash = 0;
+    for (pos, pile) in board.iter_piles() {
+        for (height, stone) in pile.iter().enumerate() {
+            hash ^= stone_key(pos.to_index(), height, stone);
+        }
+    }
+    hash
+}
+
+pub fn state_hash(state: &GameState) -> u64 {
+    match state.player_to_move() {
+        PlayerColor::White => board_hash(state.board()),
+        PlayerColor::Red => board_hash(state.board()) ^ RED_TO_MOVE_KEY,
+        PlayerColor::Blue => board_hash(state.board()) ^ BLUE_TO_MOVE_KEY,
+        PlayerColor::Green => board_hash(state.board()) ^ GREEN_TO_MOVE_KEY,
+    }
+}
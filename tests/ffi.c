@@ -0,0 +1,114 @@
+/* Plays through the C interface as an embedding program would. Exits non-zero on the first check
+   that fails, after printing it. */
+#include <stdio.h>
+#include <string.h>
+#include <time.h>
+
+#include "qawale.h"
+
+#define CHECK(condition)                                                        \
+    do {                                                                        \
+        if (!(condition)) {                                                     \
+            const char *error = qawale_last_error();                            \
+            fprintf(stderr, "%s:%d: check failed: %s (last error: %s)\n",       \
+                    __FILE__, __LINE__, #condition, error ? error : "none");    \
+            return 1;                                                           \
+        }                                                                       \
+    } while (0)
+
+static int check_moves(void) {
+    QawaleGame *game = qawale_game_new(NULL);
+    CHECK(game != NULL);
+    CHECK(qawale_game_to_move(game) == 'W');
+    char *position = qawale_game_position(game);
+    CHECK(strcmp(position, "nn,,,nn/,,,/,,,/nn,,,nn W") == 0);
+    qawale_string_free(position);
+
+    size_t count = 0;
+    char *moves = qawale_game_legal_moves(game, &count);
+    CHECK(count > 0);
+    CHECK(strstr(moves, "a1rrr") != NULL);
+    qawale_string_free(moves);
+
+    CHECK(qawale_game_apply_move(game, "a1rrr") == 0);
+    CHECK(qawale_game_to_move(game) == 'R');
+    /* An illegal move is refused, with a reason, and the game is left as it was. */
+    CHECK(qawale_game_apply_move(game, "a1uuuu") == -1);
+    CHECK(qawale_last_error() != NULL);
+    CHECK(qawale_game_to_move(game) == 'R');
+    CHECK(qawale_game_outcome(game) == QAWALE_OUTCOME_ONGOING);
+    qawale_game_free(game);
+    return 0;
+}
+
+static int check_errors(void) {
+    CHECK(qawale_game_new("no-such-rules") == NULL);
+    CHECK(strstr(qawale_last_error(), "no-such-rules") != NULL);
+    CHECK(qawale_game_from_position("not a position", NULL) == NULL);
+    CHECK(qawale_game_from_position(NULL, NULL) == NULL);
+    /* Well written, but with far more neutral stones than the rules allow. */
+    CHECK(qawale_game_from_position("nnnnnnnnnnnnnnnnnnnnnnnn,,,/,,,/,,,/,,, W", NULL) == NULL);
+    CHECK(strstr(qawale_last_error(), "neutral stones") != NULL);
+    CHECK(qawale_game_apply_move(NULL, "a1rrr") == -1);
+    qawale_game_free(NULL);
+    qawale_string_free(NULL);
+    return 0;
+}
+
+static int check_winner(void) {
+    QawaleGame *game = qawale_game_from_position("nnW,W,W,nn/R,R,R,/,,,/nn,,,nn W", "standard");
+    CHECK(game != NULL);
+    QawaleSearchResult result;
+    CHECK(qawale_game_search(game, 2, 0, &result) == 0);
+    CHECK(result.best_move != NULL);
+    CHECK(result.nodes > 0);
+    CHECK(qawale_game_apply_move(game, result.best_move) == 0);
+    qawale_string_free(result.best_move);
+    CHECK(qawale_game_outcome(game) == QAWALE_OUTCOME_WHITE_WINS);
+
+    size_t count = 1;
+    char *moves = qawale_game_legal_moves(game, &count);
+    CHECK(count == 0 && strcmp(moves, "") == 0);
+    qawale_string_free(moves);
+    CHECK(qawale_game_search(game, 2, 0, &result) == 0);
+    CHECK(result.best_move == NULL);
+    qawale_game_free(game);
+    return 0;
+}
+
+/* A search of a four player game keeps to its time limit, however deep it may go. */
+static int check_multiplayer(void) {
+    QawaleGame *game = qawale_game_new("four-player");
+    CHECK(game != NULL);
+    QawaleSearchResult result;
+    time_t start = time(NULL);
+    CHECK(qawale_game_search(game, 12, 200, &result) == 0);
+    CHECK(time(NULL) - start <= 2);
+    CHECK(result.best_move != NULL && result.depth >= 1 && result.depth < 12);
+    qawale_string_free(result.best_move);
+    qawale_game_free(game);
+    return 0;
+}
+
+/* Plays a whole game with a time limit on each search, as an app's bot would. */
+static int check_game(void) {
+    QawaleGame *game = qawale_game_new("standard");
+    CHECK(game != NULL);
+    while (qawale_game_outcome(game) == QAWALE_OUTCOME_ONGOING) {
+        QawaleSearchResult result;
+        CHECK(qawale_game_search(game, 3, 50, &result) == 0);
+        CHECK(result.best_move != NULL && result.depth >= 1);
+        CHECK(qawale_game_apply_move(game, result.best_move) == 0);
+        qawale_string_free(result.best_move);
+    }
+    qawale_game_free(game);
+    return 0;
+}
+
+int main(void) {
+    if (check_moves() || check_errors() || check_winner() || check_multiplayer() || check_game()) {
+        return 1;
+    }
+    printf("ok\n");
+    return 0;
+}
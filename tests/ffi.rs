@@ -0,0 +1,56 @@
+// Builds tests/ffi.c against the shared library and the generated header, and runs it.
+
+use std::env::consts::{DLL_PREFIX, DLL_SUFFIX};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// The test runs from target/<profile>/deps, where cargo builds the shared library for it. The copy
+// in target/<profile> is only updated by `cargo build`, so may be for other features.
+#[cfg(not(feature = "python"))]
+fn library_dir(_manifest: &Path) -> PathBuf {
+    let exe = std::env::current_exe().expect("the test should know where it is");
+    exe.parent().expect("the test should be in target/<profile>/deps").to_path_buf()
+}
+
+// With the `python` feature the library is a Python extension module, which leaves the Python
+// interpreter's symbols for the interpreter loading it to supply, so C programs can't link it.
+// The C program gets a library built without any features instead, in a target directory of its
+// own. This builds the dependencies again the first time, but tests the same interface.
+#[cfg(feature = "python")]
+fn library_dir(manifest: &Path) -> PathBuf {
+    let target = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("ffi-target");
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let status = Command::new(&cargo)
+        .args(["build", "--quiet", "--lib", "--no-default-features", "--manifest-path"])
+        .arg(manifest.join("Cargo.toml"))
+        .arg("--target-dir").arg(&target)
+        .status()
+        .unwrap_or_else(|e| panic!("{cargo} should run: {e}"));
+    assert!(status.success(), "the library should build without the python feature");
+    target.join("debug")
+}
+
+#[test]
+fn c_program_plays_through_the_interface() {
+    let manifest = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let lib_dir = &library_dir(&manifest);
+    assert!(lib_dir.join(format!("{DLL_PREFIX}qawale{DLL_SUFFIX}")).exists(), "the shared library should be built in {}", lib_dir.display());
+
+    let program = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("ffi");
+    let compiler = std::env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    let status = Command::new(&compiler)
+        .arg(manifest.join("tests/ffi.c"))
+        .arg("-I").arg(manifest.join("include"))
+        .arg("-L").arg(lib_dir)
+        .arg(format!("-Wl,-rpath,{}", lib_dir.display()))
+        .args(["-Wall", "-Werror", "-lqawale", "-o"])
+        .arg(&program)
+        .status()
+        .unwrap_or_else(|e| panic!("{compiler} should run: {e}"));
+    assert!(status.success(), "tests/ffi.c should compile");
+
+    // Cargo's library path leads to target/<profile> first, ahead of the rpath.
+    let output = Command::new(&program).env_remove("LD_LIBRARY_PATH").output().expect("the C program should run");
+    assert!(output.status.success(), "the C program failed:\n{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "ok\n");
+}
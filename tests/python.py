@@ -0,0 +1,49 @@
+# Plays through the Python bindings; run by tests/python.rs with the extension module on the path.
+import qawale
+
+
+def raises(f):
+    try:
+        f()
+    except ValueError:
+        return True
+    return False
+
+
+state = qawale.GameState()
+assert state.to_move == "W" and len(state.legal_moves()) == 40
+state.play("a1rrr")
+assert state.to_move == "R" and state.moves_left == 15
+assert qawale.GameState(state.position) == state
+
+result = qawale.search(state, depth=2)
+assert result.best_move in state.legal_moves() and result.depth == 2
+assert qawale.search(state, depth=0).best_move is not None
+assert qawale.search(state, depth=3, time=0.5).best_move is not None
+result = qawale.mcts(state, iterations=50)
+assert result.best_move in state.legal_moves() and sum(result.visits.values()) > 0
+
+three = qawale.GameState(rules="three-player")
+assert three.players == ["W", "R", "B"]
+assert raises(lambda: qawale.search(three))
+assert raises(lambda: qawale.mcts(three))
+assert raises(lambda: qawale.Agent("search:depth=2").choose_move(three))
+assert qawale.Agent("paranoid:depth=1").choose_move(three) in three.legal_moves()
+
+agent = qawale.Agent("greedy")
+while not state.is_over:
+    move = agent.choose_move(state)
+    agent.notify_move(state, move)
+    state.play(move)
+assert state.outcome is not None
+
+assert raises(lambda: qawale.GameState("nnnnnnnnnnnnnnnnnnnnnnnn,,,/,,,/,,,/,,, W"))
+assert raises(lambda: qawale.GameState(rules="standard:players=9"))
+assert raises(lambda: qawale.search(qawale.GameState(), time=-1))
+assert raises(lambda: qawale.search(qawale.GameState(), time=float("nan")))
+assert raises(lambda: qawale.GameState().play("e1rrr"))
+assert raises(lambda: qawale.Agent("no-such-agent"))
+
+samples = qawale.selfplay(games=1, iterations=20, threads=1)
+assert samples and len(samples[0].encode()) > 0
+print("ok")
@@ -0,0 +1,32 @@
+// Runs tests/python.py against the shared library, which with the `python` feature is the
+// extension module, and needs python3 on the path.
+#![cfg(feature = "python")]
+
+use std::env::consts::{DLL_PREFIX, DLL_SUFFIX};
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+#[test]
+fn python_script_plays_through_the_bindings() {
+    let manifest = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    // As in tests/ffi.rs, the shared library for the test's features is built next to it.
+    let exe = std::env::current_exe().expect("the test should know where it is");
+    let library = exe.parent().expect("the test should be in target/<profile>/deps").join(format!("{DLL_PREFIX}qawale{DLL_SUFFIX}"));
+    assert!(library.exists(), "the shared library should be built at {}", library.display());
+
+    // Python imports the module by its own name for extension modules.
+    let module_dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("python");
+    fs::create_dir_all(&module_dir).unwrap();
+    let module = if cfg!(windows) { "qawale.pyd" } else { "qawale.so" };
+    fs::copy(&library, module_dir.join(module)).expect("the shared library should copy");
+
+    let python = std::env::var("PYTHON").unwrap_or_else(|_| "python3".to_string());
+    let output = Command::new(&python)
+        .arg(manifest.join("tests/python.py"))
+        .env("PYTHONPATH", &module_dir)
+        .output()
+        .unwrap_or_else(|e| panic!("{python} should run: {e}"));
+    assert!(output.status.success(), "tests/python.py failed:\n{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "ok\n");
+}
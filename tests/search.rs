@@ -0,0 +1,38 @@
+// Checks that move ordering saves nodes, and that neither it nor the transposition table changes
+// the result.
+use qawale::{GameState, LineEvaluator, Search};
+
+fn after(moves: &[&str]) -> GameState {
+    let mut state = GameState::starting_state();
+    for notation in moves {
+        let mv = state.parse_move(notation).unwrap();
+        state.apply_move(&mv);
+    }
+    state
+}
+
+#[test]
+fn ordering_searches_fewer_nodes_for_the_same_score() {
+    let state = after(&["a1rrr"]);
+    let ordered = Search::new(&LineEvaluator).search(&state, 3);
+    let unordered = Search::new(&LineEvaluator).with_ordering(false).search(&state, 3);
+    assert_eq!(ordered.score, unordered.score);
+    assert!(ordered.nodes < unordered.nodes, "ordered {} nodes, unordered {}", ordered.nodes, unordered.nodes);
+}
+
+#[test]
+fn the_table_finds_the_same_score() {
+    let state = after(&["a1rrr"]);
+    let tabled = Search::new(&LineEvaluator).search(&state, 3);
+    let untabled = Search::new(&LineEvaluator).with_table_size(0).search(&state, 3);
+    assert_eq!(tabled.score, untabled.score);
+}
+
+#[test]
+fn a_tiny_table_still_finds_the_same_score() {
+    let state = after(&["a1rrr", "d1ldru"]);
+    let full = Search::new(&LineEvaluator).search(&state, 3);
+    let tiny = Search::new(&LineEvaluator).with_table_size(16).search(&state, 3);
+    assert_eq!(full.score, tiny.score);
+    assert_eq!(full.best_move.is_some(), tiny.best_move.is_some());
+}
@@ -0,0 +1,91 @@
+// Round-trips each serialized type through JSON, and checks malformed strings are refused.
+#![cfg(feature = "serde")]
+
+use qawale::stone_pile::StonePile;
+use qawale::{Board, Direction, GameState, Move, Path, PlayerColor, Position, Rules, Stone};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt::Debug;
+
+// Serializes the value, checks it is written as expected, and reads it back.
+fn round_trip<T: Serialize + DeserializeOwned + PartialEq + Debug>(value: T, json: &str) {
+    assert_eq!(serde_json::to_string(&value).unwrap(), json);
+    assert_eq!(serde_json::from_str::<T>(json).unwrap(), value);
+}
+
+fn refused<T: DeserializeOwned + Debug>(json: &str) {
+    assert!(serde_json::from_str::<T>(json).is_err(), "{json} should be refused");
+}
+
+#[test]
+fn stones() {
+    round_trip(Stone::Neutral, r#""n""#);
+    round_trip(Stone::White, r#""W""#);
+    round_trip(Stone::Green, r#""G""#);
+    refused::<Stone>(r#""X""#);
+    refused::<Stone>(r#""WW""#);
+    refused::<Stone>("1");
+}
+
+#[test]
+fn players() {
+    round_trip(PlayerColor::Red, r#""R""#);
+    round_trip(PlayerColor::Blue, r#""B""#);
+    refused::<PlayerColor>(r#""n""#);
+}
+
+#[test]
+fn directions() {
+    for (direction, letter) in [(Direction::Up, "u"), (Direction::Down, "d"), (Direction::Left, "l"), (Direction::Right, "r")] {
+        round_trip(direction, &format!("{letter:?}"));
+    }
+    refused::<Direction>(r#""x""#);
+}
+
+#[test]
+fn positions() {
+    round_trip(Position::<4>::parse("a1").unwrap(), r#""a1""#);
+    round_trip(Position::<4>::parse("d4").unwrap(), r#""d4""#);
+    refused::<Position<4>>(r#""e1""#);
+    refused::<Position<4>>(r#""a""#);
+}
+
+#[test]
+fn piles() {
+    round_trip(StonePile::parse("nnW").unwrap(), r#""nnW""#);
+    round_trip(StonePile::parse("").unwrap(), r#""""#);
+    refused::<StonePile>(r#""nnx""#);
+}
+
+#[test]
+fn boards() {
+    let board = *GameState::starting_state().board();
+    round_trip(board, r#""nn,,,nn/,,,/,,,/nn,,,nn""#);
+    refused::<Board>(r#""nn,,,nn/,,,/,,,""#);
+}
+
+#[test]
+fn paths() {
+    round_trip(Path::<4>::parse("a1rrr", Rules::default().topology).unwrap(), r#""a1rrr""#);
+    let torus = Rules::preset("torus").unwrap();
+    round_trip(Path::<4>::parse("a1lll", torus.topology).unwrap(), r#""torus:a1lll""#);
+    refused::<Path<4>>(r#""a1lll""#);
+    refused::<Path<4>>(r#""torus:a1xx""#);
+}
+
+#[test]
+fn moves() {
+    let state = GameState::starting_state();
+    for mv in state.legal_moves() {
+        let json = serde_json::to_string(&mv).unwrap();
+        assert_eq!(serde_json::from_str::<Move>(&json).unwrap(), mv);
+    }
+    let mv = state.parse_move("a1rrr").unwrap();
+    round_trip(mv, r#""a1rrr W""#);
+    let torus = GameState::starting_state_with(Rules::preset("torus").unwrap()).unwrap();
+    let mv = torus.parse_move("a1lll").unwrap();
+    round_trip(mv, r#""torus:a1lll W""#);
+    refused::<Move>(r#""a1rrr""#);
+    refused::<Move>(r#""a1rrr X""#);
+    refused::<Move>(r#""torus:a1rrr WW""#);
+}
@@ -0,0 +1,155 @@
+// Runs the JSON API on a free local port and exercises each endpoint over plain HTTP.
+#![cfg(feature = "server")]
+
+use qawale::server::{self, ServerConfig};
+use qawale::LineEvaluator;
+use serde_json::{json, Value};
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Starts a server on a free local port with the given workers, queue and timeout, returning its
+// address once it accepts connections.
+fn start(threads: usize, max_queued: usize, timeout: Duration) -> String {
+    let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let config = ServerConfig{address: format!("127.0.0.1:{port}"), threads, max_queued, timeout, max_depth: 8};
+    let address = config.address.clone();
+    thread::spawn(move || server::serve(&config, &LineEvaluator).unwrap());
+    let start = Instant::now();
+    while TcpStream::connect(&address).is_err() {
+        assert!(start.elapsed() < Duration::from_secs(5), "the server should start");
+        thread::sleep(Duration::from_millis(10));
+    }
+    address
+}
+
+// The address of a server shared by most tests, started on first use.
+fn address() -> &'static str {
+    static ADDRESS: OnceLock<String> = OnceLock::new();
+    ADDRESS.get_or_init(|| start(2, 16, Duration::from_secs(10)))
+}
+
+// Sends a request with the body as it is, leaving the answer to be read.
+fn send(address: &str, method: &str, path: &str, body: &str) -> TcpStream {
+    let mut stream = send_head(address, method, path, body.len());
+    stream.write_all(body.as_bytes()).unwrap();
+    stream
+}
+
+// Sends the head of a request whose body is `length` bytes, without the body.
+fn send_head(address: &str, method: &str, path: &str, length: usize) -> TcpStream {
+    let mut stream = TcpStream::connect(address).unwrap();
+    write!(stream, "{method} {path} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Length: {length}\r\n\r\n").unwrap();
+    stream
+}
+
+// Sends the rest of the body, returning the status and the JSON answer.
+fn finish(mut stream: TcpStream, rest: &str) -> (u16, Value) {
+    stream.write_all(rest.as_bytes()).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").expect("the response should have a body");
+    let status = head.split(' ').nth(1).and_then(|s| s.parse().ok()).expect("the response should have a status");
+    (status, serde_json::from_str(body).expect("the body should be JSON"))
+}
+
+// Sends a request with the body as it is to the shared server.
+fn request(method: &str, path: &str, body: &str) -> (u16, Value) {
+    finish(send(address(), method, path, body), "")
+}
+
+fn post(path: &str, body: Value) -> (u16, Value) {
+    request("POST", path, &body.to_string())
+}
+
+#[test]
+fn legal_moves() {
+    let (status, body) = post("/legal-moves", json!({}));
+    assert_eq!(status, 200);
+    assert_eq!(body["position"], "nn,,,nn/,,,/,,,/nn,,,nn W");
+    assert_eq!(body["legal_moves"].as_array().unwrap().len(), 40);
+}
+
+#[test]
+fn apply() {
+    let (status, body) = post("/apply", json!({"moves": ["a1rrr"], "move": "d1ldru"}));
+    assert_eq!(status, 200);
+    assert_eq!(body["to_move"], "W");
+    assert_eq!(body["stones_left"], json!({"W": 7, "R": 7}));
+}
+
+#[test]
+fn bestmove() {
+    let (status, body) = post("/bestmove", json!({"moves": ["a1rrr"], "depth": 2}));
+    assert_eq!(status, 200);
+    assert_eq!(body["depth"], 2);
+    assert!(body["move"].is_string());
+}
+
+#[test]
+fn bestmove_keeps_to_the_time_limit_with_more_players() {
+    let start = Instant::now();
+    let (status, body) = post("/bestmove", json!({"rules": "four-player", "depth": 8, "time_ms": 200}));
+    assert_eq!(status, 200);
+    assert!(body["move"].is_string());
+    assert!(start.elapsed() < Duration::from_secs(2), "took {:?}", start.elapsed());
+}
+
+#[test]
+fn analyze() {
+    let (status, body) = post("/analyze", json!({"moves": ["a1rrr"], "depth": 2, "multipv": 2}));
+    assert_eq!(status, 200);
+    assert_eq!(body["lines"].as_array().unwrap().len(), 2);
+    assert!(body["threats"]["safe"].is_array());
+}
+
+#[test]
+fn bad_requests() {
+    assert_eq!(request("POST", "/legal-moves", "{not json").0, 400);
+    assert_eq!(post("/legal-moves", json!([1, 2])).0, 400);
+    assert_eq!(post("/apply", json!({"move": "a1uuu"})).0, 400);
+    assert_eq!(post("/legal-moves", json!({"position": "nnnnnnnnnnnnnnnnnnnnnnnn,,,/,,,/,,,/,,, W"})).0, 400);
+    assert_eq!(request("GET", "/legal-moves", "").0, 405);
+    assert_eq!(post("/nowhere", json!({})).0, 404);
+    // The workers are still there to answer.
+    assert_eq!(post("/legal-moves", json!({})).0, 200);
+}
+
+// A request whose body is slow to arrive holds its worker until the body is sent. Small bodies
+// are read before the request reaches a worker, so this one is padded.
+fn slow_body() -> String {
+    format!("{{}}{}", " ".repeat(2048))
+}
+
+#[test]
+fn busy() {
+    let address = start(1, 0, Duration::from_secs(10));
+    let slow = send_head(&address, "POST", "/legal-moves", slow_body().len());
+    thread::sleep(Duration::from_millis(200));
+    // With the only worker busy and no queue, the next request is turned away at once.
+    let (status, body) = finish(send(&address, "POST", "/legal-moves", "{}"), "");
+    assert_eq!(status, 503);
+    assert_eq!(body["error"], "the server is busy");
+    assert_eq!(finish(slow, &slow_body()).0, 200);
+    // The worker answers the slow request a moment before it waits for the next.
+    let start = Instant::now();
+    while finish(send(&address, "POST", "/legal-moves", "{}"), "").0 != 200 {
+        assert!(start.elapsed() < Duration::from_secs(5), "the worker should be free again");
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn timed_out_in_the_queue() {
+    let address = start(1, 1, Duration::from_millis(300));
+    let slow = send_head(&address, "POST", "/legal-moves", slow_body().len());
+    thread::sleep(Duration::from_millis(200));
+    let queued = send(&address, "POST", "/legal-moves", "{}");
+    thread::sleep(Duration::from_millis(500));
+    assert_eq!(finish(slow, &slow_body()).0, 200);
+    let (status, body) = finish(queued, "");
+    assert_eq!(status, 503);
+    assert_eq!(body["error"], "timed out waiting for a free worker");
+}